@@ -0,0 +1,104 @@
+use std::{
+    env,
+    fmt::Write,
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// Programs start at 0x200, and with `small-ram` memory ends at 0x600
+const MAX_PROGRAM_LEN: usize = 0x400;
+/// Names are cut down to fit on a line of the menu, after the arrow
+const NAME_LEN: usize = 14;
+/// A button that doesn't press a key, the same as `board::NO_KEY`
+const NO_KEY: u8 = 0xFF;
+
+fn main() {
+    // Every .ch8 file in the directory is built in, in order of name
+    let dir = PathBuf::from(
+        env::var_os("CHIP8_ROMS").expect("CHIP8_ROMS should be the path to a directory of ROMs"),
+    );
+    println!("cargo:rerun-if-env-changed=CHIP8_ROMS");
+    println!("cargo:rerun-if-changed={}", dir.display());
+    let mut paths: Vec<PathBuf> = fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| {
+            path.extension()
+                .is_some_and(|extension| extension.eq_ignore_ascii_case("ch8"))
+        })
+        .collect();
+    paths.sort();
+    assert!(!paths.is_empty(), "{} has no .ch8 ROMs", dir.display());
+
+    // All the programs are in one array, as each has to have its own type otherwise
+    let mut data = String::new();
+    let mut starts = String::from("0");
+    let mut names = String::new();
+    let mut layouts = String::new();
+    let mut len = 0;
+    for path in &paths {
+        println!("cargo:rerun-if-changed={}", path.display());
+        let program = fs::read(path).unwrap();
+        assert!(
+            program.len() <= MAX_PROGRAM_LEN,
+            "{} is too big to fit in memory",
+            path.display()
+        );
+        for byte in &program {
+            write!(data, "{byte},").unwrap();
+        }
+        len += program.len();
+        write!(starts, ",{len}").unwrap();
+
+        let mut name = path
+            .file_stem()
+            .unwrap()
+            .to_string_lossy()
+            .to_ascii_uppercase()
+            .into_bytes();
+        name.resize(NAME_LEN, b' ');
+        write!(names, "{name:?},").unwrap();
+        write!(layouts, "{},", layout(path)).unwrap();
+    }
+
+    let count = paths.len();
+    let roms = format!(
+        "progmem! {{
+    static progmem ROM_DATA: [u8; {len}] = [{data}];
+    static progmem ROM_STARTS: [u16; {}] = [{starts}];
+    static progmem ROM_NAMES: [[u8; {NAME_LEN}]; {count}] = [{names}];
+    static progmem ROM_LAYOUTS: [[u8; 11]; {count}] = [{layouts}];
+}}
+const ROM_COUNT: usize = {count};
+",
+        count + 1,
+    );
+    let out_dir = PathBuf::from(env::var_os("OUT_DIR").unwrap());
+    fs::write(out_dir.join("roms.rs"), roms).unwrap();
+}
+
+/// The keys from the ROM's `.keys` file, or the default layout if it doesn't have one
+fn layout(rom: &Path) -> String {
+    let path = rom.with_extension("keys");
+    let Ok(text) = fs::read_to_string(&path) else {
+        return "DEFAULT_LAYOUT".into();
+    };
+    println!("cargo:rerun-if-changed={}", path.display());
+
+    let keys: Vec<&str> = text.split_whitespace().collect();
+    assert!(
+        matches!(keys.len(), 6 | 11),
+        "{} should have 6 or 11 keys",
+        path.display()
+    );
+    let mut layout = [NO_KEY; 11];
+    for (slot, key) in layout.iter_mut().zip(keys) {
+        if key != "-" {
+            *slot = u8::from_str_radix(key, 16)
+                .ok()
+                .filter(|&key| key < 16)
+                .unwrap_or_else(|| panic!("{key:?} in {} isn't a key", path.display()));
+        }
+    }
+    format!("{layout:?}")
+}
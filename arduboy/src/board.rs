@@ -0,0 +1,186 @@
+use arduino_hal::{
+    pac::{TC0, TC3},
+    port::{
+        Pin,
+        mode::{Input, Output, PullUp},
+    },
+};
+use avr_device::interrupt::Mutex;
+use core::cell::Cell;
+use rs_chip8_embedded::{Beeper, Keypad, Monotonic, Rng};
+
+/// In a layout, a button that doesn't press a key
+pub const NO_KEY: u8 = 0xFF;
+
+/// Up, Down, Left, Right, A, and B press 2, 8, 4, 6, 5, and F,
+/// where 2, 8, 4, and 6 are the arrows on the COSMAC VIP keypad
+pub const DEFAULT_LAYOUT: [u8; 11] = [
+    0x2, 0x8, 0x4, 0x6, 0x5, 0xF, NO_KEY, NO_KEY, NO_KEY, NO_KEY, NO_KEY,
+];
+
+/// Timer 0 runs at 16 MHz / 64, so each tick is 4 µs, and it counts 250 ticks a millisecond
+const MICROS_PER_TICK: u64 = 4;
+const TICKS_PER_MILLI: u8 = 250;
+
+/// Milliseconds since [`Clock::new`], counted by timer 0's interrupt
+static MILLIS: Mutex<Cell<u32>> = Mutex::new(Cell::new(0));
+
+#[avr_device::interrupt(atmega32u4)]
+fn TIMER0_COMPA() {
+    avr_device::interrupt::free(|cs| {
+        let millis = MILLIS.borrow(cs);
+        millis.set(millis.get() + 1);
+    });
+}
+
+/// Opposite directions held together, which a game never needs, to save or load
+#[derive(Clone, Copy, PartialEq)]
+pub enum Chord {
+    /// Up and Down
+    Save,
+    /// Left and Right
+    Load,
+}
+
+/// Up, Down, Left, Right, A, and B, each pulling its pin low when pressed
+pub struct Buttons {
+    pub pins: [Pin<Input<PullUp>>; 6],
+    /// The keys pressed by Up, Down, Left, Right, A, and B, then by Up, Down, Left, Right, and A
+    /// while B is held
+    ///
+    /// If any of the last 5 are set, B is only held down for them, and presses nothing itself,
+    /// so up to 10 keys can be used.
+    pub layout: [u8; 11],
+    held_chord: Option<Chord>,
+    /// A chord that's been pressed, until it's taken
+    pressed_chord: Option<Chord>,
+}
+
+impl Buttons {
+    /// The buttons, in the default layout
+    pub fn new(pins: [Pin<Input<PullUp>>; 6]) -> Self {
+        Self {
+            pins,
+            layout: DEFAULT_LAYOUT,
+            held_chord: None,
+            pressed_chord: None,
+        }
+    }
+
+    /// The chord pressed since the last time this was called, if any
+    pub fn take_chord(&mut self) -> Option<Chord> {
+        self.pressed_chord.take()
+    }
+}
+
+impl Keypad for Buttons {
+    fn scan(&mut self) -> u16 {
+        let held = self.pins.each_ref().map(|pin| pin.is_low());
+        let chord = if held[0] && held[1] {
+            Some(Chord::Save)
+        } else if held[2] && held[3] {
+            Some(Chord::Load)
+        } else {
+            None
+        };
+        // Holding a chord only counts once, and its buttons don't press keys
+        if chord.is_some() && chord != self.held_chord {
+            self.pressed_chord = chord;
+        }
+        self.held_chord = chord;
+        if chord.is_some() {
+            return 0;
+        }
+
+        let keys = if held[5] && self.layout[6..].iter().any(|&key| key != NO_KEY) {
+            &self.layout[6..]
+        } else {
+            &self.layout[..6]
+        };
+        held.iter()
+            .zip(keys)
+            .filter(|&(&held, &key)| held && key != NO_KEY)
+            .fold(0, |held_keys, (_, key)| held_keys | 1 << key)
+    }
+
+    fn pause_requested(&mut self) -> bool {
+        self.pressed_chord.is_some()
+    }
+}
+
+/// Counts microseconds with timer 0, which interrupts every millisecond
+///
+/// Interrupts have to be enabled for it to count.
+pub struct Clock(TC0);
+
+impl Clock {
+    pub fn new(tc0: TC0) -> Self {
+        // Count up to the compare value, then start again from 0
+        tc0.tccr0a().write(|w| w.wgm0().ctc());
+        tc0.ocr0a().write(|w| w.set(TICKS_PER_MILLI - 1));
+        tc0.tccr0b().write(|w| w.cs0().prescale_64());
+        tc0.timsk0().write(|w| w.ocie0a().set_bit());
+        Self(tc0)
+    }
+}
+
+impl Monotonic for Clock {
+    fn now_micros(&mut self) -> u64 {
+        avr_device::interrupt::free(|cs| {
+            let mut millis = MILLIS.borrow(cs).get();
+            let mut ticks = self.0.tcnt0().read().bits();
+            // A millisecond that ended after interrupts were turned off hasn't been counted yet
+            if self.0.tifr0().read().ocf0a().bit_is_set() {
+                millis += 1;
+                ticks = self.0.tcnt0().read().bits();
+            }
+            millis as u64 * 1000 + ticks as u64 * MICROS_PER_TICK
+        })
+    }
+}
+
+/// Timer 3 runs at 16 MHz / 8
+const TIMER_3_HZ: u32 = 2_000_000;
+
+/// The speaker, with timer 3 toggling one of its pins to make a square wave
+/// while the other is held low
+pub struct Buzzer {
+    tc3: TC3,
+    pin: Pin<Output>,
+}
+
+impl Buzzer {
+    /// A buzzer that beeps at `frequency` Hz, from 16 Hz up
+    pub fn new(tc3: TC3, pin: Pin<Output>, frequency: u32) -> Self {
+        // Count up to the compare value, then start again from 0, toggling the pin each time
+        tc3.ocr3a()
+            .write(|w| w.set((TIMER_3_HZ / 2 / frequency - 1) as u16));
+        tc3.tccr3b()
+            .write(|w| w.wgm3().bits(0b01).cs3().prescale_8());
+        Self { tc3, pin }
+    }
+}
+
+impl Beeper for Buzzer {
+    fn set_beeping(&mut self, beeping: bool) {
+        if beeping {
+            self.tc3.tccr3a().write(|w| w.com3a().match_toggle());
+        } else {
+            // Leave the pin low, so no current goes through the speaker
+            self.tc3.tccr3a().write(|w| w.com3a().disconnected());
+            self.pin.set_low();
+        }
+    }
+}
+
+/// A 16-bit xorshift generator, as the ATmega32u4 has no hardware one
+pub struct XorShift(pub u16);
+
+impl Rng for XorShift {
+    fn random_byte(&mut self) -> u8 {
+        self.0 ^= self.0 << 7;
+        self.0 ^= self.0 >> 9;
+        self.0 ^= self.0 << 8;
+        self.0 as u8
+    }
+}
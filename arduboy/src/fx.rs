@@ -0,0 +1,186 @@
+use arduino_hal::{
+    Spi,
+    port::{Pin, mode::Output},
+};
+use core::cell::RefCell;
+use embedded_hal::spi::{ErrorType, SpiBus};
+use rs_chip8_core::{MachineState, PROGRAM_ADDRESS};
+
+/// ROMs are kept in the last 64 KiB of the 16 MiB chip, out of the way of the FX's own games
+const CATALOG_ADDRESS: u32 = 0xFF_0000;
+/// The catalog starts with this, then has an entry for each ROM
+const MAGIC: [u8; 4] = *b"C8FX";
+/// Each entry is the ROM's name, then its length in 2 big endian bytes
+pub const NAME_LEN: usize = 14;
+const ENTRY_LEN: u32 = NAME_LEN as u32 + 2;
+/// After the catalog's first 4 KiB sector, each ROM has a slot as big as a program can be
+const SLOTS_ADDRESS: u32 = CATALOG_ADDRESS + 0x1000;
+const SLOT_LEN: u32 = 0x400;
+pub const MAX_ROMS: usize = 60;
+
+/// Flash is written a 256 byte page at a time
+const PAGE_LEN: usize = 0x100;
+/// What erased flash reads as
+const ERASED: u8 = 0xFF;
+
+const PAGE_PROGRAM: u8 = 0x02;
+const READ: u8 = 0x03;
+const READ_STATUS: u8 = 0x05;
+const WRITE_ENABLE: u8 = 0x06;
+const JEDEC_ID: u8 = 0x9F;
+const RELEASE_POWER_DOWN: u8 = 0xAB;
+
+type Error = <Spi as ErrorType>::Error;
+
+/// The Arduboy FX's SPI flash chip, on the same bus as the OLED, with a catalog of ROMs
+///
+/// The catalog is made the first time a ROM is saved, as long as nothing else is using its space.
+pub struct Fx<'a> {
+    spi: &'a RefCell<Spi>,
+    cs: Pin<Output>,
+    rom_count: usize,
+    /// Whether the catalog's space is free or has the catalog in it, so ROMs can be saved to it
+    usable: bool,
+}
+
+impl<'a> Fx<'a> {
+    /// The flash chip, or `None` if this Arduboy doesn't have one
+    pub fn new(spi: &'a RefCell<Spi>, cs: Pin<Output>) -> Result<Option<Self>, Error> {
+        let mut fx = Self {
+            spi,
+            cs,
+            rom_count: 0,
+            usable: false,
+        };
+        // The FX's bootloader leaves the chip powered down
+        fx.transaction(&[RELEASE_POWER_DOWN], &[], &mut [])?;
+        arduino_hal::delay_us(3);
+        let mut id = [0; 3];
+        fx.transaction(&[JEDEC_ID], &[], &mut id)?;
+        // Without a chip nothing drives MISO, so the manufacturer reads as all 1s or all 0s
+        if matches!(id[0], 0x00 | 0xFF) {
+            return Ok(None);
+        }
+
+        let mut magic = [0; 4];
+        fx.read(CATALOG_ADDRESS, &mut magic)?;
+        fx.usable = magic == MAGIC || magic == [ERASED; 4];
+        if magic == MAGIC {
+            while fx.rom_count < MAX_ROMS && fx.len(fx.rom_count)?.is_some() {
+                fx.rom_count += 1;
+            }
+        }
+        Ok(Some(fx))
+    }
+
+    pub fn rom_count(&self) -> usize {
+        self.rom_count
+    }
+
+    /// The name of ROM `rom`, padded with spaces
+    pub fn name(&mut self, rom: usize) -> Result<[u8; NAME_LEN], Error> {
+        let mut name = [0; NAME_LEN];
+        self.read(entry_address(rom), &mut name)?;
+        Ok(name)
+    }
+
+    /// Copy ROM `rom` into the interpreter's memory
+    pub fn load(&mut self, rom: usize, machine_state: &mut MachineState) -> Result<(), Error> {
+        let len = self.len(rom)?.unwrap_or(0);
+        let mut chunk = [0; 32];
+        for start in (0..len).step_by(chunk.len()) {
+            let chunk = &mut chunk[..(len - start).min(32)];
+            self.read(slot_address(rom) + start as u32, chunk)?;
+            for (i, &byte) in chunk.iter().enumerate() {
+                machine_state.poke(PROGRAM_ADDRESS + (start + i) as u16, byte);
+            }
+        }
+        Ok(())
+    }
+
+    /// Byte `offset` of ROM `rom`'s program, or 0 past its end, like the memory after it
+    pub fn program_byte(&mut self, rom: usize, offset: usize) -> Result<u8, Error> {
+        if offset >= self.len(rom)?.unwrap_or(0) {
+            return Ok(0);
+        }
+        let mut byte = [0];
+        self.read(slot_address(rom) + offset as u32, &mut byte)?;
+        Ok(byte[0])
+    }
+
+    /// Save the `len` byte program in the interpreter's memory as a new ROM,
+    /// returning false if there's no room for it
+    pub fn save(
+        &mut self,
+        name: &[u8; NAME_LEN],
+        len: usize,
+        machine_state: &MachineState,
+    ) -> Result<bool, Error> {
+        if !self.usable || self.rom_count == MAX_ROMS || len > SLOT_LEN as usize {
+            return Ok(false);
+        }
+        if self.rom_count == 0 {
+            self.program(CATALOG_ADDRESS, &MAGIC)?;
+        }
+
+        let rom = self.rom_count;
+        let program = &machine_state.memory()[PROGRAM_ADDRESS as usize..][..len];
+        for (i, page) in program.chunks(PAGE_LEN).enumerate() {
+            self.program(slot_address(rom) + (i * PAGE_LEN) as u32, page)?;
+        }
+        // The entry goes last, so a ROM is only listed once it's all there
+        let mut entry = [0; ENTRY_LEN as usize];
+        entry[..NAME_LEN].copy_from_slice(name);
+        entry[NAME_LEN..].copy_from_slice(&(len as u16).to_be_bytes());
+        self.program(entry_address(rom), &entry)?;
+
+        self.rom_count += 1;
+        Ok(true)
+    }
+
+    /// The length of ROM `rom`, or `None` if its entry hasn't been written
+    fn len(&mut self, rom: usize) -> Result<Option<usize>, Error> {
+        let mut len = [0; 2];
+        self.read(entry_address(rom) + NAME_LEN as u32, &mut len)?;
+        let len = u16::from_be_bytes(len) as usize;
+        Ok((len <= SLOT_LEN as usize).then_some(len))
+    }
+
+    fn read(&mut self, address: u32, buffer: &mut [u8]) -> Result<(), Error> {
+        let [_, high, middle, low] = address.to_be_bytes();
+        self.transaction(&[READ, high, middle, low], &[], buffer)
+    }
+
+    /// Write `bytes` to erased flash, all within one page, and wait for it to finish
+    fn program(&mut self, address: u32, bytes: &[u8]) -> Result<(), Error> {
+        self.transaction(&[WRITE_ENABLE], &[], &mut [])?;
+        let [_, high, middle, low] = address.to_be_bytes();
+        self.transaction(&[PAGE_PROGRAM, high, middle, low], bytes, &mut [])?;
+        // Bit 0 of the status is set while it's busy
+        let mut status = [1];
+        while status[0] & 1 == 1 {
+            self.transaction(&[READ_STATUS], &[], &mut status)?;
+        }
+        Ok(())
+    }
+
+    /// Send a command and then `write`, then read into `read`, all with the chip selected
+    fn transaction(&mut self, command: &[u8], write: &[u8], read: &mut [u8]) -> Result<(), Error> {
+        let mut spi = self.spi.borrow_mut();
+        self.cs.set_low();
+        let result = spi
+            .write(command)
+            .and_then(|()| spi.write(write))
+            .and_then(|()| spi.read(read));
+        self.cs.set_high();
+        result
+    }
+}
+
+fn entry_address(rom: usize) -> u32 {
+    CATALOG_ADDRESS + MAGIC.len() as u32 + rom as u32 * ENTRY_LEN
+}
+
+fn slot_address(rom: usize) -> u32 {
+    SLOTS_ADDRESS + rom as u32 * SLOT_LEN
+}
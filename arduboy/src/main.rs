@@ -1,64 +1,242 @@
-// #![no_std]
-// #![no_main]
-
-// use panic_halt as _;
-
-// #[arduino_hal::entry]
-// fn main() -> ! {
-//     let peripherals = arduino_hal::Peripherals::take().unwrap();
-//     let pins = arduino_hal::pins!(peripherals);
-//     let mut serial = arduino_hal::default_serial!(peripherals, pins, 57600);
-
-//     let mut rgd_led = [
-//         pins.d10.into_output_high().downgrade(),
-//         pins.d9.into_output_high().downgrade(),
-//         pins.d11.into_output_high().downgrade(),
-//     ];
-
-//     // Up, Down, Left, Right
-//     let d_pad = [
-//         pins.a0.into_pull_up_input().downgrade(),
-//         pins.a3.into_pull_up_input().downgrade(),
-//         pins.a2.into_pull_up_input().downgrade(),
-//         pins.a1.into_pull_up_input().downgrade(),
-//     ];
-//     let a_button = pins.d7.into_pull_up_input();
-//     let b_button = pins.d8.into_pull_up_input();
-
-//     let mut angle = 0.;
-//     loop {
-//         let mut red = 0.;
-//         let mut green = 0.;
-//         let mut blue = 0.;
-
-//         if angle < 60. {
-//             red = 255.;
-//             green = angle * 4.25 - 0.01;
-//             blue = 0.;
-//         } else if angle < 120. {
-//             red = (120. - angle) * 4.25 - 0.01;
-//             green = 255.;
-//             blue = 0.;
-//         } else if angle < 180. {
-//             red = 0.;
-//             green = 255.;
-//             blue = (angle - 120.) * 4.25 - 0.01;
-//         } else if angle < 240. {
-//             red = 0.;
-//             green = (240. - angle) * 4.25 - 0.01;
-//             blue = 255.;
-//         } else if angle < 300. {
-//             red = (angle - 240.) * 4.25 - 0.01;
-//             green = 0.;
-//             blue = 255.;
-//         } else {
-//             red = 255.;
-//             green = 0.;
-//             blue = (360. - angle) * 4.25 - 0.01;
-//         }
-
-//         angle = (angle + 1.) % 360.;
-//     }
-// }
-
-fn main() {}
+//! Runs ROMs on an Arduboy, with its OLED as the display and its six buttons as keys
+//!
+//! The ROMs are built in, every `.ch8` file in the directory at the absolute path in the
+//! `CHIP8_ROMS` environment variable. With more than one, they're listed at boot to choose from,
+//! with Up and Down to move and A to choose.
+//!
+//! ROMs are kept in flash and copied into the interpreter's memory once chosen, as the
+//! ATmega32u4's 2.5 KiB of RAM only has room for the interpreter itself. For that the core is
+//! built with `chip8-only` and `small-ram`, so programs can be up to 1 KiB, and SUPER-CHIP
+//! isn't supported.
+//!
+//! | Button                | Keys       |
+//! |-----------------------|------------|
+//! | Up, Down, Left, Right | 2, 8, 4, 6 |
+//! | A                     | 5          |
+//! | B                     | F          |
+//!
+//! A ROM can have its own keys, in a `.keys` file next to it with the same name. That's the keys
+//! for Up, Down, Left, Right, A, and B, as hex digits separated by spaces, or `-` for none.
+//! Five more after those are pressed by Up, Down, Left, Right, and A while B is held down,
+//! so a game can use up to 10 keys. `1 4 C D - -` plays Pong with Up and Down moving the left
+//! paddle, and Left and Right moving the right one.
+//!
+//! Holding B while it's turned on waits for a ROM over the serial port at 57600 baud instead,
+//! to try one without flashing the firmware again. It's sent as its name in 14 bytes padded with
+//! spaces, its length in 2 big endian bytes, the program, then the lowest byte of the sum of the
+//! name's and program's bytes. `K` is sent back once it's running, or `E` if it didn't arrive
+//! whole or is too big, to send it again. It uses the default keys.
+//!
+//! On an Arduboy FX, a ROM sent over serial is also saved to the FX's flash chip, and listed in
+//! the menu after the built in ROMs from then on. They're kept in a catalog in the last 64 KiB of
+//! the flash, out of the way of the FX's own games, with room for 60. A ROM sent again is saved
+//! again, under the same name. Like ROMs sent over serial, they use the default keys.
+//!
+//! Holding Up and Down together saves the game to EEPROM, and holding Left and Right together
+//! loads it again, even after it's been turned off. There's one save, shared by every ROM, so
+//! saving overwrites whatever was saved before, and a save is only loaded by the ROM it came
+//! from. A short beep says it worked, and a long one that it didn't. A whole save state is
+//! 1849 bytes, more than the 1 KiB of EEPROM, so only the memory that's changed since the ROM was
+//! loaded is saved, with room for around 680 bytes of it, and saving fails if more has changed.
+//! ROMs sent over serial can only be saved once they're saved to an FX's flash chip.
+
+#![no_std]
+#![no_main]
+
+mod board;
+mod fx;
+mod oled;
+mod save;
+mod upload;
+
+use arduino_hal::spi;
+use avr_progmem::progmem;
+use board::{Buttons, Buzzer, Chord, Clock, DEFAULT_LAYOUT, XorShift};
+use core::cell::RefCell;
+use fx::Fx;
+use oled::Oled;
+use panic_halt as _;
+use rs_chip8_core::{EmulationSystem, MachineState, PROGRAM_ADDRESS};
+use rs_chip8_embedded::{Beeper, Stopped, choose, run};
+use save::SaveSlot;
+
+// ROM_DATA has every program, one after another, with program n from ROM_STARTS[n] up to
+// ROM_STARTS[n + 1]. ROM_NAMES are their names, upper case and padded with spaces,
+// and ROM_LAYOUTS the keys their buttons press
+include!(concat!(env!("OUT_DIR"), "/roms.rs"));
+
+const IPF: u32 = 10;
+/// Pitch of the beep, in Hz
+const TONE: u32 = 440;
+/// How long to beep after saving or loading worked, and after it didn't
+const WORKED_BEEP_MS: u32 = 50;
+const FAILED_BEEP_MS: u32 = 500;
+
+/// Where the running program came from, to compare memory with when saving
+#[derive(Clone, Copy)]
+enum Source {
+    BuiltIn(usize),
+    Fx(usize),
+}
+
+#[arduino_hal::entry]
+fn main() -> ! {
+    let dp = arduino_hal::Peripherals::take().unwrap();
+    let pins = arduino_hal::pins!(dp);
+
+    let (spi, _) = arduino_hal::Spi::new(
+        dp.SPI,
+        pins.sck.into_output(),
+        pins.mosi.into_output(),
+        pins.miso.into_pull_up_input(),
+        pins.led_rx.into_output(),
+        spi::Settings {
+            // 8 MHz, the fastest it goes
+            clock: spi::SerialClockRate::OscfOver2,
+            mode: embedded_hal::spi::MODE_0,
+            ..Default::default()
+        },
+    );
+    // The OLED and the FX's flash chip share the bus, so the flash has to be deselected first
+    let spi = RefCell::new(spi);
+    let fx_cs = pins.d2.into_output_high().downgrade();
+    let mut oled = Oled::new(
+        &spi,
+        pins.d12.into_output_high().downgrade(),
+        pins.d4.into_output().downgrade(),
+        &mut pins.d6.into_output_high().downgrade(),
+    )
+    .unwrap();
+    let mut fx = Fx::new(&spi, fx_cs).unwrap();
+    // The FX's ROMs are listed after the built in ones
+    let rom_count = ROM_COUNT + fx.as_ref().map_or(0, Fx::rom_count);
+
+    // The menu's keys are in the default layout, whatever the ROMs' are
+    let mut buttons = Buttons::new([
+        pins.a0.into_pull_up_input().downgrade(),
+        pins.a3.into_pull_up_input().downgrade(),
+        pins.a2.into_pull_up_input().downgrade(),
+        pins.a1.into_pull_up_input().downgrade(),
+        pins.d7.into_pull_up_input().downgrade(),
+        pins.d8.into_pull_up_input().downgrade(),
+    ]);
+
+    // The speaker is between D5 and D13, D5 is timer 3's output
+    let speaker = pins.d5.into_output().downgrade();
+    let _ = pins.d13.into_output();
+    let mut buzzer = Buzzer::new(dp.TC3, speaker, TONE);
+
+    // Nothing is connected to A4, so the lowest bit of each reading is noise
+    let mut adc = arduino_hal::Adc::new(dp.ADC, Default::default());
+    let noise = pins.a4.into_analog_input(&mut adc);
+    let seed = (0..16).fold(0, |seed: u16, _| {
+        seed << 1 | noise.analog_read(&mut adc) & 1
+    });
+
+    let mut clock = Clock::new(dp.TC0);
+    // SAFETY: the clock's interrupt only touches its own counter, in a critical section
+    unsafe { avr_device::interrupt::enable() };
+
+    // Holding B at boot waits for a ROM over serial instead of choosing a built in one
+    let mut serial = arduino_hal::default_serial!(dp, pins, 57600);
+    let rom = if buttons.pins[5].is_low() {
+        None
+    } else if rom_count > 1 {
+        Some(
+            choose(
+                rom_count,
+                |i| match &mut fx {
+                    Some(fx) if i >= ROM_COUNT => fx.name(i - ROM_COUNT).unwrap(),
+                    _ => ROM_NAMES.load_at(i),
+                },
+                &mut oled,
+                &mut buttons,
+                &mut clock,
+            )
+            .unwrap(),
+        )
+    } else {
+        Some(0)
+    };
+
+    let mut machine_state = MachineState::new(EmulationSystem::Chip8);
+    machine_state.load_default_font();
+    let source = match rom {
+        Some(rom) if rom >= ROM_COUNT => {
+            fx.as_mut()
+                .unwrap()
+                .load(rom - ROM_COUNT, &mut machine_state)
+                .unwrap();
+            Some(Source::Fx(rom - ROM_COUNT))
+        }
+        Some(rom) => {
+            buttons.layout = ROM_LAYOUTS.load_at(rom);
+            let start = ROM_STARTS.load_at(rom) as usize;
+            for i in start..ROM_STARTS.load_at(rom + 1) as usize {
+                machine_state.poke(PROGRAM_ADDRESS + (i - start) as u16, ROM_DATA.load_at(i));
+            }
+            Some(Source::BuiltIn(rom))
+        }
+        None => {
+            let (name, len) = upload::receive(&mut serial, &mut machine_state);
+            // Saved if there's room, and it's run either way
+            match &mut fx {
+                Some(fx) => fx
+                    .save(&name, len, &machine_state)
+                    .unwrap()
+                    .then(|| Source::Fx(fx.rom_count() - 1)),
+                None => None,
+            }
+        }
+    };
+    // A chord pressed in the menu isn't meant for the game
+    buttons.take_chord();
+
+    let mut save_slot = SaveSlot(arduino_hal::Eeprom::new(dp.EEPROM));
+    // Xorshift gets stuck at 0
+    let mut rng = XorShift(seed.max(1));
+    // Leave the last frame on the display when it stops, and stop
+    while let Stopped::Paused = run(
+        &mut machine_state,
+        IPF,
+        &mut oled,
+        &mut buttons,
+        &mut buzzer,
+        &mut rng,
+        &mut clock,
+    ) {
+        let rom = |address| rom_byte(source.unwrap(), &mut fx, address);
+        let worked = match buttons.take_chord() {
+            Some(Chord::Save) if source.is_some() => save_slot.save(&machine_state, rom),
+            Some(Chord::Load) if source.is_some() => save_slot.load(&mut machine_state, rom),
+            _ => false,
+        };
+        let beep_ms = if worked {
+            WORKED_BEEP_MS
+        } else {
+            FAILED_BEEP_MS
+        };
+        buzzer.set_beeping(true);
+        arduino_hal::delay_ms(beep_ms);
+        buzzer.set_beeping(false);
+    }
+    loop {
+        avr_device::asm::sleep();
+    }
+}
+
+/// The byte the ROM loaded at `address`, or 0 past its end
+fn rom_byte(source: Source, fx: &mut Option<Fx>, address: u16) -> u8 {
+    let offset = (address - PROGRAM_ADDRESS) as usize;
+    match source {
+        Source::BuiltIn(rom) => {
+            let i = ROM_STARTS.load_at(rom) as usize + offset;
+            if i < ROM_STARTS.load_at(rom + 1) as usize {
+                ROM_DATA.load_at(i)
+            } else {
+                0
+            }
+        }
+        Source::Fx(rom) => fx.as_mut().unwrap().program_byte(rom, offset).unwrap(),
+    }
+}
@@ -0,0 +1,114 @@
+use arduino_hal::{
+    Spi,
+    port::{Pin, mode::Output},
+};
+use core::cell::RefCell;
+use embedded_hal::spi::{ErrorType, SpiBus};
+use rs_chip8_core::{DISPLAY_HEIGHT, DISPLAY_WIDTH, Display};
+use rs_chip8_embedded::Screen;
+
+/// Each page of the OLED is 8 pixels high, which is 4 of the display's rows scaled up
+const ROWS_PER_PAGE: usize = 4;
+
+const CHARGE_PUMP: u8 = 0x8D;
+const ADDRESSING_MODE: u8 = 0x20;
+const COLUMN_ADDRESS: u8 = 0x21;
+const PAGE_ADDRESS: u8 = 0x22;
+const CONTRAST: u8 = 0x81;
+const SEGMENT_REMAP: u8 = 0xA1;
+const DISPLAY_ON: u8 = 0xAF;
+const COM_SCAN_DECREMENT: u8 = 0xC8;
+const CLOCK_DIVIDE: u8 = 0xD5;
+const PRECHARGE: u8 = 0xD9;
+
+/// The Arduboy's 128x64 SSD1306, with each of the display's pixels drawn as 2x2 OLED pixels
+///
+/// There's no room in RAM for a copy of what's on the OLED, so pages are sent straight from
+/// the display as they're drawn. The SPI bus is shared with the FX's flash chip.
+pub struct Oled<'a> {
+    spi: &'a RefCell<Spi>,
+    cs: Pin<Output>,
+    dc: Pin<Output>,
+}
+
+impl<'a> Oled<'a> {
+    pub fn new(
+        spi: &'a RefCell<Spi>,
+        cs: Pin<Output>,
+        dc: Pin<Output>,
+        reset: &mut Pin<Output>,
+    ) -> Result<Self, <Spi as ErrorType>::Error> {
+        reset.set_low();
+        arduino_hal::delay_ms(10);
+        reset.set_high();
+        arduino_hal::delay_ms(10);
+
+        let mut oled = Self { spi, cs, dc };
+        // The same setup as the Arduboy's own library
+        oled.command(&[
+            // The fastest refresh, so it doesn't flicker
+            CLOCK_DIVIDE,
+            0xF0,
+            CHARGE_PUMP,
+            0x14,
+            // Rotate it 180°, the right way up for the Arduboy
+            SEGMENT_REMAP,
+            COM_SCAN_DECREMENT,
+            CONTRAST,
+            0xCF,
+            PRECHARGE,
+            0xF1,
+            // Columns, then pages, so a whole page is sent at once
+            ADDRESSING_MODE,
+            0x00,
+        ])?;
+
+        // What's on the OLED from before a reset is kept, so it has to be cleared
+        oled.command(&[COLUMN_ADDRESS, 0, 127, PAGE_ADDRESS, 0, 7])?;
+        oled.cs.set_low();
+        oled.dc.set_high();
+        let result = (0..128 * 8).try_for_each(|_| oled.spi.borrow_mut().write(&[0]));
+        oled.cs.set_high();
+        result?;
+
+        oled.command(&[DISPLAY_ON])?;
+        Ok(oled)
+    }
+
+    fn command(&mut self, bytes: &[u8]) -> Result<(), <Spi as ErrorType>::Error> {
+        self.cs.set_low();
+        self.dc.set_low();
+        let result = self.spi.borrow_mut().write(bytes);
+        self.cs.set_high();
+        result
+    }
+}
+
+impl Screen for Oled<'_> {
+    type Error = <Spi as ErrorType>::Error;
+
+    fn flush(&mut self, display: &Display, dirty_rows: u64) -> Result<(), Self::Error> {
+        for page in 0..DISPLAY_HEIGHT / ROWS_PER_PAGE {
+            let top = page * ROWS_PER_PAGE;
+            if dirty_rows >> top & 0xF == 0 {
+                continue;
+            }
+
+            self.command(&[COLUMN_ADDRESS, 0, 127, PAGE_ADDRESS, page as u8, page as u8])?;
+            let mut spi = self.spi.borrow_mut();
+            self.cs.set_low();
+            self.dc.set_high();
+            let result = (0..DISPLAY_WIDTH).try_for_each(|x| {
+                // Bit n of a byte is row n of the page, so each of the display's rows is 2 bits
+                let byte = (0..ROWS_PER_PAGE)
+                    .filter(|row| display.pixel(x, top + row))
+                    .fold(0, |byte, row| byte | 0b11 << (row * 2));
+                // Sent twice, as each pixel is two columns wide
+                spi.write(&[byte, byte])
+            });
+            self.cs.set_high();
+            result?;
+        }
+        Ok(())
+    }
+}
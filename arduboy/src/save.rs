@@ -0,0 +1,143 @@
+use arduino_hal::Eeprom;
+use rs_chip8_core::{MachineState, PROGRAM_ADDRESS, RAM_SIZE, STATE_SIZE_EXCEPT_RAM};
+
+/// The first 16 bytes of EEPROM are the Arduboy's own settings
+const SLOT_ADDRESS: u16 = 16;
+/// The slot starts with this, written last so a save that was cut off isn't loaded
+const MAGIC: [u8; 2] = *b"C8";
+/// Then the checksum of the ROM it was saved from, in 2 big endian bytes
+const CHECKSUM_ADDRESS: u16 = SLOT_ADDRESS + MAGIC.len() as u16;
+/// Then everything but RAM
+const STATE_ADDRESS: u16 = CHECKSUM_ADDRESS + 2;
+/// Then runs of the memory that's changed since the ROM was loaded, each as its address in
+/// 2 big endian bytes, its length in a byte, and then the bytes, ending with [`END`]
+const RUNS_ADDRESS: u16 = STATE_ADDRESS + STATE_SIZE_EXCEPT_RAM as u16;
+const RUN_HEADER_LEN: u16 = 3;
+const END: u16 = 0xFFFF;
+
+/// What erased EEPROM reads as
+const ERASED: u8 = 0xFF;
+
+/// A single save slot in EEPROM, shared by every ROM
+///
+/// A whole save state is bigger than the EEPROM, so only memory that's changed since the ROM
+/// was loaded is saved, and memory below the program isn't saved at all, as it's the
+/// interpreter's, with the font in it. `rom` is the byte the running ROM loaded at an address,
+/// or 0 past its end.
+pub struct SaveSlot(pub Eeprom);
+
+impl SaveSlot {
+    /// Save the machine over whatever was saved before,
+    /// returning false if too much memory has changed to fit
+    pub fn save(&mut self, machine_state: &MachineState, mut rom: impl FnMut(u16) -> u8) -> bool {
+        // Measured first, so a save that doesn't fit leaves the last one alone
+        let mut len = 0;
+        for_each_run(machine_state, &mut rom, |_, run_len| {
+            len += RUN_HEADER_LEN + run_len;
+        });
+        if RUNS_ADDRESS + len + 2 > self.0.capacity() {
+            return false;
+        }
+
+        self.write(SLOT_ADDRESS, &[ERASED; 2]);
+        self.write(CHECKSUM_ADDRESS, &checksum(&mut rom).to_be_bytes());
+        let mut address = STATE_ADDRESS;
+        machine_state.save_state_except_ram(|byte| {
+            self.0.write_byte(address, byte);
+            address += 1;
+        });
+        let mut address = RUNS_ADDRESS;
+        for_each_run(machine_state, &mut rom, |start, run_len| {
+            self.write(address, &start.to_be_bytes());
+            self.0.write_byte(address + 2, run_len as u8);
+            address += RUN_HEADER_LEN;
+            for i in 0..run_len {
+                self.0
+                    .write_byte(address + i, machine_state.peek(start + i));
+            }
+            address += run_len;
+        });
+        self.write(address, &END.to_be_bytes());
+        self.write(SLOT_ADDRESS, &MAGIC);
+        true
+    }
+
+    /// Load what was saved, returning false if nothing was, or it was saved from another ROM
+    pub fn load(
+        &mut self,
+        machine_state: &mut MachineState,
+        mut rom: impl FnMut(u16) -> u8,
+    ) -> bool {
+        if self.read_u16(SLOT_ADDRESS) != u16::from_be_bytes(MAGIC)
+            || self.read_u16(CHECKSUM_ADDRESS) != checksum(&mut rom)
+        {
+            return false;
+        }
+        let mut address = STATE_ADDRESS;
+        let loaded = machine_state.load_state_except_ram(|| {
+            address += 1;
+            self.0.read_byte(address - 1)
+        });
+        if loaded.is_err() {
+            return false;
+        }
+
+        for address in PROGRAM_ADDRESS..RAM_SIZE as u16 {
+            machine_state.poke(address, rom(address));
+        }
+        let mut address = RUNS_ADDRESS;
+        loop {
+            let start = self.read_u16(address);
+            if start == END {
+                return true;
+            }
+            let run_len = self.0.read_byte(address + 2) as u16;
+            address += RUN_HEADER_LEN;
+            for i in 0..run_len {
+                machine_state.poke(start + i, self.0.read_byte(address + i));
+            }
+            address += run_len;
+        }
+    }
+
+    fn write(&mut self, address: u16, bytes: &[u8]) {
+        for (i, &byte) in bytes.iter().enumerate() {
+            self.0.write_byte(address + i as u16, byte);
+        }
+    }
+
+    fn read_u16(&self, address: u16) -> u16 {
+        u16::from_be_bytes([self.0.read_byte(address), self.0.read_byte(address + 1)])
+    }
+}
+
+/// Call `f` with the start and length of each run of memory that's changed since the ROM was
+/// loaded, each up to 255 bytes long
+fn for_each_run(
+    machine_state: &MachineState,
+    rom: &mut impl FnMut(u16) -> u8,
+    mut f: impl FnMut(u16, u16),
+) {
+    let mut start = None;
+    for address in PROGRAM_ADDRESS..=RAM_SIZE as u16 {
+        let changed = address < RAM_SIZE as u16 && machine_state.peek(address) != rom(address);
+        match start {
+            Some(run_start) if !changed || address - run_start == u8::MAX as u16 => {
+                f(run_start, address - run_start);
+                start = changed.then_some(address);
+            }
+            None if changed => start = Some(address),
+            _ => {}
+        }
+    }
+}
+
+/// Fletcher-16 of the ROM, so a save is only loaded by the ROM it was saved from
+fn checksum(rom: &mut impl FnMut(u16) -> u8) -> u16 {
+    let (sum, sum_of_sums) =
+        (PROGRAM_ADDRESS..RAM_SIZE as u16).fold((0, 0), |(sum, sum_of_sums), address| {
+            let sum = (sum + rom(address) as u16) % 255;
+            (sum, (sum_of_sums + sum) % 255)
+        });
+    sum_of_sums << 8 | sum
+}
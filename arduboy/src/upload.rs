@@ -0,0 +1,61 @@
+use crate::fx::NAME_LEN;
+use embedded_hal_02::serial::{Read, Write};
+use rs_chip8_core::{MachineState, PROGRAM_ADDRESS, RAM_SIZE};
+
+/// Sent back once a ROM has arrived whole
+const ACK: u8 = b'K';
+/// Sent back when a ROM didn't arrive whole, or doesn't fit, so it can be sent again
+const NAK: u8 = b'E';
+
+const MAX_PROGRAM_LEN: usize = RAM_SIZE - PROGRAM_ADDRESS as usize;
+
+/// Read ROMs from `serial` into the interpreter's memory until one arrives whole,
+/// returning its name and the length of its program
+///
+/// A ROM is sent as its name in 14 bytes padded with spaces, its length in 2 big endian bytes,
+/// the program, then the lowest byte of the sum of the name's and program's bytes.
+pub fn receive<S: Read<u8> + Write<u8>>(
+    serial: &mut S,
+    machine_state: &mut MachineState,
+) -> ([u8; NAME_LEN], usize) {
+    loop {
+        let received = read_rom(serial, machine_state);
+        let _ = nb::block!(serial.write(if received.is_some() { ACK } else { NAK }));
+        let _ = nb::block!(serial.flush());
+        if let Some(received) = received {
+            return received;
+        }
+    }
+}
+
+/// Read one ROM, returning its name and length if it fits and its checksum matched
+fn read_rom(
+    serial: &mut impl Read<u8>,
+    machine_state: &mut MachineState,
+) -> Option<([u8; NAME_LEN], usize)> {
+    let mut read = || nb::block!(serial.read()).ok();
+    let mut sum = 0u8;
+    let mut name = [0; NAME_LEN];
+    for character in &mut name {
+        let byte = read()?;
+        sum = sum.wrapping_add(byte);
+        // Upper case, like the built in ROMs' names
+        *character = byte.to_ascii_uppercase();
+    }
+    let len = u16::from_be_bytes([read()?, read()?]) as usize;
+
+    // A program that's too long is still read to the end, so the next one starts in the right place
+    for i in 0..len {
+        let byte = read()?;
+        sum = sum.wrapping_add(byte);
+        if i < MAX_PROGRAM_LEN {
+            machine_state.poke(PROGRAM_ADDRESS + i as u16, byte);
+        }
+    }
+    // Clear what's left of a longer program sent before
+    for i in len..MAX_PROGRAM_LEN {
+        machine_state.poke(PROGRAM_ADDRESS + i as u16, 0);
+    }
+
+    (read()? == sum && len <= MAX_PROGRAM_LEN).then_some((name, len))
+}
@@ -0,0 +1,580 @@
+use crate::{
+    EmulationSystem, Error,
+    display::Display,
+    input::Keypad,
+    memory::{BIG_FONT_ADDRESS, FONT_ADDRESS, Memory, PROGRAM_ADDRESS},
+    quirks::Quirks,
+};
+use heapless::Vec;
+
+/// Levels of subroutine calls, the COSMAC VIP's original 12 without the SuperChip
+#[cfg(not(feature = "chip8-only"))]
+pub const STACK_SIZE: usize = 16;
+#[cfg(feature = "chip8-only")]
+pub const STACK_SIZE: usize = 12;
+
+/// Everything outside of the CPU that an instruction can access
+pub(crate) struct Bus<'a, K, R>
+where
+    K: FnMut() -> u16,
+    R: FnMut() -> u8,
+{
+    pub system: EmulationSystem,
+    pub quirks: &'a Quirks,
+    pub memory: &'a mut Memory,
+    pub display: &'a mut Display,
+    pub keypad: &'a mut Keypad,
+    pub held_keys: K,
+    pub random: R,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct Cpu {
+    pub program_counter: u16,
+    pub index_register: u16,
+    pub var_registers: [u8; 16],
+
+    pub stack: Vec<u16, STACK_SIZE>,
+
+    pub delay_timer: u8,
+    pub sound_timer: u8,
+}
+
+impl Default for Cpu {
+    fn default() -> Self {
+        Self {
+            program_counter: PROGRAM_ADDRESS,
+            index_register: 0,
+            var_registers: [0; 16],
+
+            stack: Vec::new(),
+
+            delay_timer: 0,
+            sound_timer: 0,
+        }
+    }
+}
+
+impl Cpu {
+    pub fn tick_timers(&mut self) {
+        if self.delay_timer > 0 {
+            self.delay_timer -= 1;
+        }
+        if self.sound_timer > 0 {
+            self.sound_timer -= 1;
+        }
+    }
+
+    /// Fetch, decode, and execute one instruction
+    pub fn step<K, R>(&mut self, bus: &mut Bus<K, R>) -> Result<(), Error>
+    where
+        K: FnMut() -> u16,
+        R: FnMut() -> u8,
+    {
+        /* FETCH */
+        let instruction = bus.memory.read_word(self.program_counter);
+        self.program_counter += 2;
+
+        /* DECODE */
+        let x = ((instruction & 0x0F00) >> 8) as usize;
+        let y = ((instruction & 0x00F0) >> 4) as usize;
+        let n = instruction & 0x000F;
+        let nn = (instruction & 0x00FF) as u8;
+        let nnn = instruction & 0x0FFF;
+
+        match ((instruction & 0xF000) >> 12, nn, n) {
+            // 00E0
+            (0x0, _, 0x0) if y == 0xE => bus.display.clear(),
+
+            // 00EE
+            (0x0, _, 0xE) if y == 0xE => {
+                self.program_counter = self.stack.pop().unwrap_or(PROGRAM_ADDRESS)
+            }
+
+            // 1nnn
+            (0x1, _, _) => self.program_counter = nnn,
+
+            // 2nnn
+            (0x2, _, _) => {
+                self.stack
+                    .push(self.program_counter)
+                    .map_err(|_| Error::StackOverflow)?;
+                self.program_counter = nnn;
+            }
+
+            // 3xnn
+            (0x3, _, _) => {
+                if self.var_registers[x] == nn {
+                    self.program_counter += 2;
+                }
+            }
+
+            // 4xnn
+            (0x4, _, _) => {
+                if self.var_registers[x] != nn {
+                    self.program_counter += 2;
+                }
+            }
+
+            // 5xy
+            (0x5, _, _) => {
+                if self.var_registers[x] == self.var_registers[y] {
+                    self.program_counter += 2;
+                }
+            }
+
+            // 9xy
+            (0x9, _, _) => {
+                if self.var_registers[x] != self.var_registers[y] {
+                    self.program_counter += 2;
+                }
+            }
+
+            // 8xy0
+            (0x8, _, 0x0) => self.var_registers[x] = self.var_registers[y],
+
+            // 8xy1
+            (0x8, _, 0x1) => {
+                self.var_registers[x] |= self.var_registers[y];
+                if bus.quirks.logic_resets_flag {
+                    self.var_registers[0xF] = 0;
+                }
+            }
+
+            // 8xy2
+            (0x8, _, 0x2) => {
+                self.var_registers[x] &= self.var_registers[y];
+                if bus.quirks.logic_resets_flag {
+                    self.var_registers[0xF] = 0;
+                }
+            }
+
+            // 8xy3
+            (0x8, _, 0x3) => {
+                self.var_registers[x] ^= self.var_registers[y];
+                if bus.quirks.logic_resets_flag {
+                    self.var_registers[0xF] = 0;
+                }
+            }
+
+            // 8xy4
+            (0x8, _, 0x4) => {
+                let overflow =
+                    if self.var_registers[x] as usize + self.var_registers[y] as usize > 0xFF {
+                        1
+                    } else {
+                        0
+                    };
+                self.var_registers[x] =
+                    u8::wrapping_add(self.var_registers[x], self.var_registers[y]);
+                self.var_registers[0xF] = overflow;
+            }
+
+            // 8xy5
+            (0x8, _, 0x5) => {
+                let borrow = if self.var_registers[x] >= self.var_registers[y] {
+                    1
+                } else {
+                    0
+                };
+
+                self.var_registers[x] =
+                    u8::wrapping_sub(self.var_registers[x], self.var_registers[y]);
+
+                self.var_registers[0xF] = borrow;
+            }
+
+            // 8xy7
+            (0x8, _, 0x7) => {
+                let borrow = if self.var_registers[y] >= self.var_registers[x] {
+                    1
+                } else {
+                    0
+                };
+
+                self.var_registers[x] =
+                    u8::wrapping_sub(self.var_registers[y], self.var_registers[x]);
+
+                self.var_registers[0xF] = borrow;
+            }
+
+            // 8xy6
+            (0x8, _, 0x6) => {
+                let value = self.var_registers[if bus.quirks.shift_uses_vy { y } else { x }];
+                self.var_registers[x] = value >> 1;
+                self.var_registers[0xF] = value & 0b00000001;
+            }
+
+            // 8xyE
+            (0x8, _, 0xE) => {
+                let value = self.var_registers[if bus.quirks.shift_uses_vy { y } else { x }];
+                self.var_registers[x] = value << 1;
+                self.var_registers[0xF] = (value & 0b10000000) >> 7;
+            }
+
+            // 6xnn
+            (0x6, _, _) => self.var_registers[x] = nn,
+
+            // 7xnn
+            (0x7, _, _) => self.var_registers[x] = u8::wrapping_add(self.var_registers[x], nn),
+
+            // Annn
+            (0xA, _, _) => self.index_register = nnn,
+
+            // Bnnn
+            (0xB, _, _) => {
+                self.program_counter =
+                    nnn + self.var_registers[if bus.quirks.jump_uses_vx { x } else { 0 }] as u16
+            }
+
+            // Cxnn
+            (0xC, _, _) => {
+                self.var_registers[x] = (bus.random)() & nn;
+            }
+
+            // Dxyn
+            (0xD, _, _) => {
+                let wide = n == 0 && bus.display.is_high_res();
+                let sprite = bus
+                    .memory
+                    .slice(self.index_register, if wide { 32 } else { n as usize });
+
+                self.var_registers[0xF] = bus.display.draw_sprite(
+                    self.var_registers[x],
+                    self.var_registers[y],
+                    sprite,
+                    wide,
+                );
+            }
+
+            // Ex9E
+            (0xE, 0x9E, _) => {
+                if Keypad::is_pressed((bus.held_keys)(), self.var_registers[x]) {
+                    self.program_counter += 2;
+                }
+            }
+
+            // ExA1
+            (0xE, 0xA1, _) => {
+                if !Keypad::is_pressed((bus.held_keys)(), self.var_registers[x]) {
+                    self.program_counter += 2;
+                }
+            }
+
+            // Fx07
+            (0xF, 0x07, _) => self.var_registers[x] = self.delay_timer,
+
+            // Fx15
+            (0xF, 0x15, _) => self.delay_timer = self.var_registers[x],
+
+            // Fx18
+            (0xF, 0x18, _) => self.sound_timer = self.var_registers[x],
+
+            // Fx1E
+            (0xF, 0x1E, _) => self.index_register += self.var_registers[x] as u16,
+
+            // Fx0A
+            (0xF, 0xA, _) => match bus.keypad.wait_for_release((bus.held_keys)()) {
+                Some(key) => self.var_registers[x] = key,
+                None => self.program_counter -= 2,
+            },
+
+            // Fx29
+            (0xF, 0x29, _) => {
+                self.index_register = FONT_ADDRESS + (self.var_registers[x] & 0xF) as u16 * 5;
+            }
+
+            // Fx33
+            #[allow(clippy::identity_op)]
+            (0xF, 0x33, _) => {
+                let value = self.var_registers[x];
+                bus.memory
+                    .write_byte(self.index_register + 2, (value / 1) % 10);
+                bus.memory
+                    .write_byte(self.index_register + 1, (value / 10) % 10);
+                bus.memory
+                    .write_byte(self.index_register + 0, (value / 100) % 10);
+            }
+
+            // Fx55
+            (0xF, 0x55, _) => {
+                for (i, var) in self.var_registers[..=x].iter().enumerate() {
+                    bus.memory.write_byte(self.index_register + i as u16, *var);
+                }
+                if bus.quirks.memory_increments_index {
+                    self.index_register += x as u16 + 1;
+                }
+            }
+
+            // Fx65
+            (0xF, 0x65, _) => {
+                for (i, var) in self.var_registers[..=x].iter_mut().enumerate() {
+                    *var = bus.memory.read_byte(self.index_register + i as u16);
+                }
+                if bus.quirks.memory_increments_index {
+                    self.index_register += x as u16 + 1;
+                }
+            }
+
+            _ => {
+                if bus.system == EmulationSystem::SuperChip && cfg!(not(feature = "chip8-only")) {
+                    match instruction {
+                        0x00FD => return Err(Error::ProgramExited),
+
+                        0x00FE => bus.display.set_high_res(false),
+
+                        0x00FF => bus.display.set_high_res(true),
+
+                        _ if instruction & 0xF0FF == 0xF075 => (),
+
+                        _ if instruction & 0xF0FF == 0xF085 => (),
+
+                        0x00FB => bus.display.scroll_right(),
+
+                        0x00FC => bus.display.scroll_left(),
+
+                        _ if instruction & 0xFFF0 == 0x00C0 => bus.display.scroll_down(n as usize),
+
+                        _ if instruction & 0xF0FF == 0xF030 => {
+                            self.index_register =
+                                BIG_FONT_ADDRESS + (self.var_registers[x] & 0xF) as u16 * 10;
+                        }
+
+                        _ => return Err(Error::IllegalInstruction(instruction)),
+                    }
+                } else {
+                    return Err(Error::IllegalInstruction(instruction));
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::STACK_SIZE;
+    use crate::{EmulationSystem, Error, MachineState, PROGRAM_ADDRESS, Quirks};
+
+    /// A CHIP-8 with `quirks` and `program` loaded, after running the whole program once
+    fn run(quirks: Quirks, program: &[u16]) -> MachineState {
+        let mut machine_state = MachineState::new(EmulationSystem::Chip8);
+        machine_state.set_quirks(quirks);
+        machine_state.load_default_font();
+        for (i, instruction) in program.iter().enumerate() {
+            let [high, low] = instruction.to_be_bytes();
+            machine_state.poke(PROGRAM_ADDRESS + 2 * i as u16, high);
+            machine_state.poke(PROGRAM_ADDRESS + 2 * i as u16 + 1, low);
+        }
+        for _ in program {
+            machine_state.tick(|| 0, || 0).unwrap();
+        }
+        machine_state
+    }
+
+    fn chip8(program: &[u16]) -> MachineState {
+        run(Quirks::for_system(EmulationSystem::Chip8), program)
+    }
+
+    #[test]
+    fn add_sets_carry() {
+        let machine_state = chip8(&[0x60FF, 0x6102, 0x8014]);
+        assert_eq!(machine_state.registers()[0x0], 0x01);
+        assert_eq!(machine_state.registers()[0xF], 1);
+
+        let machine_state = chip8(&[0x60FE, 0x6101, 0x8014]);
+        assert_eq!(machine_state.registers()[0x0], 0xFF);
+        assert_eq!(machine_state.registers()[0xF], 0);
+    }
+
+    #[test]
+    fn subtract_sets_no_borrow() {
+        let machine_state = chip8(&[0x6005, 0x6103, 0x8015]);
+        assert_eq!(machine_state.registers()[0x0], 0x02);
+        assert_eq!(machine_state.registers()[0xF], 1);
+
+        let machine_state = chip8(&[0x6005, 0x6103, 0x8017]);
+        assert_eq!(machine_state.registers()[0x0], 0xFE);
+        assert_eq!(machine_state.registers()[0xF], 0);
+    }
+
+    #[test]
+    fn flag_is_written_after_result() {
+        let machine_state = chip8(&[0x6FFF, 0x6101, 0x8F14]);
+        assert_eq!(machine_state.registers()[0xF], 1);
+    }
+
+    #[test]
+    fn logic_resets_flag_quirk() {
+        let program = [0x6F05, 0x6003, 0x6105, 0x8011];
+        let machine_state = chip8(&program);
+        assert_eq!(machine_state.registers()[0x0], 0x07);
+        assert_eq!(machine_state.registers()[0xF], 0);
+
+        let quirks = Quirks {
+            logic_resets_flag: false,
+            ..Quirks::for_system(EmulationSystem::Chip8)
+        };
+        assert_eq!(run(quirks, &program).registers()[0xF], 5);
+    }
+
+    #[test]
+    fn shift_quirk() {
+        let program = [0x6004, 0x6181, 0x8016, 0x620F, 0x632F, 0x823E];
+        let machine_state = chip8(&program);
+        assert_eq!(machine_state.registers()[0x0], 0x40);
+        assert_eq!(machine_state.registers()[0x2], 0x5E);
+        assert_eq!(machine_state.registers()[0xF], 0);
+
+        let quirks = Quirks {
+            shift_uses_vy: false,
+            ..Quirks::for_system(EmulationSystem::Chip8)
+        };
+        let machine_state = run(quirks, &program);
+        assert_eq!(machine_state.registers()[0x0], 0x02);
+        assert_eq!(machine_state.registers()[0x2], 0x1E);
+        // The flag is the bit shifted out of whichever register was shifted
+        assert_eq!(machine_state.registers()[0xF], 0);
+        let machine_state = run(quirks, &[0x6081, 0x6100, 0x8016]);
+        assert_eq!(machine_state.registers()[0xF], 1);
+    }
+
+    #[test]
+    fn jump_quirk() {
+        let program = [0x6001, 0x6204, 0xB210];
+        assert_eq!(chip8(&program).program_counter(), 0x211);
+
+        let quirks = Quirks {
+            jump_uses_vx: true,
+            ..Quirks::for_system(EmulationSystem::Chip8)
+        };
+        assert_eq!(run(quirks, &program).program_counter(), 0x214);
+    }
+
+    #[test]
+    fn memory_quirk() {
+        let program = [0x6011, 0x6122, 0xA300, 0xF155];
+        let machine_state = chip8(&program);
+        assert_eq!(machine_state.peek(0x300), 0x11);
+        assert_eq!(machine_state.peek(0x301), 0x22);
+        assert_eq!(machine_state.index_register(), 0x302);
+
+        let quirks = Quirks {
+            memory_increments_index: false,
+            ..Quirks::for_system(EmulationSystem::Chip8)
+        };
+        assert_eq!(run(quirks, &program).index_register(), 0x300);
+    }
+
+    #[test]
+    fn binary_coded_decimal() {
+        let machine_state = chip8(&[0x60EA, 0xA300, 0xF033]);
+        assert_eq!(machine_state.memory()[0x300..0x303], [2, 3, 4]);
+    }
+
+    #[test]
+    fn font_character() {
+        let machine_state = chip8(&[0x600A, 0xF029]);
+        let address = machine_state.index_register() as usize;
+        // The top row of A
+        assert_eq!(machine_state.memory()[address], 0xF0);
+        assert_eq!(machine_state.memory()[address + 1], 0x90);
+    }
+
+    #[test]
+    fn call_and_return() {
+        let mut machine_state = chip8(&[0x2206]);
+        assert_eq!(machine_state.stack(), [0x202]);
+        assert_eq!(machine_state.program_counter(), 0x206);
+
+        // 206: return
+        machine_state.poke(0x206, 0x00);
+        machine_state.poke(0x207, 0xEE);
+        machine_state.tick(|| 0, || 0).unwrap();
+        assert!(machine_state.stack().is_empty());
+        assert_eq!(machine_state.program_counter(), 0x202);
+    }
+
+    #[test]
+    fn stack_overflows() {
+        let mut machine_state = chip8(&[0x2200]);
+        for _ in 1..STACK_SIZE {
+            machine_state.tick(|| 0, || 0).unwrap();
+        }
+        assert!(matches!(
+            machine_state.tick(|| 0, || 0),
+            Err(Error::StackOverflow)
+        ));
+    }
+
+    #[test]
+    fn illegal_instruction() {
+        let mut machine_state = MachineState::new(EmulationSystem::Chip8);
+        machine_state.load_program(&[0x00, 0xFF]);
+        assert!(matches!(
+            machine_state.tick(|| 0, || 0),
+            Err(Error::IllegalInstruction(0x00FF))
+        ));
+    }
+
+    #[test]
+    fn random_is_masked() {
+        let mut machine_state = MachineState::new(EmulationSystem::Chip8);
+        machine_state.load_program(&[0xC0, 0x0F]);
+        machine_state.tick(|| 0, || 0xAB).unwrap();
+        assert_eq!(machine_state.registers()[0x0], 0x0B);
+    }
+
+    #[test]
+    fn skip_if_key() {
+        let mut machine_state = chip8(&[0x6005]);
+        machine_state.load_program(&[0x60, 0x05, 0xE0, 0x9E]);
+        machine_state.tick(|| 1 << 5, || 0).unwrap();
+        assert_eq!(machine_state.program_counter(), 0x206);
+
+        machine_state.set_program_counter(0x202);
+        machine_state.tick(|| 1 << 4, || 0).unwrap();
+        assert_eq!(machine_state.program_counter(), 0x204);
+    }
+
+    #[test]
+    fn wait_for_key_release() {
+        let mut machine_state = MachineState::new(EmulationSystem::Chip8);
+        machine_state.load_program(&[0xF3, 0x0A]);
+
+        // Pressing the key isn't enough, it has to be released
+        machine_state.tick(|| 1 << 7, || 0).unwrap();
+        assert_eq!(machine_state.program_counter(), 0x200);
+        assert!(machine_state.is_waiting_for_key());
+
+        machine_state.tick(|| 0, || 0).unwrap();
+        assert_eq!(machine_state.program_counter(), 0x202);
+        assert_eq!(machine_state.registers()[0x3], 7);
+    }
+
+    #[test]
+    fn timers_count_down_to_zero() {
+        let mut machine_state = chip8(&[0x6002, 0xF015, 0xF018]);
+        assert_eq!(machine_state.delay_timer(), 2);
+        assert_eq!(machine_state.sound_timer(), 2);
+        for _ in 0..3 {
+            machine_state.tick_timer();
+        }
+        assert_eq!(machine_state.delay_timer(), 0);
+        assert_eq!(machine_state.sound_timer(), 0);
+    }
+
+    #[cfg(not(feature = "chip8-only"))]
+    #[test]
+    fn super_chip_instructions() {
+        let mut machine_state = MachineState::new(EmulationSystem::SuperChip);
+        machine_state.load_program(&[0x00, 0xFF, 0x00, 0xFD]);
+        machine_state.tick(|| 0, || 0).unwrap();
+        assert!(machine_state.display().is_high_res());
+        assert!(matches!(
+            machine_state.tick(|| 0, || 0),
+            Err(Error::ProgramExited)
+        ));
+    }
+}
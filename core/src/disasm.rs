@@ -0,0 +1,177 @@
+use crate::{EmulationSystem, PROGRAM_ADDRESS, RAM_SIZE};
+use core::fmt::{self, Write};
+
+/// An instruction formatted with Cowgod's mnemonics
+///
+/// Opcodes the system can't execute are formatted as a `DW` data word.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Disassembly {
+    pub opcode: u16,
+    pub system: EmulationSystem,
+}
+
+pub fn disassemble(opcode: u16, system: EmulationSystem) -> Disassembly {
+    Disassembly { opcode, system }
+}
+
+impl Disassembly {
+    /// Whether the system can execute this instruction
+    pub fn is_valid(&self) -> bool {
+        struct Discard;
+        impl Write for Discard {
+            fn write_str(&mut self, _: &str) -> fmt::Result {
+                Ok(())
+            }
+        }
+
+        self.write_mnemonic(&mut Discard) == Ok(true)
+    }
+
+    /// Write the mnemonic, returning whether the opcode was recognised
+    fn write_mnemonic(&self, f: &mut impl Write) -> Result<bool, fmt::Error> {
+        let instruction = self.opcode;
+        let x = (instruction & 0x0F00) >> 8;
+        let y = (instruction & 0x00F0) >> 4;
+        let n = instruction & 0x000F;
+        let nn = instruction & 0x00FF;
+        let nnn = instruction & 0x0FFF;
+
+        match ((instruction & 0xF000) >> 12, nn, n) {
+            (0x0, _, 0x0) if y == 0xE => write!(f, "CLS")?,
+            (0x0, _, 0xE) if y == 0xE => write!(f, "RET")?,
+            (0x1, _, _) => write!(f, "JP 0x{nnn:03X}")?,
+            (0x2, _, _) => write!(f, "CALL 0x{nnn:03X}")?,
+            (0x3, _, _) => write!(f, "SE V{x:X}, 0x{nn:02X}")?,
+            (0x4, _, _) => write!(f, "SNE V{x:X}, 0x{nn:02X}")?,
+            (0x5, _, _) => write!(f, "SE V{x:X}, V{y:X}")?,
+            (0x9, _, _) => write!(f, "SNE V{x:X}, V{y:X}")?,
+            (0x8, _, 0x0) => write!(f, "LD V{x:X}, V{y:X}")?,
+            (0x8, _, 0x1) => write!(f, "OR V{x:X}, V{y:X}")?,
+            (0x8, _, 0x2) => write!(f, "AND V{x:X}, V{y:X}")?,
+            (0x8, _, 0x3) => write!(f, "XOR V{x:X}, V{y:X}")?,
+            (0x8, _, 0x4) => write!(f, "ADD V{x:X}, V{y:X}")?,
+            (0x8, _, 0x5) => write!(f, "SUB V{x:X}, V{y:X}")?,
+            (0x8, _, 0x7) => write!(f, "SUBN V{x:X}, V{y:X}")?,
+            (0x8, _, 0x6) => write!(f, "SHR V{x:X}, V{y:X}")?,
+            (0x8, _, 0xE) => write!(f, "SHL V{x:X}, V{y:X}")?,
+            (0x6, _, _) => write!(f, "LD V{x:X}, 0x{nn:02X}")?,
+            (0x7, _, _) => write!(f, "ADD V{x:X}, 0x{nn:02X}")?,
+            (0xA, _, _) => write!(f, "LD I, 0x{nnn:03X}")?,
+            (0xB, _, _) => write!(f, "JP V0, 0x{nnn:03X}")?,
+            (0xC, _, _) => write!(f, "RND V{x:X}, 0x{nn:02X}")?,
+            (0xD, _, _) => write!(f, "DRW V{x:X}, V{y:X}, {n}")?,
+            (0xE, 0x9E, _) => write!(f, "SKP V{x:X}")?,
+            (0xE, 0xA1, _) => write!(f, "SKNP V{x:X}")?,
+            (0xF, 0x07, _) => write!(f, "LD V{x:X}, DT")?,
+            (0xF, 0x15, _) => write!(f, "LD DT, V{x:X}")?,
+            (0xF, 0x18, _) => write!(f, "LD ST, V{x:X}")?,
+            (0xF, 0x1E, _) => write!(f, "ADD I, V{x:X}")?,
+            (0xF, 0x0A, _) => write!(f, "LD V{x:X}, K")?,
+            (0xF, 0x29, _) => write!(f, "LD F, V{x:X}")?,
+            (0xF, 0x33, _) => write!(f, "LD B, V{x:X}")?,
+            (0xF, 0x55, _) => write!(f, "LD [I], V{x:X}")?,
+            (0xF, 0x65, _) => write!(f, "LD V{x:X}, [I]")?,
+
+            _ if self.system == EmulationSystem::SuperChip => match instruction {
+                0x00FD => write!(f, "EXIT")?,
+                0x00FE => write!(f, "LOW")?,
+                0x00FF => write!(f, "HIGH")?,
+                0x00FB => write!(f, "SCR")?,
+                0x00FC => write!(f, "SCL")?,
+                _ if instruction & 0xFFF0 == 0x00C0 => write!(f, "SCD {n}")?,
+                _ if instruction & 0xF0FF == 0xF030 => write!(f, "LD HF, V{x:X}")?,
+                _ if instruction & 0xF0FF == 0xF075 => write!(f, "LD R, V{x:X}")?,
+                _ if instruction & 0xF0FF == 0xF085 => write!(f, "LD V{x:X}, R")?,
+                _ => return Ok(false),
+            },
+
+            _ => return Ok(false),
+        }
+
+        Ok(true)
+    }
+}
+
+impl fmt::Display for Disassembly {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.write_mnemonic(f)? {
+            Ok(())
+        } else {
+            write!(f, "DW 0x{:04X}", self.opcode)
+        }
+    }
+}
+
+/// Longest run of bytes shown as a sprite, enough for a 16x16 SuperChip sprite
+const MAX_SPRITE_LEN: usize = 32;
+
+/// One bit per address in memory
+struct AddressSet([u32; RAM_SIZE / 32]);
+
+impl AddressSet {
+    fn insert(&mut self, address: usize) {
+        if address < RAM_SIZE {
+            self.0[address / 32] |= 1 << (address % 32);
+        }
+    }
+
+    fn contains(&self, address: usize) -> bool {
+        address < RAM_SIZE && (self.0[address / 32] >> (address % 32)) & 1 == 1
+    }
+}
+
+/// Write an annotated hex listing of a ROM loaded at 0x200
+///
+/// Code is shown two bytes per line with its disassembly. Bytes that an `Annn`
+/// instruction points at are shown one per line with an ASCII-art preview,
+/// as they are most likely sprite data.
+pub fn write_listing(rom: &[u8], system: EmulationSystem, f: &mut impl Write) -> fmt::Result {
+    let start = PROGRAM_ADDRESS as usize;
+    let end = start + rom.len();
+
+    let mut sprites = AddressSet([0; RAM_SIZE / 32]);
+    let mut code = AddressSet([0; RAM_SIZE / 32]);
+    for pair in rom.chunks_exact(2) {
+        let instruction = u16::from_be_bytes([pair[0], pair[1]]);
+        let nnn = (instruction & 0x0FFF) as usize;
+        match instruction >> 12 {
+            0xA => sprites.insert(nnn),
+            0x1 | 0x2 | 0xB => code.insert(nnn),
+            _ => (),
+        }
+    }
+
+    let mut address = start;
+    while address < end {
+        if sprites.contains(address) {
+            let run_end = (address + 1..end)
+                .take(MAX_SPRITE_LEN - 1)
+                .find(|&address| sprites.contains(address) || code.contains(address))
+                .unwrap_or(end.min(address + MAX_SPRITE_LEN));
+
+            for address in address..run_end {
+                let byte = rom[address - start];
+                write!(f, "{address:03X}  {byte:02X}     ")?;
+                for bit in (0..8).rev() {
+                    f.write_char(if (byte >> bit) & 1 == 1 { '#' } else { '.' })?;
+                }
+                writeln!(f)?;
+            }
+            address = run_end;
+        } else if address + 1 < end && !sprites.contains(address + 1) {
+            let (high, low) = (rom[address - start], rom[address - start + 1]);
+            let instruction = u16::from_be_bytes([high, low]);
+            writeln!(
+                f,
+                "{address:03X}  {high:02X} {low:02X}  {}",
+                disassemble(instruction, system)
+            )?;
+            address += 2;
+        } else {
+            writeln!(f, "{address:03X}  {:02X}", rom[address - start])?;
+            address += 1;
+        }
+    }
+
+    Ok(())
+}
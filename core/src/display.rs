@@ -0,0 +1,290 @@
+#[cfg(not(feature = "chip8-only"))]
+pub const DISPLAY_WIDTH: usize = 128;
+#[cfg(not(feature = "chip8-only"))]
+pub const DISPLAY_HEIGHT: usize = 64;
+/// A row of pixels, with the leftmost pixel in the most significant bit
+#[cfg(not(feature = "chip8-only"))]
+type Row = u128;
+
+#[cfg(feature = "chip8-only")]
+pub const DISPLAY_WIDTH: usize = 64;
+#[cfg(feature = "chip8-only")]
+pub const DISPLAY_HEIGHT: usize = 32;
+#[cfg(feature = "chip8-only")]
+type Row = u64;
+
+/// Width and height of the block of pixels each low resolution pixel is drawn as
+const LOW_RES_SCALE: usize = DISPLAY_WIDTH / 64;
+
+/// The monochrome framebuffer
+///
+/// The buffer is always at the SuperChip's high resolution,
+/// low resolution pixels are drawn as 2x2 blocks.
+/// Without the SuperChip, it's only the CHIP-8's 64x32 pixels.
+///
+/// Each row is packed into the bits of an integer, to keep it small and draw whole sprite rows at once.
+#[derive(Debug, Clone)]
+pub struct Display {
+    buffer: [Row; DISPLAY_HEIGHT],
+    high_res: bool,
+    /// Bit n is set if row n changed since the frontend last drew it
+    dirty_rows: u64,
+}
+
+impl Default for Display {
+    fn default() -> Self {
+        Self {
+            buffer: [0; DISPLAY_HEIGHT],
+            high_res: false,
+            dirty_rows: u64::MAX,
+        }
+    }
+}
+
+impl Display {
+    pub fn pixel(&self, x: usize, y: usize) -> bool {
+        (self.buffer[y] >> (DISPLAY_WIDTH - 1 - x)) & 0b1 == 1
+    }
+
+    pub fn is_high_res(&self) -> bool {
+        self.high_res
+    }
+
+    /// Rows changed since they were last taken, as a bitmask with bit n for row n
+    ///
+    /// See [`crate::MachineState::take_dirty_rows`].
+    pub fn dirty_rows(&self) -> u64 {
+        self.dirty_rows
+    }
+
+    pub(crate) fn take_dirty_rows(&mut self) -> u64 {
+        core::mem::take(&mut self.dirty_rows)
+    }
+
+    pub(crate) fn set_high_res(&mut self, high_res: bool) {
+        self.high_res = high_res;
+    }
+
+    /// Set a pixel outside of a program, for frontends that draw their own screens, like menus
+    pub fn set_pixel(&mut self, x: usize, y: usize, on: bool) {
+        let bit = 1 << (DISPLAY_WIDTH - 1 - x);
+        if on {
+            self.buffer[y] |= bit;
+        } else {
+            self.buffer[y] &= !bit;
+        }
+        self.dirty_rows |= 1 << y;
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.buffer = [0; DISPLAY_HEIGHT];
+        self.dirty_rows = u64::MAX;
+    }
+
+    /// XOR a sprite onto the display, returning the value VF should be set to
+    ///
+    /// In high resolution mode, `sprite` holds 16 pixel wide rows if `wide` is set.
+    pub(crate) fn draw_sprite(&mut self, x: u8, y: u8, sprite: &[u8], wide: bool) -> u8 {
+        if self.high_res {
+            self.draw_sprite_high_res(x, y, sprite, wide)
+        } else {
+            self.draw_sprite_low_res(x, y, sprite)
+        }
+    }
+
+    fn draw_sprite_high_res(&mut self, x: u8, y: u8, sprite: &[u8], wide: bool) -> u8 {
+        let x = (x % DISPLAY_WIDTH as u8) as usize;
+        let y = (y % DISPLAY_HEIGHT as u8) as usize;
+
+        let n = if wide { sprite.len() / 2 } else { sprite.len() };
+
+        let mut flag = 0;
+
+        for i in 0..n {
+            if y + i >= DISPLAY_HEIGHT {
+                flag += (n - i) as u8;
+                break;
+            }
+
+            let sprite_row = if wide {
+                ((sprite[i * 2] as u16) << 8) + (sprite[i * 2 + 1] as u16)
+            } else {
+                (sprite[i] as u16) << 8
+            };
+            // Pixels past the right edge are shifted out, rather than wrapping around
+            let bits = ((sprite_row as Row) << (DISPLAY_WIDTH - 16)) >> x;
+
+            let row = &mut self.buffer[y + i];
+            if *row & bits != 0 {
+                flag += 1;
+            }
+            *row ^= bits;
+            self.dirty_rows |= 1 << (y + i);
+        }
+
+        flag
+    }
+
+    fn draw_sprite_low_res(&mut self, x: u8, y: u8, sprite: &[u8]) -> u8 {
+        let x = (x % (DISPLAY_WIDTH / LOW_RES_SCALE) as u8) as usize;
+        let y = (y % (DISPLAY_HEIGHT / LOW_RES_SCALE) as u8) as usize;
+
+        let mut flag = 0;
+
+        for (i, &sprite_row) in sprite.iter().enumerate() {
+            let top = LOW_RES_SCALE * (y + i);
+            if top >= DISPLAY_HEIGHT {
+                break;
+            }
+
+            let bits = (scale_row(sprite_row) << (DISPLAY_WIDTH - 8 * LOW_RES_SCALE))
+                >> (LOW_RES_SCALE * x);
+            if self.buffer[top] & bits != 0 {
+                flag = 1;
+            }
+            for row in &mut self.buffer[top..top + LOW_RES_SCALE] {
+                *row ^= bits;
+            }
+            self.dirty_rows |= ((1 << LOW_RES_SCALE) - 1) << top;
+        }
+
+        flag
+    }
+
+    /// 00Cn
+    pub(crate) fn scroll_down(&mut self, n: usize) {
+        let n = if self.high_res { n } else { n * LOW_RES_SCALE };
+        self.buffer.copy_within(0..DISPLAY_HEIGHT - n, n);
+        self.buffer[0..n].fill(0);
+        self.dirty_rows = u64::MAX;
+    }
+
+    /// 00FB
+    pub(crate) fn scroll_right(&mut self) {
+        let n = if self.high_res { 4 } else { 4 * LOW_RES_SCALE };
+        for row in &mut self.buffer {
+            *row >>= n;
+        }
+        self.dirty_rows = u64::MAX;
+    }
+
+    /// 00FC
+    pub(crate) fn scroll_left(&mut self) {
+        let n = if self.high_res { 4 } else { 4 * LOW_RES_SCALE };
+        for row in &mut self.buffer {
+            *row <<= n;
+        }
+        self.dirty_rows = u64::MAX;
+    }
+}
+
+/// Widen each bit of a sprite row into a block of [`LOW_RES_SCALE`] bits
+fn scale_row(sprite_row: u8) -> Row {
+    (0..8)
+        .filter(|bit| (sprite_row >> bit) & 0b1 == 1)
+        .fold(0, |row, bit| {
+            row | ((1 << LOW_RES_SCALE) - 1) << (LOW_RES_SCALE * bit)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DISPLAY_HEIGHT, DISPLAY_WIDTH, Display, LOW_RES_SCALE};
+
+    /// Whether the low resolution pixel at `x`, `y` is lit
+    fn lit(display: &Display, x: usize, y: usize) -> bool {
+        display.pixel(x * LOW_RES_SCALE, y * LOW_RES_SCALE)
+    }
+
+    #[test]
+    fn draw_xors_and_reports_collision() {
+        let mut display = Display::default();
+        assert_eq!(display.draw_sprite(0, 0, &[0b1010_0000], false), 0);
+        assert!(lit(&display, 0, 0));
+        assert!(!lit(&display, 1, 0));
+        assert!(lit(&display, 2, 0));
+
+        // Only turning a pixel off is a collision
+        assert_eq!(display.draw_sprite(0, 0, &[0b0100_0000], false), 0);
+        assert_eq!(display.draw_sprite(0, 0, &[0b1000_0000], false), 1);
+        assert!(!lit(&display, 0, 0));
+        assert!(lit(&display, 1, 0));
+    }
+
+    #[test]
+    fn draw_wraps_start_position() {
+        let mut display = Display::default();
+        display.draw_sprite(64 + 3, 32 + 2, &[0b1000_0000], false);
+        assert!(lit(&display, 3, 2));
+    }
+
+    #[test]
+    fn draw_clips_at_edges() {
+        let mut display = Display::default();
+        display.draw_sprite(60, 31, &[0xFF, 0xFF], false);
+        for x in 60..64 {
+            assert!(lit(&display, x, 31));
+        }
+        // Nothing wraps around to the other side
+        for x in 0..4 {
+            assert!(!lit(&display, x, 31));
+            assert!(!lit(&display, x, 0));
+        }
+        assert!(!lit(&display, 60, 0));
+    }
+
+    #[test]
+    fn draw_marks_rows_dirty() {
+        let mut display = Display::default();
+        display.take_dirty_rows();
+        display.draw_sprite(0, 1, &[0xFF], false);
+        let rows = (1 << LOW_RES_SCALE) - 1;
+        assert_eq!(display.take_dirty_rows(), rows << LOW_RES_SCALE);
+    }
+
+    #[test]
+    fn scroll() {
+        let mut display = Display::default();
+        display.draw_sprite(8, 0, &[0b1000_0000], false);
+
+        display.scroll_down(1);
+        assert!(lit(&display, 8, 1));
+        assert!(!lit(&display, 8, 0));
+
+        display.scroll_right();
+        assert!(lit(&display, 12, 1));
+        display.scroll_left();
+        display.scroll_left();
+        assert!(lit(&display, 4, 1));
+    }
+
+    #[test]
+    fn clear() {
+        let mut display = Display::default();
+        display.draw_sprite(0, 0, &[0xFF], false);
+        display.clear();
+        assert!((0..DISPLAY_HEIGHT).all(|y| (0..DISPLAY_WIDTH).all(|x| !display.pixel(x, y))));
+    }
+
+    #[cfg(not(feature = "chip8-only"))]
+    #[test]
+    fn high_res_counts_clipped_rows() {
+        let mut display = Display::default();
+        display.set_high_res(true);
+        // The SuperChip counts rows that collide or fall off the bottom
+        assert_eq!(display.draw_sprite(0, 62, &[0xFF, 0xFF, 0xFF], false), 1);
+        assert_eq!(display.draw_sprite(0, 62, &[0xFF, 0xFF, 0xFF], false), 3);
+        assert!(!display.pixel(0, 63));
+    }
+
+    #[cfg(not(feature = "chip8-only"))]
+    #[test]
+    fn high_res_draws_wide_sprites() {
+        let mut display = Display::default();
+        display.set_high_res(true);
+        display.draw_sprite(0, 0, &[0x80, 0x01], true);
+        assert!(display.pixel(0, 0));
+        assert!(display.pixel(15, 0));
+        assert!(!display.pixel(0, 1));
+    }
+}
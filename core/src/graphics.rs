@@ -0,0 +1,41 @@
+use crate::{DISPLAY_HEIGHT, DISPLAY_WIDTH, Display};
+use embedded_graphics_core::{
+    Drawable, Pixel,
+    draw_target::DrawTarget,
+    geometry::{OriginDimensions, Point, Size},
+    pixelcolor::BinaryColor,
+};
+
+impl Display {
+    /// Every pixel as an embedded-graphics pixel, left to right then top to bottom
+    pub fn pixels(&self) -> impl Iterator<Item = Pixel<BinaryColor>> + '_ {
+        (0..DISPLAY_HEIGHT).flat_map(move |y| {
+            (0..DISPLAY_WIDTH).map(move |x| {
+                Pixel(
+                    Point::new(x as i32, y as i32),
+                    BinaryColor::from(self.pixel(x, y)),
+                )
+            })
+        })
+    }
+}
+
+impl OriginDimensions for Display {
+    fn size(&self) -> Size {
+        Size::new(DISPLAY_WIDTH as u32, DISPLAY_HEIGHT as u32)
+    }
+}
+
+/// Draw the whole display at the origin of any embedded-graphics display driver,
+/// with `machine_state.display().draw(&mut driver)`
+impl Drawable for Display {
+    type Color = BinaryColor;
+    type Output = ();
+
+    fn draw<D>(&self, target: &mut D) -> Result<Self::Output, D::Error>
+    where
+        D: DrawTarget<Color = Self::Color>,
+    {
+        target.draw_iter(self.pixels())
+    }
+}
@@ -0,0 +1,34 @@
+/// Tracks keypad state that has to persist between instructions
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Keypad {
+    previous_keystate: u16,
+}
+
+impl Keypad {
+    pub fn is_pressed(keystate: u16, key: u8) -> bool {
+        (keystate >> (key & 0xF)) & 0b1 == 1
+    }
+
+    pub fn previous_keystate(&self) -> u16 {
+        self.previous_keystate
+    }
+
+    pub fn set_previous_keystate(&mut self, keystate: u16) {
+        self.previous_keystate = keystate;
+    }
+
+    /// Feed the current keystate to Fx0A, which waits for a key to be released
+    ///
+    /// Returns the released key, or `None` if the instruction should keep waiting.
+    pub fn wait_for_release(&mut self, current_keystate: u16) -> Option<u8> {
+        if current_keystate < self.previous_keystate {
+            let key_diff = self.previous_keystate - current_keystate;
+            self.previous_keystate = 0;
+
+            (0..16).find(|i| (key_diff >> i) & 0b1 == 1)
+        } else {
+            self.previous_keystate = current_keystate;
+            None
+        }
+    }
+}
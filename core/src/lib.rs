@@ -1,13 +1,29 @@
 #![no_std]
 
+mod cpu;
 mod default_font;
-
-use heapless::Vec;
-
-pub const DISPLAY_WIDTH: usize = 128;
-pub const DISPLAY_HEIGHT: usize = 64;
+pub mod disasm;
+mod display;
+#[cfg(feature = "embedded-graphics")]
+mod graphics;
+mod input;
+mod memory;
+mod quirks;
+pub mod sprite;
+mod state;
+
+use core::fmt;
+use cpu::{Bus, Cpu};
+use input::Keypad;
+use memory::{BIG_FONT_ADDRESS, FONT_ADDRESS, Memory};
+
+pub use display::{DISPLAY_HEIGHT, DISPLAY_WIDTH, Display};
+pub use memory::{PROGRAM_ADDRESS, RAM_SIZE};
+pub use quirks::Quirks;
+pub use state::{STATE_SIZE, STATE_SIZE_EXCEPT_RAM};
 
 #[derive(Debug, Clone, thiserror::Error)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum Error {
     #[error("Stack overflowed!")]
     StackOverflow,
@@ -17,6 +33,35 @@ pub enum Error {
 
     #[error("Program exited")]
     ProgramExited,
+
+    #[error("Segment at 0x{address:03X} with length 0x{len:X} does not fit in memory")]
+    SegmentOutOfBounds { address: u16, len: usize },
+
+    #[error("Segments at 0x{0:03X} and 0x{1:03X} overlap")]
+    SegmentsOverlap(u16, u16),
+
+    #[error("Invalid save state")]
+    InvalidState,
+
+    #[error("Invalid image: {0}")]
+    InvalidImage(&'static str),
+}
+
+/// A block of bytes to be loaded at a specific address
+#[derive(Debug, Clone, Copy)]
+pub struct Segment<'a> {
+    pub address: u16,
+    pub bytes: &'a [u8],
+}
+
+impl<'a> Segment<'a> {
+    pub fn new(address: u16, bytes: &'a [u8]) -> Self {
+        Self { address, bytes }
+    }
+
+    fn end(&self) -> usize {
+        self.address as usize + self.bytes.len()
+    }
 }
 
 #[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
@@ -26,509 +71,333 @@ pub enum EmulationSystem {
     SuperChip,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct MachineState {
     system: EmulationSystem,
+    quirks: Quirks,
 
-    pub display_buffer: [[bool; DISPLAY_HEIGHT]; DISPLAY_WIDTH],
-
-    ram: [u8; 4096],
-
-    program_counter: u16,
-    index_register: u16,
-    var_registers: [u8; 16],
-
-    stack: Vec<u16, 16>,
-
-    delay_timer: u8,
-    pub sound_timer: u8,
-
-    previous_keystate: u16,
-
-    high_res: bool,
-}
-
-impl Default for MachineState {
-    fn default() -> Self {
-        Self {
-            system: EmulationSystem::default(),
-
-            display_buffer: [[false; DISPLAY_HEIGHT]; DISPLAY_WIDTH],
-
-            ram: [0; 4096],
-
-            program_counter: 0x200,
-            index_register: 0,
-            var_registers: [0; 16],
-
-            stack: Vec::new(),
-
-            delay_timer: 0,
-            sound_timer: 0,
-
-            previous_keystate: 0,
-
-            high_res: false,
-        }
-    }
+    cpu: Cpu,
+    memory: Memory,
+    display: Display,
+    keypad: Keypad,
 }
 
 impl MachineState {
     pub fn new(system: EmulationSystem) -> Self {
         Self {
             system,
+            quirks: Quirks::for_system(system),
             ..Default::default()
         }
     }
 
-    pub fn load_default_font(&mut self) {
-        self.load_font(&default_font::DEFAULT_FONT);
-        if self.system == EmulationSystem::SuperChip {
-            self.load_big_font(&default_font::DEFAULT_BIG_FONT);
-        }
+    pub fn system(&self) -> EmulationSystem {
+        self.system
     }
 
-    pub fn load_font(&mut self, font: &[u8; 0x50]) {
-        self.ram[0x050..0x050 + size_of_val(font)].copy_from_slice(font);
+    pub fn quirks(&self) -> Quirks {
+        self.quirks
     }
 
-    pub fn load_big_font(&mut self, big_font: &[u8; 0xA0]) {
-        self.ram[0x0A0..0x0A0 + size_of_val(big_font)].copy_from_slice(big_font);
+    pub fn set_quirks(&mut self, quirks: Quirks) {
+        self.quirks = quirks;
     }
 
-    pub fn load_program(&mut self, program: &[u8]) {
-        self.ram[0x200..(0x200 + program.len())].copy_from_slice(program);
+    pub fn display(&self) -> &Display {
+        &self.display
     }
 
-    pub fn tick_timer(&mut self) {
-        if self.delay_timer > 0 {
-            self.delay_timer -= 1;
-        }
-        if self.sound_timer > 0 {
-            self.sound_timer -= 1;
-        }
+    /// Rows of the display changed since the last call, as a bitmask with bit n for row n
+    ///
+    /// Frontends can use this to only redraw what changed. All rows start out dirty.
+    pub fn take_dirty_rows(&mut self) -> u64 {
+        self.display.take_dirty_rows()
     }
 
-    pub fn tick(
-        &mut self,
-        mut held_keys: impl FnMut() -> u16,
-        mut random: impl FnMut() -> u8,
-    ) -> Result<(), Error> {
-        /* FETCH */
-        let instruction: u16 = ((self.ram[self.program_counter as usize] as u16) << 8)
-            + (self.ram[(self.program_counter + 1) as usize] as u16);
-        self.program_counter += 2;
-
-        /* DECODE */
-        let x = ((instruction & 0x0F00) >> 8) as usize;
-        let y = ((instruction & 0x00F0) >> 4) as usize;
-        let n = instruction & 0x000F;
-        let nn = (instruction & 0x00FF) as u8;
-        let nnn = instruction & 0x0FFF;
-
-        // #[cfg(debug_assertions)]
-        // {
-        //     // Display machine state
-        //     println!("PC: 0x{:04X}", self.program_counter - 2);
-        //     println!("Instruction: {:04X}", instruction);
-        //     print!("Stack: ");
-        //     for address in &self.stack {
-        //         print!("{address:03X}, ");
-        //     }
-        //     println!();
-        //     println!("I : 0x{:04X}", self.index_register);
-        //     print!("V : ");
-        //     for var in self.var_registers {
-        //         print!("0x{var:02X}, ");
-        //     }
-        //     println!();
-        //     println!("VX: 0x{:02X}", self.var_registers[x]);
-        //     println!("VY: 0x{:02X}", self.var_registers[y]);
-        // }
-
-        match ((instruction & 0xF000) >> 12, nn, n) {
-            // 00E0
-            (0x0, _, 0x0) if y == 0xE => {
-                self.display_buffer = [[false; DISPLAY_HEIGHT]; DISPLAY_WIDTH];
-            }
-
-            // 00EE
-            (0x0, _, 0xE) if y == 0xE => self.program_counter = self.stack.pop().unwrap_or(0x200),
-
-            // 1nnn
-            (0x1, _, _) => self.program_counter = nnn,
-
-            // 2nnn
-            (0x2, _, _) => {
-                self.stack
-                    .push(self.program_counter)
-                    .map_err(|_| Error::StackOverflow)?;
-                self.program_counter = nnn;
-            }
-
-            // 3xnn
-            (0x3, _, _) => {
-                if self.var_registers[x] == nn {
-                    self.program_counter += 2;
-                }
-            }
-
-            // 4xnn
-            (0x4, _, _) => {
-                if self.var_registers[x] != nn {
-                    self.program_counter += 2;
-                }
-            }
-
-            // 5xy
-            (0x5, _, _) => {
-                if self.var_registers[x] == self.var_registers[y] {
-                    self.program_counter += 2;
-                }
-            }
-
-            // 9xy
-            (0x9, _, _) => {
-                if self.var_registers[x] != self.var_registers[y] {
-                    self.program_counter += 2;
-                }
-            }
+    pub fn program_counter(&self) -> u16 {
+        self.cpu.program_counter
+    }
 
-            // 8xy0
-            (0x8, _, 0x0) => self.var_registers[x] = self.var_registers[y],
+    pub fn index_register(&self) -> u16 {
+        self.cpu.index_register
+    }
 
-            // 8xy1
-            (0x8, _, 0x1) => {
-                self.var_registers[x] |= self.var_registers[y];
-                if self.system == EmulationSystem::Chip8 {
-                    self.var_registers[0xF] = 0;
-                }
-            }
+    /// The variable registers V0 to VF
+    pub fn registers(&self) -> &[u8; 16] {
+        &self.cpu.var_registers
+    }
 
-            // 8xy2
-            (0x8, _, 0x2) => {
-                self.var_registers[x] &= self.var_registers[y];
-                if self.system == EmulationSystem::Chip8 {
-                    self.var_registers[0xF] = 0;
-                }
-            }
+    /// Return addresses, the most recent call last
+    pub fn stack(&self) -> &[u16] {
+        &self.cpu.stack
+    }
 
-            // 8xy3
-            (0x8, _, 0x3) => {
-                self.var_registers[x] ^= self.var_registers[y];
-                if self.system == EmulationSystem::Chip8 {
-                    self.var_registers[0xF] = 0;
-                }
-            }
+    pub fn delay_timer(&self) -> u8 {
+        self.cpu.delay_timer
+    }
 
-            // 8xy4
-            (0x8, _, 0x4) => {
-                let overflow =
-                    if self.var_registers[x] as usize + self.var_registers[y] as usize > 0xFF {
-                        1
-                    } else {
-                        0
-                    };
-                self.var_registers[x] =
-                    u8::wrapping_add(self.var_registers[x], self.var_registers[y]);
-                self.var_registers[0xF] = overflow;
-            }
+    pub fn sound_timer(&self) -> u8 {
+        self.cpu.sound_timer
+    }
 
-            // 8xy5
-            (0x8, _, 0x5) => {
-                let borrow = if self.var_registers[x] >= self.var_registers[y] {
-                    1
-                } else {
-                    0
-                };
+    /// Whether the program is stuck on Fx0A, waiting for a key, with both timers stopped
+    ///
+    /// Running more frames won't change anything until a key does,
+    /// so boards can sleep until one is pressed or released.
+    pub fn is_waiting_for_key(&self) -> bool {
+        self.cpu.delay_timer == 0
+            && self.cpu.sound_timer == 0
+            && self.memory.read_word(self.cpu.program_counter) & 0xF0FF == 0xF00A
+    }
 
-                self.var_registers[x] =
-                    u8::wrapping_sub(self.var_registers[x], self.var_registers[y]);
+    pub fn memory(&self) -> &[u8; RAM_SIZE] {
+        self.memory.ram()
+    }
 
-                self.var_registers[0xF] = borrow;
-            }
+    /// Read a byte of RAM, the address wrapping around to fit in memory
+    pub fn peek(&self, address: u16) -> u8 {
+        self.memory.read_byte(address % RAM_SIZE as u16)
+    }
 
-            // 8xy7
-            (0x8, _, 0x7) => {
-                let borrow = if self.var_registers[y] >= self.var_registers[x] {
-                    1
-                } else {
-                    0
-                };
+    /// Write a byte of RAM, the address wrapping around to fit in memory
+    pub fn poke(&mut self, address: u16, value: u8) {
+        self.memory.write_byte(address % RAM_SIZE as u16, value);
+    }
 
-                self.var_registers[x] =
-                    u8::wrapping_sub(self.var_registers[y], self.var_registers[x]);
+    pub fn set_program_counter(&mut self, address: u16) {
+        self.cpu.program_counter = address;
+    }
 
-                self.var_registers[0xF] = borrow;
-            }
+    pub fn set_index_register(&mut self, value: u16) {
+        self.cpu.index_register = value;
+    }
 
-            // 8xy6
-            (0x8, _, 0x6) => {
-                let shifted_out = self.var_registers[y] & 0b00000001;
-                self.var_registers[x] =
-                    self.var_registers[if self.system == EmulationSystem::SuperChip {
-                        x
-                    } else {
-                        y
-                    } & 0xF]
-                        >> 1;
-                self.var_registers[0xF] = shifted_out;
-            }
+    /// Set the variable register Vx
+    pub fn set_register(&mut self, x: usize, value: u8) {
+        self.cpu.var_registers[x] = value;
+    }
 
-            // 8xyE
-            (0x8, _, 0xE) => {
-                let shifted_out = (self.var_registers[y] & 0b10000000) >> 7;
-                self.var_registers[x] =
-                    self.var_registers[if self.system == EmulationSystem::SuperChip {
-                        x
-                    } else {
-                        y
-                    } & 0xF]
-                        << 1;
-                self.var_registers[0xF] = shifted_out;
-            }
+    /// Replace a return address on the stack, at `depth` from the bottom
+    pub fn set_stack_entry(&mut self, depth: usize, address: u16) {
+        self.cpu.stack[depth] = address;
+    }
 
-            // 6xnn
-            (0x6, _, _) => self.var_registers[x] = nn,
+    pub fn set_delay_timer(&mut self, value: u8) {
+        self.cpu.delay_timer = value;
+    }
 
-            // 7xnn
-            (0x7, _, _) => self.var_registers[x] = u8::wrapping_add(self.var_registers[x], nn),
+    pub fn set_sound_timer(&mut self, value: u8) {
+        self.cpu.sound_timer = value;
+    }
 
-            // Annn
-            (0xA, _, _) => self.index_register = nnn,
+    pub fn load_default_font(&mut self) {
+        self.load_font(&default_font::DEFAULT_FONT);
+        if self.system == EmulationSystem::SuperChip {
+            self.load_big_font(&default_font::DEFAULT_BIG_FONT);
+        }
+    }
 
-            // Bnnn
-            (0xB, _, _) => {
-                self.program_counter = nnn
-                    + self.var_registers[if self.system == EmulationSystem::SuperChip {
-                        x
-                    } else {
-                        0
-                    }] as u16
-            }
+    pub fn load_font(&mut self, font: &[u8; 0x50]) {
+        self.memory.load(FONT_ADDRESS, font);
+    }
 
-            // Cxnn
-            (0xC, _, _) => {
-                self.var_registers[x] = random() & nn;
-            }
+    pub fn load_big_font(&mut self, big_font: &[u8; 0xA0]) {
+        self.memory.load(BIG_FONT_ADDRESS, big_font);
+    }
 
-            // Dxyn
-            (0xD, _, _) => {
-                if self.high_res {
-                    let x = (self.var_registers[x] % DISPLAY_WIDTH as u8) as usize;
-                    let y = (self.var_registers[y] % DISPLAY_HEIGHT as u8) as usize;
-
-                    let (n, sprite16) = if n == 0 {
-                        (16, true)
-                    } else {
-                        (n as usize, false)
-                    };
-
-                    self.var_registers[0xF] = 0;
-
-                    for i in 0..n {
-                        if y + i >= DISPLAY_HEIGHT {
-                            self.var_registers[0xF] += (n - i) as u8;
-                            break;
-                        }
-
-                        let sprite_row = if sprite16 {
-                            ((self.ram[self.index_register as usize + i * 2] as u16) << 8)
-                                + (self.ram[(self.index_register as usize + i * 2) + 1] as u16)
-                        } else {
-                            (self.ram[self.index_register as usize + i] as u16) << 8
-                        };
-
-                        let mut collision = false;
-
-                        for j in 0..16 {
-                            if x + j >= DISPLAY_WIDTH {
-                                break;
-                            }
-
-                            let pixel = (sprite_row >> (15 - j)) & 0b1 == 1;
-
-                            if pixel {
-                                if self.display_buffer[x + j][y + i] {
-                                    collision = true;
-                                }
-
-                                self.display_buffer[x + j][y + i] =
-                                    !self.display_buffer[x + j][y + i];
-                            }
-                        }
-
-                        if collision {
-                            self.var_registers[0xF] += 1;
-                        }
-                    }
-                } else {
-                    let x = (self.var_registers[x] % (DISPLAY_WIDTH / 2) as u8) as usize;
-                    let y = (self.var_registers[y] % (DISPLAY_HEIGHT / 2) as u8) as usize;
-
-                    let n = n as usize;
-
-                    self.var_registers[0xF] = 0;
-
-                    for i in 0..n {
-                        if 2 * (y + i) >= DISPLAY_HEIGHT {
-                            break;
-                        }
-
-                        let sprite_row = self.ram[self.index_register as usize + i];
-
-                        for j in 0..8 {
-                            if 2 * (x + j) >= DISPLAY_WIDTH {
-                                break;
-                            }
-
-                            if (sprite_row >> (7 - j)) & 0b1 == 1 {
-                                if self.display_buffer[2 * (x + j)][2 * (y + i)] {
-                                    self.var_registers[0xF] = 1;
-                                }
-
-                                #[expect(clippy::identity_op)]
-                                {
-                                    self.display_buffer[2 * (x + j) + 0][2 * (y + i) + 0] =
-                                        !self.display_buffer[2 * (x + j) + 0][2 * (y + i) + 0];
-                                    self.display_buffer[2 * (x + j) + 0][2 * (y + i) + 1] =
-                                        !self.display_buffer[2 * (x + j) + 0][2 * (y + i) + 1];
-                                    self.display_buffer[2 * (x + j) + 1][2 * (y + i) + 0] =
-                                        !self.display_buffer[2 * (x + j) + 1][2 * (y + i) + 0];
-                                    self.display_buffer[2 * (x + j) + 1][2 * (y + i) + 1] =
-                                        !self.display_buffer[2 * (x + j) + 1][2 * (y + i) + 1];
-                                }
-                            }
-                        }
-                    }
-                }
-            }
+    pub fn load_program(&mut self, program: &[u8]) {
+        self.memory.load(PROGRAM_ADDRESS, program);
+    }
 
-            // Ex9E
-            (0xE, 0x9E, _) => {
-                if (held_keys() >> (self.var_registers[x] & 0xF)) & 0b1 == 1 {
-                    self.program_counter += 2;
-                }
+    /// Load several segments into memory at once
+    ///
+    /// All segments are validated before anything is written, so memory is left
+    /// untouched if any segment is out of bounds or overlaps another.
+    pub fn load_segments(&mut self, segments: &[Segment]) -> Result<(), Error> {
+        for (i, segment) in segments.iter().enumerate() {
+            if segment.end() > RAM_SIZE {
+                return Err(Error::SegmentOutOfBounds {
+                    address: segment.address,
+                    len: segment.bytes.len(),
+                });
             }
 
-            // ExA1
-            (0xE, 0xA1, _) => {
-                if (held_keys() >> (self.var_registers[x] & 0xF)) & 0b1 == 0 {
-                    self.program_counter += 2;
+            for other in &segments[..i] {
+                if (segment.address as usize) < other.end()
+                    && (other.address as usize) < segment.end()
+                {
+                    return Err(Error::SegmentsOverlap(other.address, segment.address));
                 }
             }
+        }
 
-            // Fx07
-            (0xF, 0x07, _) => self.var_registers[x] = self.delay_timer,
-
-            // Fx15
-            (0xF, 0x15, _) => self.delay_timer = self.var_registers[x],
-
-            // Fx18
-            (0xF, 0x18, _) => self.sound_timer = self.var_registers[x],
-
-            // Fx1E
-            (0xF, 0x1E, _) => self.index_register += self.var_registers[x] as u16,
+        for segment in segments {
+            self.memory.load(segment.address, segment.bytes);
+        }
 
-            // Fx0A
-            (0xF, 0xA, _) => {
-                let current_keystate = held_keys();
+        Ok(())
+    }
 
-                if current_keystate < self.previous_keystate {
-                    let key_diff = self.previous_keystate - current_keystate;
+    /// Write a human readable report of the machine state
+    pub fn dump(&self, f: &mut impl fmt::Write) -> fmt::Result {
+        writeln!(f, "System: {:?}", self.system)?;
+        write!(f, "PC: 0x{:04X}", self.cpu.program_counter)?;
+        if (self.cpu.program_counter as usize) < RAM_SIZE - 1 {
+            write!(
+                f,
+                " (instruction: {:04X})",
+                self.memory.read_word(self.cpu.program_counter)
+            )?;
+        }
+        writeln!(f)?;
+        writeln!(f, "I : 0x{:04X}", self.cpu.index_register)?;
 
-                    for i in 0..16 {
-                        if (key_diff >> i) & 0b1 == 1 {
-                            self.var_registers[x] = i;
-                            break;
-                        }
-                    }
-                    self.previous_keystate = 0;
-                } else {
-                    self.previous_keystate = current_keystate;
-                    self.program_counter -= 2;
-                }
-            }
+        write!(f, "V :")?;
+        for var in self.cpu.var_registers {
+            write!(f, " {var:02X}")?;
+        }
+        writeln!(f)?;
 
-            // Fx29
-            (0xF, 0x29, _) => {
-                self.index_register = (0x050 + (self.var_registers[x] & 0xF) * 5) as u16;
-            }
+        write!(f, "Stack ({}/16):", self.cpu.stack.len())?;
+        for address in &self.cpu.stack {
+            write!(f, " {address:03X}")?;
+        }
+        writeln!(f)?;
+
+        writeln!(
+            f,
+            "Delay timer: {}, sound timer: {}",
+            self.cpu.delay_timer, self.cpu.sound_timer
+        )?;
+        writeln!(f, "High resolution: {}", self.display.is_high_res())?;
+
+        let Quirks {
+            logic_resets_flag,
+            shift_uses_vy,
+            jump_uses_vx,
+            memory_increments_index,
+        } = self.quirks;
+        writeln!(f, "Quirks:")?;
+        writeln!(f, "  logic_resets_flag: {logic_resets_flag}")?;
+        writeln!(f, "  shift_uses_vy: {shift_uses_vy}")?;
+        writeln!(f, "  jump_uses_vx: {jump_uses_vx}")?;
+        writeln!(f, "  memory_increments_index: {memory_increments_index}")
+    }
 
-            // Fx33
-            #[allow(clippy::identity_op)]
-            (0xF, 0x33, _) => {
-                self.ram[self.index_register as usize + 2] = (self.var_registers[x] / 1) % 10;
-                self.ram[self.index_register as usize + 1] = (self.var_registers[x] / 10) % 10;
-                self.ram[self.index_register as usize + 0] = (self.var_registers[x] / 100) % 10;
-            }
+    pub fn tick_timer(&mut self) {
+        self.cpu.tick_timers();
+    }
 
-            // Fx55
-            (0xF, 0x55, _) => {
-                for (i, var) in self.var_registers[..=(x)].iter().enumerate() {
-                    self.ram[self.index_register as usize + i] = *var;
-                }
-                if self.system == EmulationSystem::Chip8 {
-                    self.index_register += (x) as u16 + 1;
-                }
-            }
+    /// Run one 60 Hz frame, ticking the timers then executing `instructions` instructions
+    pub fn run_frame(
+        &mut self,
+        instructions: u32,
+        held_keys: impl FnMut() -> u16,
+        random: impl FnMut() -> u8,
+    ) -> Result<(), Error> {
+        self.run_frame_until(instructions, held_keys, random, |_| false)
+            .map(|_| ())
+    }
 
-            // Fx65
-            (0xF, 0x65, _) => {
-                for (i, var) in self.var_registers[..=(x)].iter_mut().enumerate() {
-                    *var = self.ram[self.index_register as usize + i];
-                }
-                if self.system == EmulationSystem::Chip8 {
-                    self.index_register += (x) as u16 + 1;
-                }
+    /// Run a frame like [`run_frame`](Self::run_frame),
+    /// stopping before any instruction that `breakpoint` returns true for
+    ///
+    /// Returns whether a breakpoint stopped the frame early.
+    pub fn run_frame_until(
+        &mut self,
+        instructions: u32,
+        mut held_keys: impl FnMut() -> u16,
+        mut random: impl FnMut() -> u8,
+        mut breakpoint: impl FnMut(&Self) -> bool,
+    ) -> Result<bool, Error> {
+        self.tick_timer();
+        for _ in 0..instructions {
+            if breakpoint(self) {
+                return Ok(true);
             }
+            self.tick(&mut held_keys, &mut random)?;
+        }
+        Ok(false)
+    }
 
-            _ => {
-                if self.system == EmulationSystem::SuperChip {
-                    match instruction {
-                        0x00FD => return Err(Error::ProgramExited),
-
-                        0x00FE => self.high_res = false,
-
-                        0x00FF => self.high_res = true,
-
-                        _ if instruction & 0xF0FF == 0xF075 => (),
-
-                        _ if instruction & 0xF0FF == 0xF085 => (),
-
-                        0x00FB => {
-                            let n = if self.high_res { 4 } else { 8 };
-                            self.display_buffer.copy_within(0..DISPLAY_WIDTH - n, n);
-                            self.display_buffer[0..n].fill([false; DISPLAY_HEIGHT]);
-                        }
-
-                        0x00FC => {
-                            let n = if self.high_res { 4 } else { 8 };
-                            self.display_buffer.copy_within(n..DISPLAY_WIDTH, 0);
-                            self.display_buffer[DISPLAY_WIDTH - n..DISPLAY_WIDTH]
-                                .fill([false; DISPLAY_HEIGHT]);
-                        }
-
-                        _ if instruction & 0xFFF0 == 0x00C0 => {
-                            let n = if self.high_res { n } else { n * 2 } as usize;
-                            for column in &mut self.display_buffer {
-                                column.copy_within(0..DISPLAY_HEIGHT - n, n);
-                                column[0..n].fill(false);
-                            }
-                        }
+    pub fn tick(
+        &mut self,
+        held_keys: impl FnMut() -> u16,
+        random: impl FnMut() -> u8,
+    ) -> Result<(), Error> {
+        #[cfg(feature = "defmt")]
+        let (address, opcode) = (
+            self.cpu.program_counter,
+            self.memory.read_word(self.cpu.program_counter),
+        );
+        #[cfg(feature = "defmt")]
+        defmt::trace!("{=u16:03X}  {=u16:04X}", address, opcode);
+
+        let result = self.cpu.step(&mut Bus {
+            system: self.system,
+            quirks: &self.quirks,
+            memory: &mut self.memory,
+            display: &mut self.display,
+            keypad: &mut self.keypad,
+            held_keys,
+            random,
+        });
+
+        #[cfg(feature = "defmt")]
+        if let Err(err) = &result {
+            defmt::error!("{} at {=u16:03X}, running {=u16:04X}", err, address, opcode);
+        }
+        result
+    }
+}
 
-                        _ if instruction & 0xF0FF == 0xF030 => {
-                            self.index_register = 0x0A0 + (self.var_registers[x] & 0xF) as u16 * 10;
-                        }
+#[cfg(test)]
+mod tests {
+    use super::{EmulationSystem, Error, MachineState, PROGRAM_ADDRESS, RAM_SIZE, Segment};
+
+    #[test]
+    fn load_segments() {
+        let mut machine_state = MachineState::new(EmulationSystem::Chip8);
+        let segments = [
+            Segment::new(PROGRAM_ADDRESS, &[1, 2]),
+            Segment::new(0x300, &[3]),
+            Segment::new(RAM_SIZE as u16 - 1, &[4]),
+        ];
+        machine_state.load_segments(&segments).unwrap();
+        assert_eq!(machine_state.peek(0x200), 1);
+        assert_eq!(machine_state.peek(0x201), 2);
+        assert_eq!(machine_state.peek(0x300), 3);
+        assert_eq!(machine_state.peek(RAM_SIZE as u16 - 1), 4);
+    }
 
-                        _ => return Err(Error::IllegalInstruction(instruction)),
-                    }
-                } else {
-                    return Err(Error::IllegalInstruction(instruction));
-                }
-            }
-        }
+    #[test]
+    fn load_segments_out_of_bounds() {
+        let mut machine_state = MachineState::new(EmulationSystem::Chip8);
+        let segments = [
+            Segment::new(PROGRAM_ADDRESS, &[1]),
+            Segment::new(RAM_SIZE as u16 - 1, &[2, 3]),
+        ];
+        assert!(matches!(
+            machine_state.load_segments(&segments),
+            Err(Error::SegmentOutOfBounds { len: 2, .. })
+        ));
+        // Nothing is loaded, not even the segments that fit
+        assert_eq!(machine_state.peek(PROGRAM_ADDRESS), 0);
+    }
 
-        Ok(())
+    #[test]
+    fn load_segments_overlapping() {
+        let mut machine_state = MachineState::new(EmulationSystem::Chip8);
+        let segments = [
+            Segment::new(0x300, &[1, 2, 3]),
+            Segment::new(0x303, &[4]),
+            Segment::new(0x302, &[5]),
+        ];
+        assert!(matches!(
+            machine_state.load_segments(&segments),
+            Err(Error::SegmentsOverlap(0x300, 0x302))
+        ));
+        assert_eq!(machine_state.peek(0x300), 0);
+        assert_eq!(machine_state.peek(0x303), 0);
     }
 }
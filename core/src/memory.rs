@@ -0,0 +1,49 @@
+#[cfg(not(feature = "small-ram"))]
+pub const RAM_SIZE: usize = 4096;
+#[cfg(feature = "small-ram")]
+pub const RAM_SIZE: usize = 0x600;
+
+pub const FONT_ADDRESS: u16 = 0x050;
+pub const BIG_FONT_ADDRESS: u16 = 0x0A0;
+pub const PROGRAM_ADDRESS: u16 = 0x200;
+
+/// CHIP-8 RAM, where addresses wrap around to fit, which only matters with less than 4 KiB
+#[derive(Debug, Clone)]
+pub(crate) struct Memory {
+    ram: [u8; RAM_SIZE],
+}
+
+impl Default for Memory {
+    fn default() -> Self {
+        Self { ram: [0; RAM_SIZE] }
+    }
+}
+
+impl Memory {
+    pub fn read_byte(&self, address: u16) -> u8 {
+        self.ram[address as usize % RAM_SIZE]
+    }
+
+    /// Read a big-endian word, as instructions are stored
+    pub fn read_word(&self, address: u16) -> u16 {
+        ((self.read_byte(address) as u16) << 8) + (self.read_byte(address.wrapping_add(1)) as u16)
+    }
+
+    pub fn write_byte(&mut self, address: u16, value: u8) {
+        self.ram[address as usize % RAM_SIZE] = value;
+    }
+
+    /// Bytes from `address`, cut short at the end of memory
+    pub fn slice(&self, address: u16, len: usize) -> &[u8] {
+        let start = address as usize % RAM_SIZE;
+        &self.ram[start..(start + len).min(RAM_SIZE)]
+    }
+
+    pub fn ram(&self) -> &[u8; RAM_SIZE] {
+        &self.ram
+    }
+
+    pub fn load(&mut self, address: u16, bytes: &[u8]) {
+        self.ram[address as usize..address as usize + bytes.len()].copy_from_slice(bytes);
+    }
+}
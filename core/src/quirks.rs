@@ -0,0 +1,51 @@
+use crate::EmulationSystem;
+
+/// Behaviours that differ between CHIP-8 interpreters
+///
+/// Each system has a sensible default set, but ROMs written for a particular
+/// interpreter sometimes need individual quirks toggled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Quirks {
+    /// 8xy1, 8xy2, and 8xy3 reset VF to 0
+    pub logic_resets_flag: bool,
+
+    /// 8xy6 and 8xyE shift VY into VX, instead of shifting VX in place
+    pub shift_uses_vy: bool,
+
+    /// Bnnn jumps to nnn + VX instead of nnn + V0
+    pub jump_uses_vx: bool,
+
+    /// Fx55 and Fx65 leave I pointing past the last register accessed
+    pub memory_increments_index: bool,
+}
+
+impl Quirks {
+    pub const fn for_system(system: EmulationSystem) -> Self {
+        match system {
+            EmulationSystem::Chip8 => Self {
+                logic_resets_flag: true,
+                shift_uses_vy: true,
+                jump_uses_vx: false,
+                memory_increments_index: true,
+            },
+            EmulationSystem::SuperChip => Self {
+                logic_resets_flag: false,
+                shift_uses_vy: false,
+                jump_uses_vx: true,
+                memory_increments_index: false,
+            },
+        }
+    }
+}
+
+impl Default for Quirks {
+    fn default() -> Self {
+        Self::for_system(EmulationSystem::default())
+    }
+}
+
+impl From<EmulationSystem> for Quirks {
+    fn from(system: EmulationSystem) -> Self {
+        Self::for_system(system)
+    }
+}
@@ -0,0 +1,244 @@
+use crate::Error;
+use core::fmt;
+
+/// A monochrome image that can be cut into sprites
+pub trait Bitmap {
+    fn width(&self) -> usize;
+    fn height(&self) -> usize;
+
+    /// Whether the pixel at the in-bounds coordinates is set
+    fn pixel(&self, x: usize, y: usize) -> bool;
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SpriteFormat {
+    /// 8 pixel wide strips of up to 15 rows, drawn with Dxyn
+    #[default]
+    Strip,
+    /// 16x16 sprites of 32 bytes, drawn with the SuperChip's Dxy0
+    SuperChip16,
+}
+
+impl SpriteFormat {
+    pub const fn tile_width(self) -> usize {
+        match self {
+            SpriteFormat::Strip => 8,
+            SpriteFormat::SuperChip16 => 16,
+        }
+    }
+
+    pub const fn tile_height(self) -> usize {
+        match self {
+            SpriteFormat::Strip => 15,
+            SpriteFormat::SuperChip16 => 16,
+        }
+    }
+}
+
+/// One sprite cut out of a bitmap
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Tile {
+    pub column: usize,
+    pub row: usize,
+    format: SpriteFormat,
+    height: usize,
+}
+
+impl Tile {
+    /// The sprite data, as it should be laid out in memory
+    ///
+    /// Pixels past the edge of the bitmap are left unset.
+    pub fn bytes<'a>(&self, bitmap: &'a impl Bitmap) -> impl Iterator<Item = u8> + 'a {
+        let x = self.column * self.format.tile_width();
+        let y = self.row * self.format.tile_height();
+        let bytes_per_row = self.format.tile_width() / 8;
+
+        (y..y + self.height).flat_map(move |y| {
+            (0..bytes_per_row).map(move |byte| {
+                (0..8).fold(0, |acc, bit| {
+                    let x = x + byte * 8 + bit;
+                    let set = x < bitmap.width() && y < bitmap.height() && bitmap.pixel(x, y);
+                    acc | ((set as u8) << (7 - bit))
+                })
+            })
+        })
+    }
+}
+
+/// Cut a bitmap into tiles, left to right then top to bottom
+pub fn tiles(bitmap: &impl Bitmap, format: SpriteFormat) -> impl Iterator<Item = Tile> {
+    let columns = bitmap.width().div_ceil(format.tile_width());
+    let rows = bitmap.height().div_ceil(format.tile_height());
+    let height = bitmap.height();
+
+    (0..rows).flat_map(move |row| {
+        let tile_height = match format {
+            // Strips only need as many rows as are left in the image
+            SpriteFormat::Strip => (height - row * format.tile_height()).min(format.tile_height()),
+            SpriteFormat::SuperChip16 => format.tile_height(),
+        };
+
+        (0..columns).map(move |column| Tile {
+            column,
+            row,
+            format,
+            height: tile_height,
+        })
+    })
+}
+
+/// Write the sprites as Octo assembler directives, one labelled block per tile
+///
+/// Tiles are labelled `{label}-{column}-{row}`.
+pub fn write_octo(
+    bitmap: &impl Bitmap,
+    format: SpriteFormat,
+    label: &str,
+    f: &mut impl fmt::Write,
+) -> fmt::Result {
+    let bytes_per_row = format.tile_width() / 8;
+
+    for tile in tiles(bitmap, format) {
+        writeln!(f, ": {label}-{}-{}", tile.column, tile.row)?;
+        for (i, byte) in tile.bytes(bitmap).enumerate() {
+            if i % bytes_per_row == 0 {
+                write!(f, "\t")?;
+            } else {
+                write!(f, " ")?;
+            }
+            write!(f, "0b{byte:08b}")?;
+            if i % bytes_per_row == bytes_per_row - 1 {
+                writeln!(f)?;
+            }
+        }
+        writeln!(f)?;
+    }
+
+    Ok(())
+}
+
+/// A borrowed PBM (portable bitmap) image, in either plain (P1) or raw (P4) form
+///
+/// Plain images are scanned on every pixel lookup, which is fine for sprite sheets
+/// but slow for large images.
+#[derive(Debug, Clone)]
+pub struct Pbm<'a> {
+    width: usize,
+    height: usize,
+    data: PbmData<'a>,
+}
+
+#[derive(Debug, Clone)]
+enum PbmData<'a> {
+    /// Packed rows, padded to a whole byte
+    Raw(&'a [u8]),
+    /// ASCII pixels, possibly separated by whitespace
+    Plain(&'a [u8]),
+}
+
+impl<'a> Pbm<'a> {
+    pub fn parse(file: &'a [u8]) -> Result<Self, Error> {
+        let plain = match file.get(..2) {
+            Some(b"P1") => true,
+            Some(b"P4") => false,
+            _ => return Err(Error::InvalidImage("not a PBM image")),
+        };
+
+        let mut pos = 2;
+        let width = parse_header_number(file, &mut pos)?;
+        let height = parse_header_number(file, &mut pos)?;
+        let pixels = width
+            .checked_mul(height)
+            .ok_or(Error::InvalidImage("image dimensions are too large"))?;
+
+        if plain {
+            let data = &file[pos..];
+
+            let mut count = 0;
+            for byte in data {
+                match byte {
+                    b'0' | b'1' => count += 1,
+                    byte if byte.is_ascii_whitespace() => (),
+                    _ => return Err(Error::InvalidImage("unexpected character in pixel data")),
+                }
+            }
+            if count < pixels {
+                return Err(Error::InvalidImage("pixel data is truncated"));
+            }
+
+            Ok(Self {
+                width,
+                height,
+                data: PbmData::Plain(data),
+            })
+        } else {
+            // Exactly one whitespace character separates the header from the data
+            let data = file
+                .get(pos + 1..pos + 1 + width.div_ceil(8) * height)
+                .ok_or(Error::InvalidImage("pixel data is truncated"))?;
+
+            Ok(Self {
+                width,
+                height,
+                data: PbmData::Raw(data),
+            })
+        }
+    }
+}
+
+impl Bitmap for Pbm<'_> {
+    fn width(&self) -> usize {
+        self.width
+    }
+
+    fn height(&self) -> usize {
+        self.height
+    }
+
+    fn pixel(&self, x: usize, y: usize) -> bool {
+        match &self.data {
+            PbmData::Raw(data) => {
+                let byte = data[y * self.width.div_ceil(8) + x / 8];
+                (byte >> (7 - x % 8)) & 0b1 == 1
+            }
+            PbmData::Plain(data) => {
+                data.iter()
+                    .filter(|byte| !byte.is_ascii_whitespace())
+                    .nth(y * self.width + x)
+                    == Some(&b'1')
+            }
+        }
+    }
+}
+
+/// Skip whitespace and comments, then parse a decimal number from a PBM header
+fn parse_header_number(file: &[u8], pos: &mut usize) -> Result<usize, Error> {
+    loop {
+        match file.get(*pos) {
+            Some(b'#') => {
+                while file.get(*pos).is_some_and(|&byte| byte != b'\n') {
+                    *pos += 1;
+                }
+            }
+            Some(byte) if byte.is_ascii_whitespace() => *pos += 1,
+            Some(_) => break,
+            None => return Err(Error::InvalidImage("header is truncated")),
+        }
+    }
+
+    let mut number: usize = 0;
+    let start = *pos;
+    while let Some(byte) = file.get(*pos).filter(|byte| byte.is_ascii_digit()) {
+        number = number
+            .checked_mul(10)
+            .and_then(|number| number.checked_add((byte - b'0') as usize))
+            .ok_or(Error::InvalidImage("image dimensions are too large"))?;
+        *pos += 1;
+    }
+
+    if *pos == start || number == 0 {
+        Err(Error::InvalidImage("malformed image dimensions"))
+    } else {
+        Ok(number)
+    }
+}
@@ -0,0 +1,312 @@
+use crate::{
+    DISPLAY_HEIGHT, DISPLAY_WIDTH, EmulationSystem, Error, MachineState, Quirks, RAM_SIZE,
+    cpu::STACK_SIZE,
+};
+
+const MAGIC: &[u8; 4] = b"C8ST";
+const VERSION: u8 = 1;
+
+const DISPLAY_BYTES: usize = DISPLAY_WIDTH * DISPLAY_HEIGHT / 8;
+
+/// Size of a serialised machine state
+pub const STATE_SIZE: usize = MAGIC.len()
+    + 1 // version
+    + 1 // system
+    + 1 // quirks
+    + 2 // program counter
+    + 2 // index register
+    + 16 // variable registers
+    + 1 // stack length
+    + 2 * STACK_SIZE // stack
+    + 1 // delay timer
+    + 1 // sound timer
+    + 2 // previous keystate
+    + 1 // high resolution
+    + DISPLAY_BYTES
+    + RAM_SIZE;
+
+/// Size of a serialised machine state without its RAM, as saved by
+/// [`MachineState::save_state_except_ram`]
+pub const STATE_SIZE_EXCEPT_RAM: usize = STATE_SIZE - RAM_SIZE;
+
+/// Writes a state a byte at a time, so it doesn't need a buffer to go in
+struct Writer<F: FnMut(u8)>(F);
+
+impl<F: FnMut(u8)> Writer<F> {
+    fn bytes(&mut self, bytes: &[u8]) {
+        bytes.iter().for_each(|&byte| (self.0)(byte));
+    }
+
+    fn u8(&mut self, value: u8) {
+        (self.0)(value);
+    }
+
+    fn u16(&mut self, value: u16) {
+        self.bytes(&value.to_be_bytes());
+    }
+}
+
+/// Reads a state a byte at a time
+struct Reader<F: FnMut() -> u8>(F);
+
+impl<F: FnMut() -> u8> Reader<F> {
+    fn bytes<const N: usize>(&mut self) -> [u8; N] {
+        core::array::from_fn(|_| (self.0)())
+    }
+
+    fn u8(&mut self) -> u8 {
+        (self.0)()
+    }
+
+    fn u16(&mut self) -> u16 {
+        u16::from_be_bytes(self.bytes())
+    }
+}
+
+impl MachineState {
+    /// Serialise the entire machine state, so it can be restored with [`Self::load_state`]
+    pub fn save_state(&self, buffer: &mut [u8; STATE_SIZE]) {
+        let mut bytes = buffer.iter_mut();
+        let mut w = Writer(|byte| *bytes.next().unwrap() = byte);
+        self.write_state(&mut w);
+        w.bytes(self.memory.ram());
+    }
+
+    /// Restore a machine state serialised with [`Self::save_state`]
+    ///
+    /// The current state is left untouched if the buffer is not a valid state.
+    pub fn load_state(&mut self, buffer: &[u8]) -> Result<(), Error> {
+        if buffer.len() != STATE_SIZE {
+            return Err(Error::InvalidState);
+        }
+        let (state, ram) = buffer.split_at(STATE_SIZE_EXCEPT_RAM);
+        let mut bytes = state.iter().copied();
+        self.read_state(&mut Reader(|| bytes.next().unwrap()))?;
+        self.memory.load(0, ram);
+        Ok(())
+    }
+
+    /// Serialise everything but RAM, a byte at a time, for devices without room for the whole
+    /// state
+    ///
+    /// It's the first [`STATE_SIZE_EXCEPT_RAM`] bytes of what [`Self::save_state`] saves, and can
+    /// be restored with [`Self::load_state_except_ram`].
+    pub fn save_state_except_ram(&self, write: impl FnMut(u8)) {
+        self.write_state(&mut Writer(write));
+    }
+
+    /// Restore everything but RAM from a state serialised with [`Self::save_state_except_ram`],
+    /// read a byte at a time
+    ///
+    /// RAM is left as it is, and the current state is left untouched if it's not a valid state.
+    pub fn load_state_except_ram(&mut self, read: impl FnMut() -> u8) -> Result<(), Error> {
+        self.read_state(&mut Reader(read))
+    }
+
+    fn write_state(&self, w: &mut Writer<impl FnMut(u8)>) {
+        w.bytes(MAGIC);
+        w.u8(VERSION);
+
+        w.u8(match self.system {
+            EmulationSystem::Chip8 => 0,
+            EmulationSystem::SuperChip => 1,
+        });
+        let Quirks {
+            logic_resets_flag,
+            shift_uses_vy,
+            jump_uses_vx,
+            memory_increments_index,
+        } = self.quirks;
+        w.u8(logic_resets_flag as u8
+            | (shift_uses_vy as u8) << 1
+            | (jump_uses_vx as u8) << 2
+            | (memory_increments_index as u8) << 3);
+
+        w.u16(self.cpu.program_counter);
+        w.u16(self.cpu.index_register);
+        w.bytes(&self.cpu.var_registers);
+        w.u8(self.cpu.stack.len() as u8);
+        for i in 0..STACK_SIZE {
+            w.u16(self.cpu.stack.get(i).copied().unwrap_or(0));
+        }
+        w.u8(self.cpu.delay_timer);
+        w.u8(self.cpu.sound_timer);
+
+        w.u16(self.keypad.previous_keystate());
+
+        w.u8(self.display.is_high_res() as u8);
+        for y in 0..DISPLAY_HEIGHT {
+            for x in (0..DISPLAY_WIDTH).step_by(8) {
+                w.u8((0..8).fold(0, |byte, bit| {
+                    byte | (self.display.pixel(x + bit, y) as u8) << (7 - bit)
+                }));
+            }
+        }
+    }
+
+    fn read_state(&mut self, r: &mut Reader<impl FnMut() -> u8>) -> Result<(), Error> {
+        if r.bytes::<4>() != *MAGIC || r.u8() != VERSION {
+            return Err(Error::InvalidState);
+        }
+
+        let system = match r.u8() {
+            0 => EmulationSystem::Chip8,
+            1 => EmulationSystem::SuperChip,
+            _ => return Err(Error::InvalidState),
+        };
+        let quirks = r.u8();
+        let quirks = Quirks {
+            logic_resets_flag: quirks & 0b0001 != 0,
+            shift_uses_vy: quirks & 0b0010 != 0,
+            jump_uses_vx: quirks & 0b0100 != 0,
+            memory_increments_index: quirks & 0b1000 != 0,
+        };
+        let program_counter = r.u16();
+        let index_register = r.u16();
+        let var_registers: [u8; 16] = r.bytes();
+        let stack_len = r.u8() as usize;
+        if stack_len > STACK_SIZE {
+            return Err(Error::InvalidState);
+        }
+
+        // Everything that could be invalid has been checked, so the state is loaded in place,
+        // without room for a second machine on devices with little RAM
+        self.system = system;
+        self.quirks = quirks;
+        self.cpu.program_counter = program_counter;
+        self.cpu.index_register = index_register;
+        self.cpu.var_registers.copy_from_slice(&var_registers);
+        self.cpu.stack.clear();
+        for i in 0..STACK_SIZE {
+            let address = r.u16();
+            if i < stack_len {
+                let _ = self.cpu.stack.push(address);
+            }
+        }
+        self.cpu.delay_timer = r.u8();
+        self.cpu.sound_timer = r.u8();
+
+        self.keypad.set_previous_keystate(r.u16());
+
+        self.display.set_high_res(r.u8() != 0);
+        for y in 0..DISPLAY_HEIGHT {
+            for x in (0..DISPLAY_WIDTH).step_by(8) {
+                let byte = r.u8();
+                for bit in 0..8 {
+                    self.display
+                        .set_pixel(x + bit, y, (byte >> (7 - bit)) & 1 == 1);
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MAGIC, STATE_SIZE, STATE_SIZE_EXCEPT_RAM};
+    use crate::{EmulationSystem, Error, MachineState, PROGRAM_ADDRESS, Quirks};
+
+    /// A machine that's been running for a bit, with something in each part of its state
+    fn machine_state() -> MachineState {
+        let mut machine_state = MachineState::new(EmulationSystem::Chip8);
+        machine_state.set_quirks(Quirks {
+            jump_uses_vx: true,
+            ..Quirks::for_system(EmulationSystem::Chip8)
+        });
+        machine_state.load_default_font();
+        // Call 206, where V3 = 5, I = the font's 5, and it's drawn
+        machine_state.load_program(&[0x22, 0x06, 0, 0, 0, 0, 0x63, 0x05, 0xF3, 0x29, 0xD0, 0x05]);
+        for _ in 0..4 {
+            machine_state.tick(|| 0, || 0).unwrap();
+        }
+        machine_state.set_delay_timer(7);
+        machine_state
+    }
+
+    fn save(machine_state: &MachineState) -> [u8; STATE_SIZE] {
+        let mut buffer = [0; STATE_SIZE];
+        machine_state.save_state(&mut buffer);
+        buffer
+    }
+
+    #[test]
+    fn round_trip() {
+        let saved = save(&machine_state());
+        let mut loaded = MachineState::new(EmulationSystem::Chip8);
+        loaded.load_state(&saved).unwrap();
+
+        assert_eq!(loaded.program_counter(), 0x20C);
+        assert_eq!(loaded.stack(), [0x202]);
+        assert_eq!(loaded.registers()[0x3], 5);
+        assert_eq!(loaded.delay_timer(), 7);
+        assert!(loaded.quirks().jump_uses_vx);
+        assert!(loaded.display().pixel(0, 0));
+        assert_eq!(save(&loaded), saved);
+    }
+
+    #[test]
+    fn round_trip_except_ram() {
+        let machine_state = machine_state();
+        let mut saved = [0; STATE_SIZE_EXCEPT_RAM];
+        let mut bytes = saved.iter_mut();
+        machine_state.save_state_except_ram(|byte| *bytes.next().unwrap() = byte);
+        assert_eq!(saved, save(&machine_state)[..STATE_SIZE_EXCEPT_RAM]);
+
+        let mut loaded = MachineState::new(EmulationSystem::Chip8);
+        loaded.poke(PROGRAM_ADDRESS, 0xAB);
+        let mut bytes = saved.iter().copied();
+        loaded
+            .load_state_except_ram(|| bytes.next().unwrap())
+            .unwrap();
+        assert_eq!(loaded.program_counter(), 0x20C);
+        // RAM is left alone
+        assert_eq!(loaded.peek(PROGRAM_ADDRESS), 0xAB);
+    }
+
+    /// Loading `buffer` fails, leaving the machine untouched
+    fn assert_invalid(buffer: &[u8]) {
+        let mut machine_state = machine_state();
+        let before = save(&machine_state);
+        assert!(matches!(
+            machine_state.load_state(buffer),
+            Err(Error::InvalidState)
+        ));
+        assert_eq!(save(&machine_state), before);
+    }
+
+    #[test]
+    fn rejects_wrong_length() {
+        let saved = save(&MachineState::new(EmulationSystem::Chip8));
+        assert_invalid(&saved[..STATE_SIZE - 1]);
+        let mut longer = [0; STATE_SIZE + 1];
+        longer[..STATE_SIZE].copy_from_slice(&saved);
+        assert_invalid(&longer);
+    }
+
+    #[test]
+    fn rejects_invalid_header() {
+        let saved = save(&MachineState::new(EmulationSystem::Chip8));
+
+        let mut bad_magic = saved;
+        bad_magic[0] = b'X';
+        assert_invalid(&bad_magic);
+
+        let mut bad_version = saved;
+        bad_version[MAGIC.len()] += 1;
+        assert_invalid(&bad_version);
+
+        let mut bad_system = saved;
+        bad_system[MAGIC.len() + 1] = 2;
+        assert_invalid(&bad_system);
+    }
+
+    #[test]
+    fn rejects_stack_too_deep() {
+        let mut saved = save(&MachineState::new(EmulationSystem::Chip8));
+        // After the magic, version, system, quirks, PC, I, and registers
+        saved[MAGIC.len() + 3 + 4 + 16] = u8::MAX;
+        assert_invalid(&saved);
+    }
+}
@@ -0,0 +1,129 @@
+use crate::config::AudioConfig;
+use sdl3::{
+    AudioSubsystem,
+    audio::{AudioCallback, AudioFormat, AudioSpec, AudioStream, AudioStreamWithCallback},
+};
+use serde::Deserialize;
+use std::{
+    f32::consts::TAU,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+};
+
+const SAMPLE_RATE: i32 = 44100;
+
+/// Time taken to fade the tone in and out, which avoids clicks
+const RAMP_DURATION: f32 = 0.005;
+
+/// Shape of the beeper's tone, triangle and sine waves are softer than the square wave
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Waveform {
+    #[default]
+    Square,
+    Triangle,
+    Sine,
+}
+
+impl Waveform {
+    /// The wave's value between -1 and 1, `phase` of the way through a cycle
+    pub fn sample(self, phase: f32) -> f32 {
+        match self {
+            Self::Square if phase < 0.5 => 1.,
+            Self::Square => -1.,
+            Self::Triangle => 1. - 4. * (phase - 0.5).abs(),
+            Self::Sine => (phase * TAU).sin(),
+        }
+    }
+}
+
+pub struct Tone {
+    playing: Arc<AtomicBool>,
+    waveform: Waveform,
+    frequency: f32,
+    volume: f32,
+    phase: f32,
+    amplitude: f32,
+}
+
+impl AudioCallback<f32> for Tone {
+    fn callback(&mut self, stream: &mut AudioStream, requested: i32) {
+        let target = if self.playing.load(Ordering::Relaxed) {
+            self.volume
+        } else {
+            0.
+        };
+        let ramp_step = self.volume / (RAMP_DURATION * SAMPLE_RATE as f32);
+
+        let samples = (0..requested)
+            .map(|_| {
+                self.amplitude = if self.amplitude < target {
+                    (self.amplitude + ramp_step).min(target)
+                } else {
+                    (self.amplitude - ramp_step).max(target)
+                };
+
+                let sample = self.amplitude * self.waveform.sample(self.phase);
+                self.phase = (self.phase + self.frequency / SAMPLE_RATE as f32) % 1.;
+
+                sample
+            })
+            .collect::<Vec<_>>();
+
+        let _ = stream.put_data_f32(&samples);
+    }
+}
+
+/// Plays a tone while switched on
+pub struct Beeper {
+    switch: BeeperSwitch,
+    _stream: AudioStreamWithCallback<Tone>,
+}
+
+/// Turns the tone on and off, this can be shared across threads
+#[derive(Debug, Clone, Default)]
+pub struct BeeperSwitch(Arc<AtomicBool>);
+
+impl BeeperSwitch {
+    pub fn set(&self, playing: bool) {
+        self.0.store(playing, Ordering::Relaxed);
+    }
+}
+
+impl Beeper {
+    pub fn new(
+        audio_subsystem: &AudioSubsystem,
+        config: &AudioConfig,
+    ) -> Result<Self, sdl3::Error> {
+        let switch = BeeperSwitch::default();
+
+        let spec = AudioSpec {
+            freq: Some(SAMPLE_RATE),
+            channels: Some(1),
+            format: Some(AudioFormat::f32_sys()),
+        };
+        let stream = audio_subsystem.open_playback_stream(
+            &spec,
+            Tone {
+                playing: switch.0.clone(),
+                waveform: config.waveform,
+                frequency: config.frequency,
+                volume: config.volume,
+                phase: 0.,
+                amplitude: 0.,
+            },
+        )?;
+        stream.resume()?;
+
+        Ok(Self {
+            switch,
+            _stream: stream,
+        })
+    }
+
+    pub fn switch(&self) -> BeeperSwitch {
+        self.switch.clone()
+    }
+}
@@ -0,0 +1,28 @@
+use rs_chip8_core::{EmulationSystem, disasm};
+use std::{ffi::OsStr, path::PathBuf, process::ExitCode};
+
+fn main() -> ExitCode {
+    let Some(rom_filepath) = std::env::args().nth(1).map(PathBuf::from) else {
+        eprintln!("Usage: rs_chip8_dump <ROM>");
+        return ExitCode::FAILURE;
+    };
+
+    let program = match std::fs::read(&rom_filepath) {
+        Ok(program) => program,
+        Err(err) => {
+            eprintln!("Error: {err}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let system = match rom_filepath.extension().and_then(OsStr::to_str) {
+        Some("sc8") => EmulationSystem::SuperChip,
+        _ => EmulationSystem::Chip8,
+    };
+
+    let mut listing = String::new();
+    disasm::write_listing(&program, system, &mut listing).expect("Writing to a String can't fail");
+    print!("{listing}");
+
+    ExitCode::SUCCESS
+}
@@ -0,0 +1,212 @@
+use rs_chip8_core::MachineState;
+use std::{fmt, ops::Range};
+
+/// A value a breakpoint condition can test
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Operand {
+    Register(usize),
+    IndexRegister,
+    DelayTimer,
+    SoundTimer,
+}
+
+impl Operand {
+    fn value(self, machine_state: &MachineState) -> u16 {
+        match self {
+            Operand::Register(x) => machine_state.registers()[x] as u16,
+            Operand::IndexRegister => machine_state.index_register(),
+            Operand::DelayTimer => machine_state.delay_timer() as u16,
+            Operand::SoundTimer => machine_state.sound_timer() as u16,
+        }
+    }
+}
+
+impl fmt::Display for Operand {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Operand::Register(x) => write!(f, "V{x:X}"),
+            Operand::IndexRegister => write!(f, "I"),
+            Operand::DelayTimer => write!(f, "DT"),
+            Operand::SoundTimer => write!(f, "ST"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Test {
+    Equal(u16),
+    NotEqual(u16),
+    Less(u16),
+    LessOrEqual(u16),
+    Greater(u16),
+    GreaterOrEqual(u16),
+    In(Range<u16>),
+}
+
+/// A condition such as `V3 == 0x1F` or `I in 0x300..0x340`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Condition {
+    operand: Operand,
+    test: Test,
+}
+
+impl Condition {
+    pub fn holds(&self, machine_state: &MachineState) -> bool {
+        let value = self.operand.value(machine_state);
+        match &self.test {
+            Test::Equal(other) => value == *other,
+            Test::NotEqual(other) => value != *other,
+            Test::Less(other) => value < *other,
+            Test::LessOrEqual(other) => value <= *other,
+            Test::Greater(other) => value > *other,
+            Test::GreaterOrEqual(other) => value >= *other,
+            Test::In(range) => range.contains(&value),
+        }
+    }
+
+    /// Parse a condition of the form `<operand> <comparison> <value>`
+    ///
+    /// The operand is one of `V0` to `VF`, `I`, `DT`, or `ST`.
+    /// The comparison is one of `==`, `!=`, `<`, `<=`, `>`, `>=`, or `in` followed by a range `start..end`.
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let tokens = s.split_whitespace().collect::<Vec<_>>();
+        let [operand, comparison, value] = tokens[..] else {
+            return Err(format!(
+                "{s:?} is not in the form <operand> <comparison> <value>"
+            ));
+        };
+
+        let operand = match operand.to_ascii_uppercase().as_str() {
+            "I" => Operand::IndexRegister,
+            "DT" => Operand::DelayTimer,
+            "ST" => Operand::SoundTimer,
+            register => register
+                .strip_prefix('V')
+                .filter(|x| x.len() == 1)
+                .and_then(|x| usize::from_str_radix(x, 16).ok())
+                .map(Operand::Register)
+                .ok_or_else(|| format!("{operand:?} is not a register"))?,
+        };
+
+        let test = match comparison {
+            "==" => Test::Equal(parse_number(value)?),
+            "!=" => Test::NotEqual(parse_number(value)?),
+            "<" => Test::Less(parse_number(value)?),
+            "<=" => Test::LessOrEqual(parse_number(value)?),
+            ">" => Test::Greater(parse_number(value)?),
+            ">=" => Test::GreaterOrEqual(parse_number(value)?),
+            "in" | "IN" => {
+                let (start, end) = value
+                    .split_once("..")
+                    .ok_or_else(|| format!("{value:?} is not a range in the form start..end"))?;
+                Test::In(parse_number(start)?..parse_number(end)?)
+            }
+            _ => return Err(format!("{comparison:?} is not a comparison")),
+        };
+
+        Ok(Self { operand, test })
+    }
+}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let operand = self.operand;
+        match &self.test {
+            Test::Equal(value) => write!(f, "{operand} == 0x{value:X}"),
+            Test::NotEqual(value) => write!(f, "{operand} != 0x{value:X}"),
+            Test::Less(value) => write!(f, "{operand} < 0x{value:X}"),
+            Test::LessOrEqual(value) => write!(f, "{operand} <= 0x{value:X}"),
+            Test::Greater(value) => write!(f, "{operand} > 0x{value:X}"),
+            Test::GreaterOrEqual(value) => write!(f, "{operand} >= 0x{value:X}"),
+            Test::In(range) => write!(f, "{operand} IN 0x{:X}..0x{:X}", range.start, range.end),
+        }
+    }
+}
+
+/// A number in decimal, or in hex if prefixed with `0x`
+fn parse_number(s: &str) -> Result<u16, String> {
+    match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        Some(hex) => u16::from_str_radix(hex, 16),
+        None => s.parse(),
+    }
+    .map_err(|_| format!("{s:?} is not a number"))
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Breakpoint {
+    pub address: u16,
+    pub condition: Option<Condition>,
+    /// How many times execution stopped here
+    pub hits: u32,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Breakpoints {
+    breakpoints: Vec<Breakpoint>,
+    /// Address of the breakpoint that was just hit, so resuming doesn't stop there again straight away
+    resume_from: Option<u16>,
+}
+
+impl Breakpoints {
+    pub fn iter(&self) -> impl Iterator<Item = &Breakpoint> {
+        self.breakpoints.iter()
+    }
+
+    pub fn contains(&self, address: u16) -> bool {
+        self.breakpoints
+            .iter()
+            .any(|breakpoint| breakpoint.address == address)
+    }
+
+    /// Parse `<address> [if <condition>]`, the address being in hex,
+    /// and add the breakpoint, or remove the breakpoints at the address if there's no condition
+    pub fn toggle(&mut self, s: &str) -> Result<(), String> {
+        let (address, condition) = match s.split_once(" if ").or_else(|| s.split_once(" IF ")) {
+            Some((address, condition)) => (address, Some(Condition::parse(condition)?)),
+            None => (s, None),
+        };
+        let address = address.trim();
+        let address = address
+            .strip_prefix("0x")
+            .or_else(|| address.strip_prefix("0X"))
+            .unwrap_or(address);
+        let address = u16::from_str_radix(address, 16)
+            .map_err(|_| format!("{address:?} is not an address"))?;
+
+        if condition.is_none() && self.contains(address) {
+            self.breakpoints
+                .retain(|breakpoint| breakpoint.address != address);
+        } else {
+            self.breakpoints.push(Breakpoint {
+                address,
+                condition,
+                hits: 0,
+            });
+        }
+        Ok(())
+    }
+
+    /// Whether execution should stop before the next instruction, counting the hit if so
+    pub fn check(&mut self, machine_state: &MachineState) -> bool {
+        let pc = machine_state.program_counter();
+        if self.resume_from.take() == Some(pc) {
+            return false;
+        }
+
+        let hit = self.breakpoints.iter_mut().find(|breakpoint| {
+            breakpoint.address == pc
+                && breakpoint
+                    .condition
+                    .as_ref()
+                    .is_none_or(|condition| condition.holds(machine_state))
+        });
+        match hit {
+            Some(breakpoint) => {
+                breakpoint.hits += 1;
+                self.resume_from = Some(pc);
+                true
+            }
+            None => false,
+        }
+    }
+}
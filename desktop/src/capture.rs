@@ -0,0 +1,207 @@
+use crate::{Error, config, palette::Colours};
+use rs_chip8_core::{DISPLAY_HEIGHT, DISPLAY_WIDTH, Display};
+use std::{
+    borrow::Cow,
+    fs::File,
+    io::BufWriter,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Saves captures of the display into a folder for the ROM, named after it
+pub struct Capture {
+    directory: PathBuf,
+    rom_name: String,
+}
+
+impl Capture {
+    /// Use a subfolder of `directory`, or of a folder in the pictures directory if not specified
+    ///
+    /// Without a pictures directory, the data directory is used instead.
+    pub fn new(directory: Option<&Path>, rom: &Path) -> Self {
+        let directory = directory.map(Path::to_path_buf).unwrap_or_else(|| {
+            directories::UserDirs::new()
+                .and_then(|dirs| dirs.picture_dir().map(|dir| dir.join("rs_chip8")))
+                .or_else(|| config::project_dirs().map(|dirs| dirs.data_dir().join("captures")))
+                .unwrap_or_default()
+        });
+        let rom_name = rom
+            .file_stem()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .into_owned();
+
+        Self {
+            directory: directory.join(&rom_name),
+            rom_name,
+        }
+    }
+
+    /// A new file path with the ROM name and current time, creating the directory if needed
+    pub fn path(&self, extension: &str) -> Result<PathBuf, Error> {
+        std::fs::create_dir_all(&self.directory)?;
+        Ok(self
+            .directory
+            .join(format!("{}-{}.{extension}", self.rom_name, timestamp())))
+    }
+
+    /// Save the display as a PNG at its native resolution, returning the file's path
+    pub fn screenshot(&self, display: &Display, colours: Colours) -> Result<PathBuf, Error> {
+        let path = self.path("png")?;
+
+        let mut encoder = png::Encoder::new(
+            BufWriter::new(File::create(&path)?),
+            DISPLAY_WIDTH as u32,
+            DISPLAY_HEIGHT as u32,
+        );
+        encoder.set_color(png::ColorType::Indexed);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.set_palette(vec![
+            colours.off.r,
+            colours.off.g,
+            colours.off.b,
+            colours.on.r,
+            colours.on.g,
+            colours.on.b,
+        ]);
+
+        let mut data = Vec::with_capacity(DISPLAY_WIDTH * DISPLAY_HEIGHT);
+        for y in 0..DISPLAY_HEIGHT {
+            for x in 0..DISPLAY_WIDTH {
+                data.push(display.pixel(x, y) as u8);
+            }
+        }
+
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&data)?;
+        writer.finish()?;
+
+        Ok(path)
+    }
+}
+
+/// Records the display to an animated GIF, one call to [`GifRecorder::frame`] per 60 Hz frame
+///
+/// The GIF only has the two palette colours, and repeated frames are merged.
+pub struct GifRecorder {
+    encoder: gif::Encoder<BufWriter<File>>,
+    path: PathBuf,
+    /// The frame waiting to be written, once it's known how long it's shown for
+    pending: Option<(Vec<u8>, u32)>,
+    frame_count: u32,
+}
+
+impl Capture {
+    pub fn start_gif(&self, colours: Colours) -> Result<GifRecorder, Error> {
+        let path = self.path("gif")?;
+
+        let mut encoder = gif::Encoder::new(
+            BufWriter::new(File::create(&path)?),
+            DISPLAY_WIDTH as u16,
+            DISPLAY_HEIGHT as u16,
+            &[
+                colours.off.r,
+                colours.off.g,
+                colours.off.b,
+                colours.on.r,
+                colours.on.g,
+                colours.on.b,
+            ],
+        )?;
+        encoder.set_repeat(gif::Repeat::Infinite)?;
+
+        Ok(GifRecorder {
+            encoder,
+            path,
+            pending: None,
+            frame_count: 0,
+        })
+    }
+}
+
+impl GifRecorder {
+    pub fn frame(&mut self, display: &Display) -> Result<(), Error> {
+        let mut data = Vec::with_capacity(DISPLAY_WIDTH * DISPLAY_HEIGHT);
+        for y in 0..DISPLAY_HEIGHT {
+            for x in 0..DISPLAY_WIDTH {
+                data.push(display.pixel(x, y) as u8);
+            }
+        }
+
+        match &self.pending {
+            Some((pending, _)) if *pending == data => (),
+            Some((_, start)) => {
+                // GIF delays are in hundredths of a second, and most viewers
+                // slow down anything shorter than 2, so drop frames that short
+                let start = *start;
+                if centiseconds(self.frame_count) - centiseconds(start) >= 2 {
+                    self.write_pending()?;
+                    self.pending = Some((data, self.frame_count));
+                } else {
+                    self.pending = Some((data, start));
+                }
+            }
+            None => self.pending = Some((data, self.frame_count)),
+        }
+
+        self.frame_count += 1;
+
+        Ok(())
+    }
+
+    fn write_pending(&mut self) -> Result<(), Error> {
+        if let Some((data, start)) = &self.pending {
+            let delay = (centiseconds(self.frame_count) - centiseconds(*start)).max(2);
+            self.encoder.write_frame(&gif::Frame {
+                width: DISPLAY_WIDTH as u16,
+                height: DISPLAY_HEIGHT as u16,
+                delay: delay.min(u16::MAX as u32) as u16,
+                buffer: Cow::Borrowed(data),
+                ..gif::Frame::default()
+            })?;
+        }
+
+        Ok(())
+    }
+
+    /// Write the last frame and finish the file, returning its path
+    pub fn finish(mut self) -> Result<PathBuf, Error> {
+        self.write_pending()?;
+        self.encoder.into_inner()?;
+        Ok(self.path)
+    }
+}
+
+/// Time from the start of the recording to the start of a frame, in hundredths of a second
+fn centiseconds(frame: u32) -> u32 {
+    (frame as u64 * 100 / 60) as u32
+}
+
+/// The current UTC time as `YYYY-MM-DD_HH-MM-SS`, which sorts chronologically
+pub fn timestamp() -> String {
+    let seconds = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default();
+    let (days, time) = (seconds / 86400, seconds % 86400);
+
+    // Convert days since the epoch to a date in the proleptic Gregorian calendar
+    // https://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let z = days as i64 + 719468;
+    let era = z.div_euclid(146097);
+    let day_of_era = z.rem_euclid(146097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + (month <= 2) as i64;
+
+    format!(
+        "{year:04}-{month:02}-{day:02}_{:02}-{:02}-{:02}",
+        time / 3600,
+        time / 60 % 60,
+        time % 60
+    )
+}
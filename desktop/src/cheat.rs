@@ -0,0 +1,36 @@
+use rs_chip8_core::MachineState;
+use serde::{Deserialize, Serialize};
+
+/// A byte of RAM pinned to a value, such as a game's number of lives
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Cheat {
+    pub address: u16,
+    pub value: u8,
+    #[serde(default = "enabled")]
+    pub enabled: bool,
+}
+
+fn enabled() -> bool {
+    true
+}
+
+/// Write the values of the enabled cheats, to be done after every frame
+pub fn apply(cheats: &[Cheat], machine_state: &mut MachineState) {
+    for cheat in cheats.iter().filter(|cheat| cheat.enabled) {
+        machine_state.poke(cheat.address, cheat.value);
+    }
+}
+
+/// Freeze `address` at its current value, or unfreeze it if it already is
+pub fn toggle_freeze(cheats: &mut Vec<Cheat>, machine_state: &MachineState, address: u16) {
+    if cheats.iter().any(|cheat| cheat.address == address) {
+        cheats.retain(|cheat| cheat.address != address);
+    } else {
+        cheats.push(Cheat {
+            address,
+            value: machine_state.peek(address),
+            enabled: true,
+        });
+    }
+}
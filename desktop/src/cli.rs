@@ -0,0 +1,258 @@
+use crate::{
+    compare::Settings,
+    filter::Filter,
+    keymap::KeymapPreset,
+    palette::{HexColour, Palette},
+    rotation::Rotation,
+};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use rs_chip8_core::{EmulationSystem, sprite};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+#[derive(Debug, Parser)]
+#[command(
+    version,
+    about = "A CHIP-8 and SuperChip interpreter",
+    args_conflicts_with_subcommands = true
+)]
+pub struct Arguments {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    /// Without a command, the ROM is run
+    #[command(flatten)]
+    pub run: Cli,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Run a ROM, the same as giving no command
+    Run(Box<Cli>),
+    /// Print a listing of a ROM's instructions
+    Disasm {
+        rom: PathBuf,
+        /// The system to disassemble for, detected from the ROM's file extension by default
+        #[arg(short, long, value_enum)]
+        system: Option<System>,
+    },
+    /// Print a ROM's hashes and what the ROM database knows about it
+    Info { rom: PathBuf },
+    /// Find instructions that behave differently depending on the quirks emulated
+    Check {
+        rom: PathBuf,
+        /// The system to check for, detected from the ROM's file extension by default
+        #[arg(short, long, value_enum)]
+        system: Option<System>,
+    },
+    /// Convert a monochrome PNG or PBM image to sprites, printed as Octo assembler directives
+    ///
+    /// Dark pixels are set, like black ones in a PBM, and transparent ones in a PNG aren't.
+    Sprites {
+        image: PathBuf,
+        /// The kind of sprites to cut the image into
+        #[arg(short, long, value_enum, default_value_t)]
+        format: SpriteFormat,
+        /// The start of each sprite's label, the image's file name by default
+        #[arg(short, long)]
+        label: Option<String>,
+    },
+    /// Print ROM database entries for the ROMs in the CHIP-8 community's database,
+    /// from the `programs.json` in https://github.com/chip-8/chip-8-database
+    ImportDatabase { programs: PathBuf },
+}
+
+/// Options for running a ROM
+#[derive(Debug, Args)]
+pub struct Cli {
+    /// The ROM file to run, chosen from recently opened ROMs and the library if not given,
+    /// or with a file chooser if there are none
+    pub rom: Option<PathBuf>,
+
+    /// The system to emulate, detected from the ROM's file extension by default
+    #[arg(short, long, value_enum)]
+    pub system: Option<System>,
+
+    /// Number of instructions executed per frame, at 60 frames per second
+    #[arg(long, visible_alias = "speed")]
+    pub ipf: Option<u32>,
+
+    /// Initial window size, as a multiple of the 128x64 display
+    #[arg(long)]
+    pub scale: Option<u32>,
+
+    /// Colours used to draw the display
+    #[arg(long, value_enum)]
+    pub palette: Option<Palette>,
+
+    /// Custom colour of pixels that are off, as #rrggbb, overriding the palette
+    #[arg(long)]
+    pub off_colour: Option<HexColour>,
+
+    /// Custom colour of pixels that are on, as #rrggbb, overriding the palette
+    #[arg(long)]
+    pub on_colour: Option<HexColour>,
+
+    /// Keys used for the keypad, by position so `qwerty` suits QWERTZ and AZERTY keyboards too,
+    /// individual keys can be remapped in the config file
+    #[arg(long, value_enum)]
+    pub keymap: Option<KeymapPreset>,
+
+    /// Fade pixels out over a few frames, reducing flicker
+    #[arg(long)]
+    pub phosphor: bool,
+
+    /// Post-processing drawn over the display
+    #[arg(long, value_enum)]
+    pub filter: Option<Filter>,
+
+    /// Clockwise rotation of the display in degrees, the keypad is rotated to match
+    #[arg(long, value_enum)]
+    pub rotation: Option<Rotation>,
+
+    /// Show the keypad with the held keys highlighted, its keys can be clicked to press them
+    #[arg(long)]
+    pub keypad: bool,
+
+    /// Draw lines between the display's pixels, for designing sprites
+    #[arg(long)]
+    pub grid: bool,
+
+    /// Outline the display while the beeper is playing, so sound can be seen
+    #[arg(long)]
+    pub sound_indicator: bool,
+
+    /// Only scale the display by whole multiples, keeping every pixel the same size
+    #[arg(long)]
+    pub integer_scale: bool,
+
+    /// Time frames by the display's refresh instead of sleeping, which is smoother
+    #[arg(long)]
+    pub vsync: bool,
+
+    /// Start in fullscreen
+    #[arg(long)]
+    pub fullscreen: bool,
+
+    /// Record a video with ffmpeg until the emulator is closed, e.g. to an .mp4 or .webm file
+    #[arg(long, value_name = "OUTPUT")]
+    pub record: Option<PathBuf>,
+
+    /// ffmpeg encoder settings for the recording, replacing the defaults
+    #[arg(
+        long,
+        value_name = "ARGS",
+        requires = "record",
+        allow_hyphen_values = true
+    )]
+    pub record_args: Option<String>,
+
+    /// Cycle through the ROMs in a directory or playlist file, moving on when nobody is playing,
+    /// and playing any demo saved next to a ROM as `<rom>.movie.json`
+    #[arg(long, conflicts_with_all = ["rom", "record_movie", "play_movie"])]
+    pub playlist: Option<PathBuf>,
+
+    /// Record the keys pressed and the random seed to a movie file, to replay the run exactly
+    #[arg(long, value_name = "MOVIE", conflicts_with = "play_movie")]
+    pub record_movie: Option<PathBuf>,
+
+    /// Replay a movie file, the player takes over when it ends
+    #[arg(long, value_name = "MOVIE")]
+    pub play_movie: Option<PathBuf>,
+
+    /// Read debugger commands from stdin, such as `regs`, `step`, and `break 2A4`
+    #[arg(long)]
+    pub monitor: bool,
+
+    /// Pause before the first instruction, with the debug overlay shown
+    #[arg(long)]
+    pub start_paused: bool,
+
+    /// Restart whenever the ROM file changes, for testing a ROM as it's developed
+    #[arg(long)]
+    pub watch: bool,
+
+    /// Run a second copy of the ROM beside the game with other quirks or speed, sharing its keys,
+    /// written like `shift_uses_vy=false,ipf=20`
+    #[arg(long, value_name = "SETTINGS")]
+    pub compare: Option<Settings>,
+
+    /// Write every instruction executed to a file, with the registers each one changed,
+    /// keeping the last few files once it grows large
+    #[arg(long, value_name = "FILE")]
+    pub trace: Option<PathBuf>,
+
+    /// Accept JSON remote control commands, one per line, on this TCP port of localhost
+    #[arg(long, value_name = "PORT")]
+    pub remote: Option<u16>,
+
+    /// Run without a window or audio, printing the hash of the display after the last frame
+    #[arg(long, requires = "rom")]
+    pub headless: bool,
+
+    /// Number of frames to run in headless mode
+    #[arg(long, requires = "headless", default_value_t = 600)]
+    pub frames: u32,
+
+    /// JSON file of the keys to hold in headless mode, or a movie,
+    /// e.g. `[{"frame": 60, "keys": ["5"]}, {"frame": 90, "keys": []}]`
+    #[arg(long, value_name = "SCRIPT", requires = "headless")]
+    pub input: Option<PathBuf>,
+
+    /// Save the display after the last frame in headless mode to a .pbm file,
+    /// or with any other extension, the hash of the display after every frame
+    #[arg(long, value_name = "OUTPUT", requires = "headless")]
+    pub out: Option<PathBuf>,
+
+    /// Run as fast as possible without a window for this many seconds, then report the speed
+    #[arg(
+        long,
+        value_name = "SECONDS",
+        num_args = 0..=1,
+        default_missing_value = "10",
+        requires = "rom",
+        conflicts_with = "headless"
+    )]
+    pub bench: Option<u64>,
+
+    /// Config file to use instead of the one in the default location
+    #[arg(short, long)]
+    pub config: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum System {
+    Chip8,
+    #[value(alias = "schip")]
+    #[serde(alias = "schip")]
+    SuperChip,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum SpriteFormat {
+    /// 8 pixel wide strips of up to 15 rows, drawn with DXYN
+    #[default]
+    Strip,
+    /// 16x16 sprites drawn with the SuperChip's DXY0
+    #[value(name = "16x16")]
+    SuperChip16,
+}
+
+impl From<SpriteFormat> for sprite::SpriteFormat {
+    fn from(format: SpriteFormat) -> Self {
+        match format {
+            SpriteFormat::Strip => sprite::SpriteFormat::Strip,
+            SpriteFormat::SuperChip16 => sprite::SpriteFormat::SuperChip16,
+        }
+    }
+}
+
+impl From<System> for EmulationSystem {
+    fn from(system: System) -> Self {
+        match system {
+            System::Chip8 => EmulationSystem::Chip8,
+            System::SuperChip => EmulationSystem::SuperChip,
+        }
+    }
+}
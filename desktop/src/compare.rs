@@ -0,0 +1,180 @@
+use crate::{Error, config::QuirksConfig, palette::Colours};
+use rand::{Rng, rngs::StdRng};
+use rs_chip8_core::{DISPLAY_HEIGHT, DISPLAY_WIDTH, MachineState};
+use sdl3::{
+    VideoSubsystem,
+    rect::FRect,
+    render::Canvas,
+    sys::render::SDL_RendererLogicalPresentation,
+    video::{Window, WindowBuildError},
+};
+use std::{fmt, str::FromStr};
+
+/// How the second machine in a comparison differs from the first,
+/// written like `shift_uses_vy=false,ipf=20`, where a quirk on its own is turned on
+#[derive(Debug, Clone)]
+pub struct Settings {
+    quirks: QuirksConfig,
+    ipf: Option<u32>,
+    description: String,
+}
+
+impl FromStr for Settings {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut settings = Self {
+            quirks: QuirksConfig::default(),
+            ipf: None,
+            description: s.to_owned(),
+        };
+        for setting in s.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+            let (name, value) = setting.split_once('=').unwrap_or((setting, "true"));
+            if name == "ipf" {
+                let ipf = value
+                    .parse()
+                    .map_err(|_| format!("{value:?} is not a number of instructions"))?;
+                settings.ipf = Some(ipf);
+                continue;
+            }
+
+            let quirk = match name {
+                "logic_resets_flag" => &mut settings.quirks.logic_resets_flag,
+                "shift_uses_vy" => &mut settings.quirks.shift_uses_vy,
+                "jump_uses_vx" => &mut settings.quirks.jump_uses_vx,
+                "memory_increments_index" => &mut settings.quirks.memory_increments_index,
+                _ => return Err(format!("{name:?} is not a quirk or ipf")),
+            };
+            *quirk = Some(
+                value
+                    .parse()
+                    .map_err(|_| format!("{value:?} is not true or false"))?,
+            );
+        }
+        Ok(settings)
+    }
+}
+
+impl fmt::Display for Settings {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.description)
+    }
+}
+
+/// A second machine running the same ROM with different settings, in a window beside the game's
+///
+/// It's given the same keys as the game, and each frame starts with the same random numbers,
+/// so any difference between them comes from the settings.
+pub struct Comparison {
+    machine_state: MachineState,
+    quirks: QuirksConfig,
+    /// The game's IPF is followed if not set
+    ipf: Option<u32>,
+    /// Set once the machine fails, after which it's left as it was
+    stopped: bool,
+    canvas: Canvas<Window>,
+}
+
+impl Comparison {
+    /// Copy the game's machine, changed by `settings`
+    pub fn open(
+        video_subsystem: &VideoSubsystem,
+        game_window: &Window,
+        title: &str,
+        settings: &Settings,
+        machine_state: &MachineState,
+    ) -> Result<Self, Error> {
+        let (width, height) = game_window.size();
+        let (x, y) = game_window.position();
+        let window = match video_subsystem
+            .window(&format!("{title} ({settings}) - rs_chip8"), width, height)
+            .position(x + width as i32, y)
+            .resizable()
+            .high_pixel_density()
+            .build()
+        {
+            Ok(window) => window,
+            Err(WindowBuildError::SdlError(err)) => return Err(err.into()),
+            Err(err) => panic!("Expected window dimensions and title to be valid, but {err}"),
+        };
+
+        let mut canvas = window.into_canvas();
+        if let Err(err) = canvas.set_logical_size(
+            DISPLAY_WIDTH as u32,
+            DISPLAY_HEIGHT as u32,
+            SDL_RendererLogicalPresentation::LETTERBOX,
+        ) {
+            if let sdl3::IntegerOrSdlError::SdlError(err) = err {
+                return Err(err.into());
+            } else {
+                panic!("Expected display height and width to be valid");
+            }
+        }
+
+        let mut comparison = Self {
+            machine_state: machine_state.clone(),
+            quirks: settings.quirks,
+            ipf: settings.ipf,
+            stopped: false,
+            canvas,
+        };
+        comparison.reset(machine_state);
+        Ok(comparison)
+    }
+
+    pub fn id(&self) -> u32 {
+        self.canvas.window().id()
+    }
+
+    /// Run a frame given the keys and random number generator the game's frame started with
+    pub fn run_frame(&mut self, ipf: u32, held_keys: u16, mut rng: StdRng) {
+        if !self.stopped {
+            let result = self.machine_state.run_frame(
+                self.ipf.unwrap_or(ipf),
+                || held_keys,
+                || rng.random(),
+            );
+            self.stop_on_error(result);
+        }
+    }
+
+    pub fn step(&mut self, held_keys: u16, mut rng: StdRng) {
+        if !self.stopped {
+            let result = self.machine_state.tick(|| held_keys, || rng.random());
+            self.stop_on_error(result);
+        }
+    }
+
+    fn stop_on_error(&mut self, result: Result<(), rs_chip8_core::Error>) {
+        if let Err(err) = result {
+            eprintln!("The comparison stopped: {err}");
+            self.stopped = true;
+        }
+    }
+
+    /// Start again from the game's machine, with the quirks it now has changed by the settings
+    pub fn reset(&mut self, machine_state: &MachineState) {
+        self.machine_state = machine_state.clone();
+        self.machine_state
+            .set_quirks(self.quirks.apply(machine_state.quirks()));
+        self.stopped = false;
+    }
+
+    pub fn draw(&mut self, colours: Colours) -> Result<(), Error> {
+        let canvas = &mut self.canvas;
+        canvas.set_draw_color(colours.off);
+        canvas.clear();
+
+        let display = self.machine_state.display();
+        let pixels = (0..DISPLAY_HEIGHT)
+            .flat_map(|y| (0..DISPLAY_WIDTH).map(move |x| (x, y)))
+            .filter(|&(x, y)| display.pixel(x, y))
+            .map(|(x, y)| FRect::new(x as f32, y as f32, 1., 1.))
+            .collect::<Vec<_>>();
+        canvas.set_draw_color(colours.on);
+        canvas.fill_rects(&pixels)?;
+
+        canvas.present();
+        Ok(())
+    }
+}
@@ -0,0 +1,310 @@
+use crate::{
+    audio::Waveform,
+    cheat::Cheat,
+    cli::System,
+    filter::Filter,
+    gamepad::GamepadConfig,
+    keymap::KeymapConfig,
+    palette::{HexColour, Palette},
+    rotation::Rotation,
+};
+use rs_chip8_core::Quirks;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// System to emulate when it can't be detected from the ROM's file extension
+    pub system: Option<System>,
+    pub ipf: u32,
+    /// Speed multiplier while fast-forwarding, 0 runs as fast as possible
+    pub fast_forward: u32,
+    pub palette: Palette,
+    pub colours: ColoursConfig,
+    pub keymap: KeymapConfig,
+    pub gamepad: GamepadConfig,
+    /// Named sets of gamepad button overrides, for games that use different keys
+    pub gamepad_profiles: BTreeMap<String, BTreeMap<String, String>>,
+    pub audio: AudioConfig,
+    pub display: DisplayConfig,
+    pub window: WindowConfig,
+    pub capture: CaptureConfig,
+    pub library: LibraryConfig,
+    pub playlist: PlaylistConfig,
+    /// Restart whenever the ROM file changes, for testing a ROM as it's developed
+    pub watch: bool,
+    /// Save the game when closing the emulator, and offer to resume it when the ROM is next run
+    pub resume: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            system: None,
+            ipf: 10,
+            fast_forward: 4,
+            palette: Palette::default(),
+            colours: ColoursConfig::default(),
+            keymap: KeymapConfig::default(),
+            gamepad: GamepadConfig::default(),
+            gamepad_profiles: BTreeMap::new(),
+            audio: AudioConfig::default(),
+            display: DisplayConfig::default(),
+            window: WindowConfig::default(),
+            capture: CaptureConfig::default(),
+            library: LibraryConfig::default(),
+            playlist: PlaylistConfig::default(),
+            watch: false,
+            resume: false,
+        }
+    }
+}
+
+/// Custom colours, overriding the palette
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ColoursConfig {
+    pub off: Option<HexColour>,
+    pub on: Option<HexColour>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AudioConfig {
+    pub enabled: bool,
+    pub volume: f32,
+    pub waveform: Waveform,
+    /// Pitch of the tone in Hz
+    pub frequency: f32,
+    pub mute_fast_forward: bool,
+}
+
+impl Default for AudioConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            volume: 0.2,
+            waveform: Waveform::default(),
+            frequency: 440.,
+            mute_fast_forward: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DisplayConfig {
+    /// Fade pixels out over a few frames, reducing flicker
+    pub phosphor: bool,
+    pub filter: Filter,
+    /// Clockwise rotation of the display in degrees, the keypad is rotated to match
+    pub rotation: Rotation,
+    /// Show the keypad with the held keys highlighted, its keys can be clicked to press them
+    pub keypad: bool,
+    /// Outline the display while the beeper is playing, so sound can be seen
+    pub sound_indicator: bool,
+    /// Draw lines between the display's pixels, for designing sprites
+    pub grid: bool,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WindowConfig {
+    pub scale: u32,
+    pub fullscreen: bool,
+    /// Only scale the display by whole multiples, keeping every pixel the same size
+    pub integer_scale: bool,
+    /// Time frames by the display's refresh instead of sleeping, which is smoother
+    pub vsync: bool,
+    /// Pause, and so mute, while the window isn't focused
+    pub pause_unfocused: bool,
+}
+
+impl Default for WindowConfig {
+    fn default() -> Self {
+        Self {
+            scale: 10,
+            fullscreen: false,
+            integer_scale: false,
+            vsync: false,
+            pause_unfocused: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CaptureConfig {
+    /// Where screenshots and GIFs are saved, a folder in the pictures directory by default
+    ///
+    /// Each ROM's captures go in a subfolder named after it.
+    pub directory: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LibraryConfig {
+    /// Directories searched for ROMs, which are listed when no ROM is given
+    pub directories: Vec<PathBuf>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PlaylistConfig {
+    /// Seconds without any input before moving on to the next ROM
+    pub idle: u64,
+}
+
+impl Default for PlaylistConfig {
+    fn default() -> Self {
+        Self { idle: 60 }
+    }
+}
+
+/// The platform's directories for rs_chip8's config and data
+pub fn project_dirs() -> Option<directories::ProjectDirs> {
+    directories::ProjectDirs::from("", "", "rs_chip8")
+}
+
+/// A folder for data belonging to one ROM, such as save states and crash reports, in the
+/// platform's data directory, or next to the ROM if there's no home directory
+///
+/// Like [`RomConfig::path`] it's named after a hash of the program, so ROMs that share a name
+/// don't share saves.
+pub fn rom_data_dir(rom: &Path, program: &[u8]) -> PathBuf {
+    let hash = hash(program);
+    project_dirs().map_or_else(
+        || rom.with_extension(""),
+        |dirs| dirs.data_dir().join("roms").join(format!("{hash:016x}")),
+    )
+}
+
+/// The default location of the config file, in the platform's config directory
+pub fn default_path() -> Option<PathBuf> {
+    project_dirs().map(|dirs| dirs.config_dir().join("config.toml"))
+}
+
+/// Settings for a single ROM, overriding the config file
+///
+/// These are stored in the config directory, named after a hash of the ROM's contents,
+/// so they follow the ROM if it's renamed or moved.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RomConfig {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub system: Option<System>,
+    pub quirks: QuirksConfig,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ipf: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub palette: Option<Palette>,
+    pub keymap: KeymapConfig,
+    pub gamepad: GamepadConfig,
+    /// Bytes of RAM pinned to a value after every frame
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub cheats: Vec<Cheat>,
+}
+
+/// Quirks to change from the emulated system's defaults
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct QuirksConfig {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub logic_resets_flag: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub shift_uses_vy: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub jump_uses_vx: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub memory_increments_index: Option<bool>,
+}
+
+impl QuirksConfig {
+    /// The quirks that differ from `defaults`
+    pub fn changes(defaults: Quirks, quirks: Quirks) -> Self {
+        let changed = |default: bool, quirk: bool| (quirk != default).then_some(quirk);
+        Self {
+            logic_resets_flag: changed(defaults.logic_resets_flag, quirks.logic_resets_flag),
+            shift_uses_vy: changed(defaults.shift_uses_vy, quirks.shift_uses_vy),
+            jump_uses_vx: changed(defaults.jump_uses_vx, quirks.jump_uses_vx),
+            memory_increments_index: changed(
+                defaults.memory_increments_index,
+                quirks.memory_increments_index,
+            ),
+        }
+    }
+
+    pub fn apply(self, quirks: Quirks) -> Quirks {
+        Quirks {
+            logic_resets_flag: self.logic_resets_flag.unwrap_or(quirks.logic_resets_flag),
+            shift_uses_vy: self.shift_uses_vy.unwrap_or(quirks.shift_uses_vy),
+            jump_uses_vx: self.jump_uses_vx.unwrap_or(quirks.jump_uses_vx),
+            memory_increments_index: self
+                .memory_increments_index
+                .unwrap_or(quirks.memory_increments_index),
+        }
+    }
+}
+
+impl RomConfig {
+    /// Where the settings for the ROM with this program are stored
+    pub fn path(program: &[u8]) -> Option<PathBuf> {
+        let hash = hash(program);
+        project_dirs().map(|dirs| {
+            dirs.config_dir()
+                .join("roms")
+                .join(format!("{hash:016x}.toml"))
+        })
+    }
+
+    /// Load the settings at `path`, a missing file gives the defaults
+    pub fn load(path: &Path) -> Result<Self, crate::Error> {
+        match std::fs::read_to_string(path) {
+            Ok(contents) => Ok(toml::from_str(&contents)?),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), crate::Error> {
+        if let Some(directory) = path.parent() {
+            std::fs::create_dir_all(directory)?;
+        }
+        std::fs::write(path, toml::to_string(self)?)?;
+        Ok(())
+    }
+}
+
+impl Config {
+    /// Load the config file from `path`, or from the default location if not specified
+    ///
+    /// A missing file at the default location is not an error, the defaults are used instead.
+    pub fn load(path: Option<&Path>) -> Result<Self, crate::Error> {
+        let (path, required) = match path {
+            Some(path) => (path.to_owned(), true),
+            None => match default_path() {
+                Some(path) => (path, false),
+                None => return Ok(Self::default()),
+            },
+        };
+
+        match std::fs::read_to_string(&path) {
+            Ok(contents) => Ok(toml::from_str(&contents)?),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound && !required => {
+                Ok(Self::default())
+            }
+            Err(err) => Err(err.into()),
+        }
+    }
+}
+
+/// 64-bit FNV-1a, which unlike std's hashers is stable between releases
+pub fn hash(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
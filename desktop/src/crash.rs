@@ -0,0 +1,72 @@
+use crate::{Error, capture::timestamp};
+use rs_chip8_core::{MachineState, RAM_SIZE, STATE_SIZE, disasm};
+use std::{
+    fmt::Write,
+    path::{Path, PathBuf},
+};
+
+/// Instructions shown either side of the faulting instruction
+const DISASSEMBLY_CONTEXT: usize = 8;
+
+/// Save the machine state and a readable report of an emulation error in the ROM's data folder,
+/// as `crash.state` and `crash.txt`, returning the report's path
+///
+/// The state can be loaded from a save slot by renaming it in the same folder, e.g. to `1.state`.
+pub fn save_report(
+    rom: &Path,
+    data_dir: &Path,
+    machine_state: &MachineState,
+    error: &rs_chip8_core::Error,
+    address: u16,
+) -> Result<PathBuf, Error> {
+    std::fs::create_dir_all(data_dir)?;
+
+    let mut state = [0; STATE_SIZE];
+    machine_state.save_state(&mut state);
+    std::fs::write(data_dir.join("crash.state"), state)?;
+
+    let report_path = data_dir.join("crash.txt");
+    std::fs::write(&report_path, report(rom, machine_state, error, address))?;
+    Ok(report_path)
+}
+
+fn report(
+    rom: &Path,
+    machine_state: &MachineState,
+    error: &rs_chip8_core::Error,
+    address: u16,
+) -> String {
+    // Writing to a string can't fail
+    let mut report = String::new();
+    let _ = writeln!(report, "rs_chip8 {}", env!("CARGO_PKG_VERSION"));
+    let _ = writeln!(report, "Time: {} UTC", timestamp());
+    let _ = writeln!(report, "ROM: {}", rom.display());
+    let _ = writeln!(report, "Error: {error} at 0x{address:03X}");
+    let _ = writeln!(report);
+    let _ = machine_state.dump(&mut report);
+    let _ = writeln!(report);
+
+    // Instructions are assumed to be aligned with the faulting instruction
+    let _ = writeln!(report, "Disassembly:");
+    let memory = machine_state.memory();
+    let first = (address as usize).saturating_sub(2 * DISASSEMBLY_CONTEXT);
+    for instruction_address in (first..=address as usize + 2 * DISASSEMBLY_CONTEXT).step_by(2) {
+        if instruction_address + 1 >= RAM_SIZE {
+            break;
+        }
+        let opcode =
+            u16::from_be_bytes([memory[instruction_address], memory[instruction_address + 1]]);
+        let _ = writeln!(
+            report,
+            "{} {instruction_address:03X}  {opcode:04X}  {}",
+            if instruction_address == address as usize {
+                ">"
+            } else {
+                " "
+            },
+            disasm::disassemble(opcode, machine_state.system())
+        );
+    }
+
+    report
+}
@@ -0,0 +1,255 @@
+use crate::{
+    Error,
+    cli::System,
+    config::{self, ColoursConfig, QuirksConfig},
+    palette::HexColour,
+};
+use rs_chip8_core::{EmulationSystem, Quirks};
+use serde::Deserialize;
+use std::{collections::BTreeMap, fmt::Write, path::PathBuf};
+
+/// Settings for known ROMs, shipped with the emulator
+const BUNDLED: &str = include_str!("../roms.toml");
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct Database {
+    roms: Vec<Entry>,
+}
+
+/// The settings a ROM was written for
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Entry {
+    /// SHA-1 of the ROM, in hex
+    pub sha1: String,
+    pub title: String,
+    pub system: Option<System>,
+    #[serde(default)]
+    pub quirks: QuirksConfig,
+    /// Called `tickrate` in the CHIP-8 community's database, which entries can be copied from
+    #[serde(alias = "tickrate")]
+    pub ipf: Option<u32>,
+    #[serde(default)]
+    pub colours: ColoursConfig,
+}
+
+/// Where the user's own entries are stored, which take priority over the bundled ones
+pub fn path() -> Option<PathBuf> {
+    config::project_dirs().map(|dirs| dirs.config_dir().join("roms.toml"))
+}
+
+/// Find the entry for the ROM with this program
+pub fn lookup(program: &[u8]) -> Result<Option<Entry>, Error> {
+    Ok(find(&load()?, program).cloned())
+}
+
+/// Every entry, with the user's own first so they take priority over the bundled ones
+pub fn load() -> Result<Vec<Entry>, Error> {
+    let mut entries = Vec::new();
+    if let Some(path) = path() {
+        match std::fs::read_to_string(&path) {
+            Ok(contents) => {
+                let database: Database =
+                    toml::from_str(&contents).map_err(|source| Error::Database { path, source })?;
+                entries = database.roms;
+            }
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+            Err(err) => return Err(err.into()),
+        }
+    }
+
+    let bundled: Database = toml::from_str(BUNDLED).expect("Bundled ROM database is invalid");
+    entries.extend(bundled.roms);
+    Ok(entries)
+}
+
+/// Find the entry for the ROM with this program among `entries`
+pub fn find<'a>(entries: &'a [Entry], program: &[u8]) -> Option<&'a Entry> {
+    let hash = hash(program);
+    entries
+        .iter()
+        .find(|entry| entry.sha1.eq_ignore_ascii_case(&hash))
+}
+
+/// A program in the CHIP-8 community's database, `programs.json` in
+/// <https://github.com/chip-8/chip-8-database>
+#[derive(Deserialize)]
+struct Program {
+    title: String,
+    /// Each version of the program, by SHA-1
+    roms: BTreeMap<String, Rom>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Rom {
+    #[serde(default)]
+    platforms: Vec<String>,
+    /// Quirks that differ from the platform's usual ones
+    #[serde(default)]
+    quirky_platforms: BTreeMap<String, BTreeMap<String, bool>>,
+    /// Instructions per frame
+    tickrate: Option<u32>,
+    colors: Option<Colors>,
+}
+
+#[derive(Deserialize)]
+struct Colors {
+    /// The background first, then the colours of each plane
+    #[serde(default)]
+    pixels: Vec<String>,
+}
+
+/// Convert the CHIP-8 community's `programs.json` to entries for `roms.toml`
+///
+/// ROMs for platforms that can't be emulated, like XO-CHIP, are left out.
+pub fn import(programs: &str) -> Result<String, Error> {
+    let programs: Vec<Program> =
+        serde_json::from_str(programs).map_err(|err| Error::Import(err.to_string()))?;
+
+    let mut entries = String::new();
+    for program in programs {
+        for (sha1, rom) in program.roms {
+            // The platforms are in order of preference
+            let Some((platform, system, defaults)) = rom.platforms.iter().find_map(|platform| {
+                let (system, defaults) = platform_quirks(platform)?;
+                Some((platform, system, defaults))
+            }) else {
+                continue;
+            };
+
+            // The community's quirks are named for how they differ from the original CHIP-8
+            let mut quirks = defaults;
+            for (quirk, &value) in rom.quirky_platforms.get(platform).into_iter().flatten() {
+                match quirk.as_str() {
+                    "logic" => quirks.logic_resets_flag = value,
+                    "shift" => quirks.shift_uses_vy = !value,
+                    "jump" => quirks.jump_uses_vx = value,
+                    "memoryLeaveIUnchanged" => quirks.memory_increments_index = !value,
+                    _ => {}
+                }
+            }
+
+            writeln!(entries, "[[roms]]").unwrap();
+            writeln!(entries, "sha1 = {sha1:?}").unwrap();
+            writeln!(entries, "title = {}", toml::Value::from(&*program.title)).unwrap();
+            let name = match system {
+                EmulationSystem::Chip8 => "chip8",
+                EmulationSystem::SuperChip => "super-chip",
+            };
+            writeln!(entries, "system = {name:?}").unwrap();
+            if let Some(tickrate) = rom.tickrate {
+                writeln!(entries, "ipf = {tickrate}").unwrap();
+            }
+            let changes = QuirksConfig::changes(Quirks::for_system(system), quirks);
+            if changes != QuirksConfig::default() {
+                let quirks = toml::to_string(&changes).expect("Quirks can always be serialised");
+                writeln!(
+                    entries,
+                    "quirks = {{ {} }}",
+                    quirks.trim().replace('\n', ", ")
+                )
+                .unwrap();
+            }
+
+            if let Some([off, on, ..]) = rom.colors.as_ref().map(|colors| &colors.pixels[..])
+                && off.parse::<HexColour>().is_ok()
+                && on.parse::<HexColour>().is_ok()
+            {
+                writeln!(entries, "colours = {{ off = {off:?}, on = {on:?} }}").unwrap();
+            }
+            writeln!(entries).unwrap();
+        }
+    }
+    Ok(entries)
+}
+
+/// The system to emulate a platform in the community's database as, and its quirks
+fn platform_quirks(platform: &str) -> Option<(EmulationSystem, Quirks)> {
+    let chip8 = Quirks::for_system(EmulationSystem::Chip8);
+    Some(match platform {
+        "originalChip8" | "hybridVIP" => (EmulationSystem::Chip8, chip8),
+        "modernChip8" => (
+            EmulationSystem::Chip8,
+            Quirks {
+                logic_resets_flag: false,
+                ..chip8
+            },
+        ),
+        // CHIP-48 has most of SUPER-CHIP's quirks, but none of its instructions
+        "chip48" => (
+            EmulationSystem::Chip8,
+            Quirks {
+                memory_increments_index: true,
+                ..Quirks::for_system(EmulationSystem::SuperChip)
+            },
+        ),
+        "superchip1" | "superchip" => (
+            EmulationSystem::SuperChip,
+            Quirks::for_system(EmulationSystem::SuperChip),
+        ),
+        _ => return None,
+    })
+}
+
+/// The SHA-1 of the program in hex, which CHIP-8 ROM databases identify ROMs by
+pub fn hash(program: &[u8]) -> String {
+    sha1(program)
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+fn sha1(data: &[u8]) -> [u8; 20] {
+    let mut state: [u32; 5] = [0x67452301, 0xEFCDAB89, 0x98BADCFE, 0x10325476, 0xC3D2E1F0];
+
+    // Pad with a 1 bit, then zeroes up to 8 bytes short of a whole block, then the length in bits
+    let mut message = data.to_vec();
+    message.push(0x80);
+    while message.len() % 64 != 56 {
+        message.push(0);
+    }
+    message.extend_from_slice(&(data.len() as u64 * 8).to_be_bytes());
+
+    for block in message.chunks_exact(64) {
+        let mut words = [0; 80];
+        for (word, bytes) in words.iter_mut().zip(block.chunks_exact(4)) {
+            *word = u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+        }
+        for i in 16..80 {
+            words[i] = (words[i - 3] ^ words[i - 8] ^ words[i - 14] ^ words[i - 16]).rotate_left(1);
+        }
+
+        let [mut a, mut b, mut c, mut d, mut e] = state;
+        for (i, word) in words.into_iter().enumerate() {
+            let (f, k) = match i {
+                0..20 => ((b & c) | (!b & d), 0x5A827999),
+                20..40 => (b ^ c ^ d, 0x6ED9EBA1),
+                40..60 => ((b & c) | (b & d) | (c & d), 0x8F1BBCDC),
+                _ => (b ^ c ^ d, 0xCA62C1D6),
+            };
+            let temp = a
+                .rotate_left(5)
+                .wrapping_add(f)
+                .wrapping_add(e)
+                .wrapping_add(k)
+                .wrapping_add(word);
+            e = d;
+            d = c;
+            c = b.rotate_left(30);
+            b = a;
+            a = temp;
+        }
+
+        for (state, value) in state.iter_mut().zip([a, b, c, d, e]) {
+            *state = state.wrapping_add(value);
+        }
+    }
+
+    let mut hash = [0; 20];
+    for (bytes, word) in hash.chunks_exact_mut(4).zip(state) {
+        bytes.copy_from_slice(&word.to_be_bytes());
+    }
+    hash
+}
@@ -0,0 +1,520 @@
+use crate::{Error, breakpoint::Breakpoints, palette::Colours, search::MemorySearch, text};
+use rs_chip8_core::{MachineState, RAM_SIZE, disasm};
+use sdl3::{
+    keyboard::Scancode,
+    pixels::Color,
+    rect::FRect,
+    render::{BlendMode, Canvas},
+    video::Window,
+};
+
+/// Instructions shown either side of the program counter
+const DISASSEMBLY_CONTEXT: usize = 5;
+
+/// Characters between the start of the registers and the disassembly
+const DISASSEMBLY_COLUMN: usize = 26;
+
+/// Shows the bytes at an address as sprites, to see what the next DXYN would draw
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SpriteViewer {
+    /// Follows the index register if not set
+    address: Option<u16>,
+}
+
+impl SpriteViewer {
+    pub fn address(self, machine_state: &MachineState) -> u16 {
+        self.address.unwrap_or(machine_state.index_register())
+    }
+
+    /// Move the viewed address by `offset` bytes, no longer following the index register
+    pub fn scroll(&mut self, machine_state: &MachineState, offset: i16) {
+        let address = self.address(machine_state).saturating_add_signed(offset);
+        self.address = Some(address.min(RAM_SIZE as u16 - 1));
+    }
+
+    pub fn follow_index(&mut self) {
+        self.address = None;
+    }
+}
+
+/// A value in the overlay that can be edited while paused
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Field {
+    ProgramCounter,
+    IndexRegister,
+    DelayTimer,
+    SoundTimer,
+    Register(usize),
+    Stack(usize),
+}
+
+impl Field {
+    /// Every editable field, in the order they are shown
+    fn all(machine_state: &MachineState) -> Vec<Field> {
+        let mut fields = vec![
+            Field::ProgramCounter,
+            Field::IndexRegister,
+            Field::DelayTimer,
+            Field::SoundTimer,
+        ];
+        fields.extend((0..16).map(Field::Register));
+        fields.extend((0..machine_state.stack().len()).map(Field::Stack));
+        fields
+    }
+
+    /// Line and column of the value in the overlay, and how many hex digits it's shown with
+    fn position(self) -> (usize, usize, usize) {
+        match self {
+            Field::ProgramCounter => (0, 3, 4),
+            Field::IndexRegister => (0, 11, 4),
+            Field::DelayTimer => (1, 3, 2),
+            Field::SoundTimer => (1, 13, 2),
+            Field::Register(x) => (2 + x / 4, 6 * (x % 4) + 3, 2),
+            Field::Stack(depth) => (7 + depth / 4, 4 * (depth % 4), 3),
+        }
+    }
+
+    fn set(self, machine_state: &mut MachineState, value: u16) {
+        match self {
+            Field::ProgramCounter => machine_state.set_program_counter(value),
+            Field::IndexRegister => machine_state.set_index_register(value),
+            Field::DelayTimer => machine_state.set_delay_timer(value as u8),
+            Field::SoundTimer => machine_state.set_sound_timer(value as u8),
+            Field::Register(x) => machine_state.set_register(x, value as u8),
+            Field::Stack(depth) => machine_state.set_stack_entry(depth, value),
+        }
+    }
+}
+
+/// Selects and edits registers, timers, and stack entries from the keyboard
+///
+/// The arrow keys select a value and enter starts editing it.
+/// While editing, hex digits are typed in, enter sets the value and escape cancels.
+#[derive(Debug, Clone, Default)]
+pub struct RegisterEditor {
+    selected: usize,
+    /// Digits typed so far, if editing
+    input: Option<String>,
+}
+
+impl RegisterEditor {
+    fn selected(&self, machine_state: &MachineState) -> Field {
+        let fields = Field::all(machine_state);
+        fields[self.selected.min(fields.len() - 1)]
+    }
+
+    /// Handle a key press, returning whether the editor used it
+    pub fn key_down(&mut self, scancode: Scancode, machine_state: &mut MachineState) -> bool {
+        let field = self.selected(machine_state);
+        let Some(input) = &mut self.input else {
+            let last = Field::all(machine_state).len() - 1;
+            let selected = self.selected.min(last);
+            self.selected = match scancode {
+                Scancode::Left => selected.saturating_sub(1),
+                Scancode::Right => (selected + 1).min(last),
+                Scancode::Up => selected.saturating_sub(4),
+                Scancode::Down => (selected + 4).min(last),
+                Scancode::Return | Scancode::KpEnter => {
+                    self.input = Some(String::new());
+                    selected
+                }
+                _ => return false,
+            };
+            return true;
+        };
+
+        match scancode {
+            Scancode::Return | Scancode::KpEnter => {
+                if let Ok(value) = u16::from_str_radix(input, 16) {
+                    field.set(machine_state, value);
+                }
+                self.input = None;
+            }
+            Scancode::Escape => self.input = None,
+            Scancode::Backspace => {
+                input.pop();
+            }
+            _ => {
+                let (_, _, digits) = field.position();
+                if let Some(digit) = hex_digit(scancode)
+                    && input.len() < digits
+                {
+                    input.push(digit);
+                }
+            }
+        }
+        // Keep other keys from reaching the game while editing
+        true
+    }
+}
+
+/// The hex digit typed with a key, from the number row, number pad, or letters A to F
+pub fn hex_digit(scancode: Scancode) -> Option<char> {
+    Some(match scancode {
+        Scancode::_0 | Scancode::Kp0 => '0',
+        Scancode::_1 | Scancode::Kp1 => '1',
+        Scancode::_2 | Scancode::Kp2 => '2',
+        Scancode::_3 | Scancode::Kp3 => '3',
+        Scancode::_4 | Scancode::Kp4 => '4',
+        Scancode::_5 | Scancode::Kp5 => '5',
+        Scancode::_6 | Scancode::Kp6 => '6',
+        Scancode::_7 | Scancode::Kp7 => '7',
+        Scancode::_8 | Scancode::Kp8 => '8',
+        Scancode::_9 | Scancode::Kp9 => '9',
+        Scancode::A => 'A',
+        Scancode::B => 'B',
+        Scancode::C => 'C',
+        Scancode::D => 'D',
+        Scancode::E => 'E',
+        Scancode::F => 'F',
+        _ => return None,
+    })
+}
+
+/// Text typed in to add or remove a breakpoint
+#[derive(Debug, Clone, Default)]
+pub struct BreakpointPrompt {
+    pub input: String,
+    /// Why the last input was rejected
+    pub error: Option<String>,
+}
+
+/// State of the debugging tools shown in the overlay
+#[derive(Debug, Clone, Default)]
+pub struct Debugger {
+    pub sprite_viewer: SpriteViewer,
+    pub register_editor: RegisterEditor,
+    pub breakpoints: Breakpoints,
+    pub breakpoint_prompt: Option<BreakpointPrompt>,
+    pub memory_search: MemorySearch,
+}
+
+/// Size of a font pixel in logical units, so text stays small and sharp at any window size
+///
+/// Each font pixel is a whole number of screen pixels, roughly a quarter of a display pixel.
+pub fn font_scale(canvas: &Canvas<Window>, (width, height): (usize, usize)) -> Result<f32, Error> {
+    let (output_width, output_height) = canvas.output_size()?;
+    let logical_scale =
+        (output_width as f32 / width as f32).min(output_height as f32 / height as f32);
+    Ok((logical_scale / 4.).floor().max(1.) / logical_scale)
+}
+
+/// Draw the CPU state, the code around the program counter and the execution controls
+pub fn draw_overlay(
+    canvas: &mut Canvas<Window>,
+    machine_state: &MachineState,
+    colours: Colours,
+    paused: bool,
+    debugger: &Debugger,
+    (width, height): (usize, usize),
+) -> Result<(), Error> {
+    let Debugger {
+        sprite_viewer,
+        register_editor,
+        breakpoints,
+        breakpoint_prompt,
+        ..
+    } = debugger;
+    let scale = font_scale(canvas, (width, height))?;
+    let line_height = (text::GLYPH_HEIGHT + 2) as f32 * scale;
+    let char_width = (text::GLYPH_WIDTH + 1) as f32 * scale;
+
+    canvas.set_blend_mode(BlendMode::Blend);
+    let off = colours.off;
+    canvas.set_draw_color(Color::RGBA(off.r, off.g, off.b, 0xd0));
+    canvas.fill_rect(FRect::new(0., 0., width as f32, height as f32))?;
+    canvas.set_blend_mode(BlendMode::None);
+
+    let mut lines = Vec::new();
+    lines.push(format!(
+        "PC {:04X}  I {:04X}",
+        machine_state.program_counter(),
+        machine_state.index_register()
+    ));
+    lines.push(format!(
+        "DT {:02X}     ST {:02X}",
+        machine_state.delay_timer(),
+        machine_state.sound_timer()
+    ));
+    for (row, registers) in machine_state.registers().chunks(4).enumerate() {
+        let mut line = String::new();
+        for (i, value) in registers.iter().enumerate() {
+            line.push_str(&format!("V{:X} {value:02X} ", row * 4 + i));
+        }
+        lines.push(line);
+    }
+    let stack = machine_state.stack();
+    lines.push(format!("STACK {}/16", stack.len()));
+    for addresses in stack.chunks(4) {
+        lines.push(
+            addresses
+                .iter()
+                .map(|address| format!("{address:03X}"))
+                .collect::<Vec<_>>()
+                .join(" "),
+        );
+    }
+
+    for (i, line) in lines.iter().enumerate() {
+        text::draw(
+            canvas,
+            scale,
+            scale + i as f32 * line_height,
+            scale,
+            line,
+            colours.on,
+        )?;
+    }
+
+    // Values can only be edited while paused
+    if paused {
+        let (line, column, digits) = register_editor.selected(machine_state).position();
+        let x = scale + column as f32 * char_width;
+        let y = scale + line as f32 * line_height;
+        canvas.set_draw_color(colours.on);
+        canvas.fill_rect(FRect::new(
+            x - scale,
+            y - scale,
+            digits as f32 * char_width + scale,
+            line_height,
+        ))?;
+        let value = match &register_editor.input {
+            Some(input) => format!("{input:_<digits$}"),
+            None => lines[line].chars().skip(column).take(digits).collect(),
+        };
+        text::draw(canvas, x, y, scale, &value, colours.off)?;
+    }
+
+    let pc = machine_state.program_counter();
+    let marker = |address| {
+        if address == pc {
+            '>'
+        } else if breakpoints.contains(address) {
+            '*'
+        } else {
+            ' '
+        }
+    };
+    for (row, line) in disassembly(machine_state, pc, marker) {
+        text::draw(
+            canvas,
+            scale + DISASSEMBLY_COLUMN as f32 * char_width,
+            scale + row as f32 * line_height,
+            scale,
+            &line,
+            colours.on,
+        )?;
+    }
+
+    draw_sprites(
+        canvas,
+        machine_state,
+        sprite_viewer.address(machine_state),
+        colours,
+        width as f32 - 28.,
+        1.,
+    )?;
+    let label = match sprite_viewer.address {
+        Some(address) => format!("{address:03X}"),
+        None => format!(
+            "I {address:03X}",
+            address = sprite_viewer.address(machine_state)
+        ),
+    };
+    text::draw(canvas, width as f32 - 28., 18., scale, &label, colours.on)?;
+
+    for (i, breakpoint) in breakpoints.iter().enumerate() {
+        let mut line = format!("*{:03X}", breakpoint.address);
+        if let Some(condition) = &breakpoint.condition {
+            line.push_str(&format!(" {condition}"));
+        }
+        line.push_str(&format!(" X{}", breakpoint.hits));
+        text::draw(
+            canvas,
+            width as f32 - 28.,
+            18. + (i + 1) as f32 * line_height,
+            scale,
+            &line,
+            colours.on,
+        )?;
+    }
+
+    // Breakpoints and values can only be changed while paused
+    let editing = match breakpoint_prompt {
+        Some(BreakpointPrompt {
+            error: Some(error), ..
+        }) => Some(error.clone()),
+        Some(BreakpointPrompt { input, .. }) => Some(format!("BREAK AT: {input}_")),
+        None if paused => Some(String::from(
+            "ARROWS: SELECT  ENTER: EDIT  B: BREAK AT ADDR [IF V3 == 0X1F]",
+        )),
+        None => None,
+    };
+    if let Some(editing) = editing {
+        text::draw(
+            canvas,
+            scale,
+            height as f32 - 2. * line_height,
+            scale,
+            &editing,
+            colours.on,
+        )?;
+    }
+
+    let controls = format!(
+        "{}  P: RUN/PAUSE  .: FRAME  ,: STEP  [ ]: SPRITE  \\: SPRITE AT I  `: HIDE",
+        if paused { "PAUSED" } else { "RUNNING" }
+    );
+    text::draw(
+        canvas,
+        scale,
+        height as f32 - line_height,
+        scale,
+        &controls,
+        colours.on,
+    )?;
+
+    Ok(())
+}
+
+/// Disassemble the instructions around `centre`, each with its row and a marker from `marker`
+///
+/// Instructions are assumed to be aligned with `centre`.
+fn disassembly(
+    machine_state: &MachineState,
+    centre: u16,
+    marker: impl Fn(u16) -> char,
+) -> Vec<(usize, String)> {
+    let memory = machine_state.memory();
+    let mut lines = Vec::new();
+    for row in 0..=2 * DISASSEMBLY_CONTEXT {
+        let address = centre as usize + 2 * row;
+        if address < 2 * DISASSEMBLY_CONTEXT {
+            continue;
+        }
+        let address = address - 2 * DISASSEMBLY_CONTEXT;
+        if address + 1 >= RAM_SIZE {
+            break;
+        }
+
+        let opcode = u16::from_be_bytes([memory[address], memory[address + 1]]);
+        lines.push((
+            row,
+            format!(
+                "{} {address:03X} {opcode:04X} {}",
+                marker(address as u16),
+                disasm::disassemble(opcode, machine_state.system())
+            ),
+        ));
+    }
+    lines
+}
+
+/// Draw an error that paused emulation, the code where it happened, and the ways to carry on
+pub fn draw_fault(
+    canvas: &mut Canvas<Window>,
+    machine_state: &MachineState,
+    error: &Error,
+    colours: Colours,
+    (width, height): (usize, usize),
+) -> Result<(), Error> {
+    let scale = font_scale(canvas, (width, height))?;
+    let line_height = (text::GLYPH_HEIGHT + 2) as f32 * scale;
+
+    canvas.set_blend_mode(BlendMode::Blend);
+    let off = colours.off;
+    canvas.set_draw_color(Color::RGBA(off.r, off.g, off.b, 0xd0));
+    canvas.fill_rect(FRect::new(0., 0., width as f32, height as f32))?;
+    canvas.set_blend_mode(BlendMode::None);
+
+    // The program ending itself isn't a failure, so only the ways to carry on are shown
+    let exited = matches!(
+        error,
+        Error::Emulation {
+            error: rs_chip8_core::Error::ProgramExited,
+            ..
+        }
+    );
+    let mut lines = if exited {
+        vec![String::from("The program exited")]
+    } else {
+        error.to_string().lines().map(String::from).collect()
+    };
+    if let Error::Emulation { address, .. } = error
+        && !exited
+    {
+        lines.push(String::new());
+        let address = *address;
+        lines.extend(
+            disassembly(machine_state, address, |other| {
+                if other == address { '>' } else { ' ' }
+            })
+            .into_iter()
+            .map(|(_, line)| line),
+        );
+    }
+    for (i, line) in lines.iter().enumerate() {
+        text::draw(
+            canvas,
+            scale,
+            scale + i as f32 * line_height,
+            scale,
+            line,
+            colours.on,
+        )?;
+    }
+
+    text::draw(
+        canvas,
+        scale,
+        height as f32 - line_height,
+        scale,
+        if exited {
+            "R: RESET  Q: QUIT"
+        } else {
+            "S: SKIP THE INSTRUCTION  R: RESET  Q: QUIT"
+        },
+        colours.on,
+    )?;
+
+    Ok(())
+}
+
+/// Draw the bytes at `address` as an 8x15 sprite and a 16x16 SuperChip sprite, side by side
+fn draw_sprites(
+    canvas: &mut Canvas<Window>,
+    machine_state: &MachineState,
+    address: u16,
+    colours: Colours,
+    x: f32,
+    y: f32,
+) -> Result<(), Error> {
+    let memory = machine_state.memory();
+    let byte = |offset: usize| memory.get(address as usize + offset).copied().unwrap_or(0);
+
+    canvas.set_draw_color(colours.off);
+    canvas.fill_rect(FRect::new(x - 1., y - 1., 10., 17.))?;
+    canvas.fill_rect(FRect::new(x + 10., y - 1., 18., 18.))?;
+
+    let mut pixels = Vec::new();
+    for row in 0..15 {
+        for column in 0..8 {
+            if (byte(row) >> (7 - column)) & 1 == 1 {
+                pixels.push(FRect::new(x + column as f32, y + row as f32, 1., 1.));
+            }
+        }
+    }
+    for row in 0..16 {
+        let bits = u16::from_be_bytes([byte(2 * row), byte(2 * row + 1)]);
+        for column in 0..16 {
+            if (bits >> (15 - column)) & 1 == 1 {
+                pixels.push(FRect::new(x + 11. + column as f32, y + row as f32, 1., 1.));
+            }
+        }
+    }
+
+    canvas.set_draw_color(colours.on);
+    canvas.fill_rects(&pixels)?;
+
+    Ok(())
+}
@@ -0,0 +1,132 @@
+use crate::{
+    Error,
+    debug::{self, Debugger, font_scale},
+    palette::Colours,
+    text,
+};
+use rs_chip8_core::{DISPLAY_HEIGHT, DISPLAY_WIDTH, MachineState, RAM_SIZE};
+use sdl3::{
+    VideoSubsystem,
+    render::Canvas,
+    sys::render::SDL_RendererLogicalPresentation,
+    video::{Window, WindowBuildError},
+};
+
+/// Height of the memory view below the debugger, in the same units as the display
+const MEMORY_HEIGHT: usize = 48;
+
+/// The debugger in a window of its own, with a view of memory, leaving the game unobstructed
+pub struct DebugWindow {
+    canvas: Canvas<Window>,
+}
+
+impl DebugWindow {
+    pub fn open(video_subsystem: &VideoSubsystem) -> Result<Self, Error> {
+        let (width, height) = (DISPLAY_WIDTH, DISPLAY_HEIGHT + MEMORY_HEIGHT);
+        let window = match video_subsystem
+            .window("rs_chip8 debugger", width as u32 * 6, height as u32 * 6)
+            .resizable()
+            .high_pixel_density()
+            .build()
+        {
+            Ok(window) => window,
+            Err(WindowBuildError::SdlError(err)) => return Err(err.into()),
+            Err(err) => panic!("Expected window dimensions and title to be valid, but {err}"),
+        };
+
+        let mut canvas = window.into_canvas();
+        if let Err(err) = canvas.set_logical_size(
+            width as u32,
+            height as u32,
+            SDL_RendererLogicalPresentation::LETTERBOX,
+        ) {
+            if let sdl3::IntegerOrSdlError::SdlError(err) = err {
+                return Err(err.into());
+            } else {
+                panic!("Expected debugger height and width to be valid");
+            }
+        }
+
+        Ok(Self { canvas })
+    }
+
+    pub fn id(&self) -> u32 {
+        self.canvas.window().id()
+    }
+
+    pub fn draw(
+        &mut self,
+        machine_state: &MachineState,
+        colours: Colours,
+        paused: bool,
+        debugger: &Debugger,
+    ) -> Result<(), Error> {
+        let canvas = &mut self.canvas;
+        canvas.set_draw_color(colours.off);
+        canvas.clear();
+
+        debug::draw_overlay(
+            canvas,
+            machine_state,
+            colours,
+            paused,
+            debugger,
+            (DISPLAY_WIDTH, DISPLAY_HEIGHT),
+        )?;
+        draw_memory(canvas, machine_state, colours)?;
+
+        canvas.present();
+        Ok(())
+    }
+}
+
+/// Rows of memory in hex around the index register, below the debugger
+fn draw_memory(
+    canvas: &mut Canvas<Window>,
+    machine_state: &MachineState,
+    colours: Colours,
+) -> Result<(), Error> {
+    let scale = font_scale(canvas, (DISPLAY_WIDTH, DISPLAY_HEIGHT))?;
+    let line_height = (text::GLYPH_HEIGHT + 2) as f32 * scale;
+    let rows = (MEMORY_HEIGHT as f32 / line_height) as usize - 1;
+
+    // A few rows before I are shown too, for data just before it
+    let memory = machine_state.memory();
+    let index = machine_state.index_register() as usize % RAM_SIZE;
+    let first_row = (index / 16)
+        .saturating_sub(rows / 4)
+        .min(RAM_SIZE / 16 - rows);
+    let top = DISPLAY_HEIGHT as f32;
+    text::draw(
+        canvas,
+        scale,
+        top,
+        scale,
+        &format!("MEMORY AT I {index:03X}"),
+        colours.on,
+    )?;
+
+    for (i, row) in (first_row..first_row + rows).enumerate() {
+        let bytes = &memory[row * 16..row * 16 + 16];
+        let line = format!(
+            "{}{:03X}: {}",
+            if row == index / 16 { '>' } else { ' ' },
+            row * 16,
+            bytes
+                .iter()
+                .map(|byte| format!("{byte:02X}"))
+                .collect::<Vec<_>>()
+                .join(" ")
+        );
+        text::draw(
+            canvas,
+            scale,
+            top + (i + 1) as f32 * line_height,
+            scale,
+            &line,
+            colours.on,
+        )?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,76 @@
+use clap::ValueEnum;
+use sdl3::{
+    pixels::Color,
+    rect::FRect,
+    render::{BlendMode, Canvas},
+    video::Window,
+};
+use serde::Deserialize;
+
+/// Darkness of the gaps between scanlines
+const SCANLINE_ALPHA: u8 = 80;
+
+/// Darkness of the vignette at the very edge of the display
+const VIGNETTE_ALPHA: f32 = 160.;
+/// Number of bands the vignette fades over, each half a display pixel wide
+const VIGNETTE_STEPS: usize = 12;
+
+/// Post-processing drawn over the display
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Filter {
+    #[default]
+    None,
+    /// Dark gaps between each row of pixels
+    Scanlines,
+    /// Scanlines with darkened edges, like a CRT monitor
+    Crt,
+}
+
+impl Filter {
+    /// Draw the filter over a logical size of `width` x `height`
+    ///
+    /// Relies on the logical size scaling fractional coordinates up to the window's resolution.
+    pub fn draw(
+        self,
+        canvas: &mut Canvas<Window>,
+        (width, height): (usize, usize),
+    ) -> Result<(), sdl3::Error> {
+        if self == Filter::None {
+            return Ok(());
+        }
+
+        canvas.set_blend_mode(BlendMode::Blend);
+
+        canvas.set_draw_color(Color::RGBA(0, 0, 0, SCANLINE_ALPHA));
+        let scanlines = (0..height)
+            .map(|y| FRect::new(0., y as f32 + 0.5, width as f32, 0.5))
+            .collect::<Vec<_>>();
+        canvas.fill_rects(&scanlines)?;
+
+        if self == Filter::Crt {
+            let (width, height) = (width as f32, height as f32);
+            for step in 0..VIGNETTE_STEPS {
+                let fade = 1. - step as f32 / VIGNETTE_STEPS as f32;
+                canvas.set_draw_color(Color::RGBA(0, 0, 0, (VIGNETTE_ALPHA * fade * fade) as u8));
+
+                let inset = step as f32 * 0.5;
+                canvas.fill_rects(&[
+                    FRect::new(inset, inset, width - 2. * inset, 0.5),
+                    FRect::new(inset, height - inset - 0.5, width - 2. * inset, 0.5),
+                    FRect::new(inset, inset + 0.5, 0.5, height - 2. * inset - 1.),
+                    FRect::new(
+                        width - inset - 0.5,
+                        inset + 0.5,
+                        0.5,
+                        height - 2. * inset - 1.,
+                    ),
+                ])?;
+            }
+        }
+
+        canvas.set_blend_mode(BlendMode::None);
+
+        Ok(())
+    }
+}
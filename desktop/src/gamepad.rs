@@ -0,0 +1,81 @@
+use crate::Error;
+use sdl3::{
+    GamepadSubsystem,
+    gamepad::{Button, Gamepad},
+};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+/// The CHIP-8 key pressed by each gamepad button
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GamepadMap(HashMap<Button, usize>);
+
+impl Default for GamepadMap {
+    /// The d-pad on 2, 4, 6, and 8, which most games use for directions,
+    /// and the face buttons on 5, A, B, and F
+    fn default() -> Self {
+        Self(HashMap::from([
+            (Button::DPadUp, 0x2),
+            (Button::DPadLeft, 0x4),
+            (Button::DPadRight, 0x6),
+            (Button::DPadDown, 0x8),
+            (Button::South, 0x5),
+            (Button::East, 0xA),
+            (Button::West, 0xB),
+            (Button::North, 0xF),
+        ]))
+    }
+}
+
+impl GamepadMap {
+    /// Apply overrides, given as SDL button name to hex digit
+    pub fn with_overrides(mut self, overrides: &BTreeMap<String, String>) -> Result<Self, Error> {
+        for (button_name, key) in overrides {
+            let button = Button::from_string(button_name)
+                .ok_or_else(|| Error::Gamepad(format!("{button_name:?} is not a button name")))?;
+            let key = u8::from_str_radix(key, 16)
+                .ok()
+                .filter(|&key| key < 16)
+                .ok_or_else(|| Error::Gamepad(format!("{key:?} is not a CHIP-8 key")))?;
+
+            self.0.insert(button, key as usize);
+        }
+
+        Ok(self)
+    }
+
+    pub fn set(&mut self, button: Button, key: usize) {
+        self.0.insert(button, key);
+    }
+
+    /// The CHIP-8 key the button is mapped to
+    pub fn key(&self, button: Button) -> Option<usize> {
+        self.0.get(&button).copied()
+    }
+}
+
+/// Open a newly connected gamepad, so its buttons are reported while it's kept in `gamepads`
+pub fn open(subsystem: &GamepadSubsystem, which: u32, gamepads: &mut Vec<Gamepad>) {
+    match subsystem.open(which) {
+        Ok(gamepad) => {
+            println!(
+                "Connected {}",
+                gamepad.name().as_deref().unwrap_or("gamepad")
+            );
+            gamepads.push(gamepad);
+        }
+        Err(err) => eprintln!("Failed to open gamepad: {err}"),
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct GamepadConfig {
+    /// Name of a profile from the config file to apply
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub profile: Option<String>,
+
+    /// Overrides of individual buttons, from SDL button name to hex digit
+    #[serde(flatten)]
+    pub buttons: BTreeMap<String, String>,
+}
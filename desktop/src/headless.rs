@@ -0,0 +1,184 @@
+use crate::{
+    Error,
+    cli::Cli,
+    config, database, emulation_error, load_machine_state,
+    movie::{self, InputChange, Movie},
+    trace::Trace,
+};
+use rand::{Rng, SeedableRng, rngs::StdRng};
+use rs_chip8_core::{DISPLAY_HEIGHT, DISPLAY_WIDTH, Display, MachineState};
+use serde::Deserialize;
+use std::{
+    ffi::OsStr,
+    fmt::Write as _,
+    io::Write as _,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+/// Keys to hold during a headless run
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+enum Script {
+    /// A recorded movie, which also sets the random seed and IPF
+    Movie(Movie),
+    Input(Vec<InputChange>),
+}
+
+/// The display as packed pixels, a row at a time
+fn pixels(display: &Display) -> Vec<u8> {
+    let mut pixels = Vec::with_capacity(DISPLAY_WIDTH / 8 * DISPLAY_HEIGHT);
+    for y in 0..DISPLAY_HEIGHT {
+        for x in (0..DISPLAY_WIDTH).step_by(8) {
+            pixels.push((0..8).fold(0, |byte, bit| byte << 1 | display.pixel(x + bit, y) as u8));
+        }
+    }
+    pixels
+}
+
+/// Load the ROM given on the command line, returning its path, its data folder,
+/// and the IPF to run it at
+fn load<'a>(
+    cli: &'a Cli,
+    config: &config::Config,
+) -> Result<(&'a Path, PathBuf, MachineState, u32), Error> {
+    let rom = cli
+        .rom
+        .as_deref()
+        .expect("clap requires a ROM without a window");
+    let program = std::fs::read(rom).map_err(|source| Error::ReadRom {
+        path: rom.to_path_buf(),
+        source,
+    })?;
+    let rom_config = match config::RomConfig::path(&program) {
+        Some(path) => config::RomConfig::load(&path)?,
+        None => config::RomConfig::default(),
+    };
+    let database_entry = database::lookup(&program)?;
+    let ipf = cli
+        .ipf
+        .or(rom_config.ipf)
+        .or(database_entry.as_ref().and_then(|entry| entry.ipf))
+        .unwrap_or(config.ipf);
+    let machine_state = load_machine_state(
+        rom,
+        &program,
+        cli.system,
+        config,
+        &rom_config,
+        database_entry.as_ref(),
+    )?;
+    Ok((rom, config::rom_data_dir(rom, &program), machine_state, ipf))
+}
+
+/// Run the ROM for a number of frames without a window or audio, for automated tests
+///
+/// Random numbers come from a fixed seed, or the one a movie was recorded with,
+/// so every run of a ROM with the same input is the same.
+pub fn run(cli: &Cli, config: &config::Config) -> Result<(), Error> {
+    let (rom, data_dir, mut machine_state, mut ipf) = load(cli, config)?;
+
+    let mut seed = 0;
+    let input = match &cli.input {
+        Some(path) => match serde_json::from_str(&std::fs::read_to_string(path)?)
+            .map_err(|err| Error::Input(err.to_string()))?
+        {
+            Script::Movie(recorded) => {
+                (seed, ipf) = (recorded.seed, recorded.ipf);
+                movie::input(&recorded.input)?
+            }
+            Script::Input(changes) => movie::input(&changes)?,
+        },
+        None => Vec::new(),
+    };
+    let mut input = input.into_iter().peekable();
+    let mut held_keys = 0;
+
+    // Unless saving the final display, the hash of every frame is written
+    let out = cli.out.as_deref();
+    let pbm = out.is_some_and(|path| path.extension() == Some(OsStr::new("pbm")));
+    let mut hashes = String::new();
+    let mut trace = cli.trace.clone().map(Trace::start).transpose()?;
+
+    for frame in 0..cli.frames {
+        while let Some((_, keys)) = input.next_if(|&(change, _)| change <= frame) {
+            held_keys = keys;
+        }
+
+        let mut rng = movie::frame_rng(seed, frame);
+        let mut traced = Ok(());
+        let result = machine_state.run_frame_until(
+            ipf,
+            || held_keys,
+            || rng.random(),
+            |machine_state| {
+                if let Some(trace) = &mut trace
+                    && traced.is_ok()
+                {
+                    traced = trace.instruction(machine_state);
+                }
+                false
+            },
+        );
+        traced?;
+        match result {
+            Ok(_) => {}
+            // SuperChip programs can end themselves with 00FD
+            Err(rs_chip8_core::Error::ProgramExited) => break,
+            Err(err) => return Err(emulation_error(rom, &data_dir, &machine_state, err)),
+        }
+
+        if out.is_some() && !pbm {
+            let hash = config::hash(&pixels(machine_state.display()));
+            let _ = writeln!(hashes, "{frame} {hash:016x}");
+        }
+    }
+
+    if let Some(trace) = trace {
+        trace.finish(&machine_state)?;
+    }
+
+    match out {
+        Some(path) if pbm => save_pbm(path, machine_state.display())?,
+        Some(path) => std::fs::write(path, hashes)?,
+        None => println!("{:016x}", config::hash(&pixels(machine_state.display()))),
+    }
+
+    Ok(())
+}
+
+/// Save the display as a binary PBM image, which is simple to compare in tests
+fn save_pbm(path: &Path, display: &Display) -> Result<(), Error> {
+    let mut file = std::io::BufWriter::new(std::fs::File::create(path)?);
+    write!(file, "P4\n{DISPLAY_WIDTH} {DISPLAY_HEIGHT}\n")?;
+    file.write_all(&pixels(display))?;
+    file.flush()?;
+    Ok(())
+}
+
+/// Run the ROM as fast as possible for `duration`, reporting the speed reached
+///
+/// No keys are held, so the speed of a game's title screen is measured.
+pub fn bench(cli: &Cli, config: &config::Config, duration: Duration) -> Result<(), Error> {
+    let (rom, data_dir, mut machine_state, ipf) = load(cli, config)?;
+    let mut rng = StdRng::seed_from_u64(0);
+
+    let start = Instant::now();
+    let mut frames = 0_u64;
+    while start.elapsed() < duration {
+        match machine_state.run_frame(ipf, || 0, || rng.random()) {
+            Ok(()) => frames += 1,
+            Err(rs_chip8_core::Error::ProgramExited) => break,
+            Err(err) => return Err(emulation_error(rom, &data_dir, &machine_state, err)),
+        }
+    }
+
+    let seconds = start.elapsed().as_secs_f64();
+    let instructions = frames * ipf as u64;
+    println!(
+        "Ran {frames} frames of {ipf} instructions in {seconds:.2} s: {:.0} FPS, {:.0} IPS",
+        frames as f64 / seconds,
+        instructions as f64 / seconds,
+    );
+    Ok(())
+}
@@ -0,0 +1,106 @@
+use crate::Error;
+use clap::ValueEnum;
+use sdl3::keyboard::Scancode;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum KeymapPreset {
+    /// The COSMAC VIP keypad on the 1234/QWER/ASDF/ZXCV block
+    ///
+    /// Keys are mapped by position, so this is the same block on any layout,
+    /// 1234/QWER/ASDF/YXCV on QWERTZ and 1234/AZER/QSDF/WXCV on AZERTY.
+    #[default]
+    Qwerty,
+    /// Hex digits on the matching number pad keys, A-F on the surrounding keys
+    Numpad,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct KeymapConfig {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub preset: Option<KeymapPreset>,
+
+    /// Overrides of individual keys, from hex digit to scancode name
+    #[serde(flatten)]
+    pub keys: BTreeMap<String, String>,
+}
+
+/// The scancode used for each CHIP-8 key, if it has one
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Keymap([Option<Scancode>; 16]);
+
+impl Keymap {
+    pub fn from_preset(preset: KeymapPreset) -> Self {
+        // Scancodes refer to physical key positions,
+        // so the same scancodes give the same layout on any keyboard
+        let scancodes = match preset {
+            KeymapPreset::Qwerty => [
+                Scancode::X,
+                Scancode::_1,
+                Scancode::_2,
+                Scancode::_3,
+                Scancode::Q,
+                Scancode::W,
+                Scancode::E,
+                Scancode::A,
+                Scancode::S,
+                Scancode::D,
+                Scancode::Z,
+                Scancode::C,
+                Scancode::_4,
+                Scancode::R,
+                Scancode::F,
+                Scancode::V,
+            ],
+            KeymapPreset::Numpad => [
+                Scancode::Kp0,
+                Scancode::Kp1,
+                Scancode::Kp2,
+                Scancode::Kp3,
+                Scancode::Kp4,
+                Scancode::Kp5,
+                Scancode::Kp6,
+                Scancode::Kp7,
+                Scancode::Kp8,
+                Scancode::Kp9,
+                Scancode::KpPeriod,
+                Scancode::KpEnter,
+                Scancode::KpPlus,
+                Scancode::KpMinus,
+                Scancode::KpMultiply,
+                Scancode::KpDivide,
+            ],
+        };
+        Self(scancodes.map(Some))
+    }
+
+    /// Apply overrides, given as hex digit to scancode name
+    pub fn with_overrides(mut self, overrides: &BTreeMap<String, String>) -> Result<Self, Error> {
+        for (key, scancode_name) in overrides {
+            let key = u8::from_str_radix(key, 16)
+                .ok()
+                .filter(|&key| key < 16)
+                .ok_or_else(|| Error::Keymap(format!("{key:?} is not a CHIP-8 key")))?;
+            let scancode = Scancode::from_name(scancode_name)
+                .ok_or_else(|| Error::Keymap(format!("{scancode_name:?} is not a key name")))?;
+
+            // A scancode only presses one key, so it's taken from any key it was mapped to
+            for mapped in &mut self.0 {
+                if *mapped == Some(scancode) {
+                    *mapped = None;
+                }
+            }
+            self.0[key as usize] = Some(scancode);
+        }
+
+        Ok(self)
+    }
+
+    /// The CHIP-8 key the scancode is mapped to
+    pub fn key(&self, scancode: Scancode) -> Option<usize> {
+        self.0.iter().position(|key| *key == Some(scancode))
+    }
+}
@@ -0,0 +1,79 @@
+use crate::{Error, palette::Colours, rotation::KEYPAD, text};
+use sdl3::{rect::FRect, render::Canvas, video::Window};
+
+/// Width and height of a key in logical units
+const KEY_SIZE: f32 = 3.;
+/// Space between keys, and around the keypad
+const GAP: f32 = 0.5;
+/// Space between the keypad and the edges of the screen
+const MARGIN: f32 = 1.;
+
+/// Size of the whole keypad, including the space around the keys
+const SIZE: f32 = 4. * KEY_SIZE + 5. * GAP;
+
+/// Top left corner of the keypad, in the bottom right corner of the screen
+fn origin((width, height): (usize, usize)) -> (f32, f32) {
+    (width as f32 - SIZE - MARGIN, height as f32 - SIZE - MARGIN)
+}
+
+/// Top left corner of the key in `row` and `column`
+fn key_origin((x, y): (f32, f32), row: usize, column: usize) -> (f32, f32) {
+    (
+        x + GAP + column as f32 * (KEY_SIZE + GAP),
+        y + GAP + row as f32 * (KEY_SIZE + GAP),
+    )
+}
+
+/// The key under a point in logical units, so the keypad can be clicked
+pub fn key_at((x, y): (f32, f32), (width, height): (usize, usize)) -> Option<usize> {
+    let origin = origin((width, height));
+    KEYPAD.iter().enumerate().find_map(|(row, keys)| {
+        keys.iter().enumerate().find_map(|(column, &key)| {
+            let (key_x, key_y) = key_origin(origin, row, column);
+            ((key_x..key_x + KEY_SIZE).contains(&x) && (key_y..key_y + KEY_SIZE).contains(&y))
+                .then_some(key)
+        })
+    })
+}
+
+/// Draw the COSMAC VIP keypad with the held keys highlighted
+///
+/// `held_keys` has bit n set if key n is held.
+pub fn draw(
+    canvas: &mut Canvas<Window>,
+    held_keys: u16,
+    colours: Colours,
+    (width, height): (usize, usize),
+) -> Result<(), Error> {
+    let (x, y) = origin((width, height));
+    canvas.set_draw_color(colours.on);
+    canvas.fill_rect(FRect::new(x, y, SIZE, SIZE))?;
+
+    // Labels fill most of the key, leaving a font pixel around them
+    let scale = KEY_SIZE / (text::GLYPH_HEIGHT + 2) as f32;
+    for (row, keys) in KEYPAD.iter().enumerate() {
+        for (column, &key) in keys.iter().enumerate() {
+            let (key_x, key_y) = key_origin((x, y), row, column);
+            let held = held_keys & (0b1 << key) != 0;
+
+            // Held keys stay the colour of the background, so they look pressed in
+            let (foreground, background) = if held {
+                (colours.off, colours.on)
+            } else {
+                (colours.on, colours.off)
+            };
+            canvas.set_draw_color(background);
+            canvas.fill_rect(FRect::new(key_x, key_y, KEY_SIZE, KEY_SIZE))?;
+            text::draw(
+                canvas,
+                key_x + (KEY_SIZE - text::GLYPH_WIDTH as f32 * scale) / 2.,
+                key_y + scale,
+                scale,
+                &format!("{key:X}"),
+                foreground,
+            )?;
+        }
+    }
+
+    Ok(())
+}
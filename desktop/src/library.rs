@@ -0,0 +1,204 @@
+use crate::{Error, database, gamepad, palette::Colours, recent, rotation::Rotation, text};
+use sdl3::{
+    EventPump, GamepadSubsystem,
+    event::Event,
+    gamepad::{Button, Gamepad},
+    keyboard::Scancode,
+    rect::FRect,
+    render::Canvas,
+    video::Window,
+};
+use std::{
+    path::{Path, PathBuf},
+    thread::sleep,
+    time::Duration,
+};
+
+/// File extensions of CHIP-8 and SuperChip ROMs
+pub const EXTENSIONS: [&str; 3] = ["ch8", "sc8", "c8"];
+
+/// Height of a line of text, including spacing
+const LINE_HEIGHT: usize = text::GLYPH_HEIGHT + 2;
+
+/// A ROM listed in the library
+pub struct Rom {
+    pub path: PathBuf,
+    pub title: String,
+}
+
+impl Rom {
+    /// Titled from the ROM database, or after its file if it isn't known
+    fn new(path: PathBuf, database: &[database::Entry]) -> Self {
+        let title = std::fs::read(&path)
+            .ok()
+            .and_then(|program| Some(database::find(database, &program)?.title.clone()))
+            .unwrap_or_else(|| title(&path));
+        Self { path, title }
+    }
+}
+
+/// Recently opened ROMs, then the rest of the ROMs in the directories sorted by title
+pub fn load(directories: &[PathBuf]) -> Result<Vec<Rom>, Error> {
+    let database = database::load()?;
+    let recent = recent::load();
+
+    let mut library = scan(directories)
+        .into_iter()
+        .filter(|rom| {
+            let path = std::fs::canonicalize(rom).unwrap_or_else(|_| rom.clone());
+            !recent.contains(&path)
+        })
+        .map(|rom| Rom::new(rom, &database))
+        .collect::<Vec<_>>();
+    library.sort_by_key(|rom| rom.title.to_ascii_lowercase());
+
+    Ok(recent
+        .into_iter()
+        .map(|rom| Rom::new(rom, &database))
+        .chain(library)
+        .collect())
+}
+
+/// Find every ROM in the directories and their subdirectories, sorted by name
+///
+/// Links to directories aren't followed, so a link back up the tree can't make the scan endless.
+pub fn scan(directories: &[PathBuf]) -> Vec<PathBuf> {
+    fn visit(directory: &Path, roms: &mut Vec<PathBuf>) {
+        // Unreadable directories are skipped rather than stopping the scan
+        let Ok(entries) = std::fs::read_dir(directory) else {
+            return;
+        };
+
+        for entry in entries.filter_map(Result::ok) {
+            let path = entry.path();
+            // The type of the entry itself, not of what a link points to
+            if entry.file_type().is_ok_and(|file_type| file_type.is_dir()) {
+                visit(&path, roms);
+            } else if path.is_file()
+                && path
+                    .extension()
+                    .and_then(|extension| extension.to_str())
+                    .is_some_and(|extension| {
+                        EXTENSIONS.contains(&extension.to_ascii_lowercase().as_str())
+                    })
+            {
+                roms.push(path);
+            }
+        }
+    }
+
+    let mut roms = Vec::new();
+    for directory in directories {
+        visit(directory, &mut roms);
+    }
+    roms.sort_by_key(|rom| title(rom).to_ascii_lowercase());
+    roms
+}
+
+/// The title shown for a ROM that isn't in the ROM database
+pub fn title(rom: &Path) -> String {
+    rom.file_stem()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// Let the user pick a ROM file with the platform's file chooser
+pub fn pick_file() -> Option<PathBuf> {
+    rfd::FileDialog::new()
+        .set_title("Open a CHIP-8 ROM")
+        .add_filter("CHIP-8 ROMs", &EXTENSIONS)
+        .add_filter("All files", &["*"])
+        .pick_file()
+}
+
+/// Let the user pick a ROM with the arrow keys and Enter, or O for the file chooser,
+/// returning `None` if they quit
+///
+/// A gamepad's d-pad and shoulder buttons move through the list, and South or Start picks a ROM.
+/// Gamepads connected while it's shown are added to `gamepads`.
+pub fn choose(
+    roms: &[Rom],
+    event_pump: &mut EventPump,
+    gamepad_subsystem: &GamepadSubsystem,
+    gamepads: &mut Vec<Gamepad>,
+    canvas: &mut Canvas<Window>,
+    colours: Colours,
+    rotation: Rotation,
+) -> Result<Option<PathBuf>, Error> {
+    let (width, height) = rotation.logical_size();
+    // One line is taken by the heading
+    let visible = height / LINE_HEIGHT - 1;
+    let max_chars = (width - 2) / (text::GLYPH_WIDTH + 1);
+
+    let mut selected = 0_usize;
+    loop {
+        for event in event_pump.poll_iter() {
+            match event {
+                Event::Quit { .. }
+                | Event::KeyDown {
+                    scancode: Some(Scancode::Escape),
+                    ..
+                } => return Ok(None),
+                Event::ControllerDeviceAdded { which, .. } => {
+                    gamepad::open(gamepad_subsystem, which, gamepads);
+                }
+                Event::ControllerDeviceRemoved { which, .. } => {
+                    gamepads.retain(|gamepad| gamepad.id() != which);
+                }
+                Event::ControllerButtonDown { button, .. } => match button {
+                    Button::DPadUp => selected = selected.saturating_sub(1),
+                    Button::DPadDown => selected = (selected + 1).min(roms.len() - 1),
+                    Button::LeftShoulder => selected = selected.saturating_sub(visible),
+                    Button::RightShoulder => selected = (selected + visible).min(roms.len() - 1),
+                    Button::South | Button::Start => return Ok(Some(roms[selected].path.clone())),
+                    _ => (),
+                },
+                Event::KeyDown {
+                    scancode: Some(scancode),
+                    ..
+                } => match scancode {
+                    Scancode::Up => selected = selected.saturating_sub(1),
+                    Scancode::Down => selected = (selected + 1).min(roms.len() - 1),
+                    Scancode::PageUp => selected = selected.saturating_sub(visible),
+                    Scancode::PageDown => selected = (selected + visible).min(roms.len() - 1),
+                    Scancode::Home => selected = 0,
+                    Scancode::End => selected = roms.len() - 1,
+                    Scancode::Return | Scancode::KpEnter => {
+                        return Ok(Some(roms[selected].path.clone()));
+                    }
+                    Scancode::O => {
+                        if let Some(rom) = pick_file() {
+                            return Ok(Some(rom));
+                        }
+                    }
+                    _ => (),
+                },
+                _ => (),
+            }
+        }
+
+        canvas.set_draw_color(colours.off);
+        canvas.clear();
+
+        let heading = format!("ROMS {}/{}", selected + 1, roms.len());
+        text::draw(canvas, 1., 1., 1., &heading, colours.on)?;
+
+        // Keep the selection in view
+        let first = selected.saturating_sub(visible - 1);
+        for (line, rom) in roms.iter().enumerate().skip(first).take(visible) {
+            let y = ((line - first + 1) * LINE_HEIGHT) as f32;
+            let title = rom.title.chars().take(max_chars).collect::<String>();
+            if line == selected {
+                canvas.set_draw_color(colours.on);
+                canvas.fill_rect(FRect::new(0., y, width as f32, LINE_HEIGHT as f32))?;
+                text::draw(canvas, 1., y + 1., 1., &title, colours.off)?;
+            } else {
+                text::draw(canvas, 1., y + 1., 1., &title, colours.on)?;
+            }
+        }
+
+        canvas.present();
+        sleep(Duration::from_secs(1) / 60);
+    }
+}
@@ -1,94 +1,248 @@
+mod audio;
+mod breakpoint;
+mod capture;
+mod cheat;
+mod cli;
+mod compare;
+mod config;
+mod crash;
+mod database;
+mod debug;
+mod debug_window;
+mod filter;
+mod gamepad;
+mod headless;
+mod keymap;
+mod keypad;
+mod library;
+mod monitor;
+mod movie;
+mod osd;
+mod pacing;
+mod palette;
+mod phosphor;
+mod playlist;
+mod quirk_menu;
+mod recent;
+mod remote;
+mod rewind;
+mod rotation;
+mod savestate;
+mod screen;
+mod search;
+mod text;
+mod tools;
+mod trace;
+mod video;
+mod watch;
+
+use clap::Parser;
 use parking_lot::Mutex;
-use rand::Rng;
-use rs_chip8_core::{DISPLAY_HEIGHT, DISPLAY_WIDTH, EmulationSystem, MachineState};
+use rand::{Rng, SeedableRng, rngs::StdRng};
+use rs_chip8_core::{EmulationSystem, MachineState, PROGRAM_ADDRESS, Quirks, RAM_SIZE, disasm};
 use sdl3::{
     event::{Event, WindowEvent},
-    keyboard::Scancode,
-    pixels::Color,
-    rect::Point,
+    gamepad::Button,
+    keyboard::{Mod, Scancode},
+    messagebox::{
+        ButtonData, ClickedButton, MessageBoxButtonFlag, MessageBoxFlag, show_message_box,
+        show_simple_message_box,
+    },
+    mouse::MouseButton,
+    rect::FRect,
+    video::WindowPos,
 };
 use std::{
     ffi::OsStr,
-    path::PathBuf,
+    path::{Path, PathBuf},
     process::ExitCode,
     thread::sleep,
     time::{Duration, Instant},
 };
 
-const OFF_COLOUR: Color = Color::RGB(0x8f, 0x91, 0x85);
-const ON_COLOUR: Color = Color::RGB(0x11, 0x1d, 0x2b);
-
-const INSTR_PER_FRAME: u32 = 10;
-
-const KEYMAP: [Scancode; 16] = [
-    Scancode::X,
-    Scancode::_1,
-    Scancode::_2,
-    Scancode::_3,
-    Scancode::Q,
-    Scancode::W,
-    Scancode::E,
-    Scancode::A,
-    Scancode::S,
-    Scancode::D,
-    Scancode::Z,
-    Scancode::C,
-    Scancode::_4,
-    Scancode::R,
-    Scancode::F,
-    Scancode::V,
-];
+/// Execution to perform while paused
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Step {
+    Frame,
+    Instruction,
+}
+
+/// How many times the keyboard is checked during each frame
+const POLLS_PER_FRAME: u32 = 8;
+
+/// How often events are checked while paused in the background
+const BACKGROUND_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Frames and instructions run since the speed was last measured
+#[derive(Debug, Default)]
+struct SpeedCounter {
+    frames: u32,
+    instructions: u64,
+}
+
+/// Fraction of normal speed to run at
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SlowMotion {
+    Off,
+    Half,
+    Quarter,
+}
+
+impl SlowMotion {
+    /// Only every n-th frame is emulated
+    fn divisor(self) -> u32 {
+        match self {
+            SlowMotion::Off => 1,
+            SlowMotion::Half => 2,
+            SlowMotion::Quarter => 4,
+        }
+    }
+
+    fn next(self) -> Self {
+        match self {
+            SlowMotion::Off => SlowMotion::Half,
+            SlowMotion::Half => SlowMotion::Quarter,
+            SlowMotion::Quarter => SlowMotion::Off,
+        }
+    }
+}
 
 #[derive(Debug, thiserror::Error)]
 #[error(transparent)]
 enum Error {
     Sdl(#[from] sdl3::Error),
     Core(#[from] rs_chip8_core::Error),
-    #[error("One argument required")]
-    Argument,
+    #[error(
+        "{error}\n\nAt 0x{address:03X} executing {opcode:04X} ({instruction}){}",
+        .report.as_ref().map_or(String::new(), |path| {
+            format!("\n\nDetails were saved to {}", path.display())
+        })
+    )]
+    Emulation {
+        error: rs_chip8_core::Error,
+        address: u16,
+        opcode: u16,
+        instruction: String,
+        /// Where the crash report was saved
+        report: Option<PathBuf>,
+    },
+    #[error("Failed to read {}: {source}", path.display())]
+    ReadRom {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    #[error("{} is too big, at {len} bytes when {max_len} fit in memory", path.display())]
+    RomTooBig {
+        path: PathBuf,
+        len: usize,
+        max_len: usize,
+    },
     IO(#[from] std::io::Error),
+    Config(#[from] toml::de::Error),
+    ConfigSave(#[from] toml::ser::Error),
+    Png(#[from] png::EncodingError),
+    PngDecoding(#[from] png::DecodingError),
+    Gif(#[from] gif::EncodingError),
+    #[error("Invalid ROM database {}: {source}", path.display())]
+    Database {
+        path: PathBuf,
+        source: toml::de::Error,
+    },
+    #[error("Invalid CHIP-8 community database: {0}")]
+    Import(String),
+    #[error("Invalid input script: {0}")]
+    Input(String),
+    #[error("Invalid playlist: {0}")]
+    Playlist(String),
+    #[error("Invalid keymap: {0}")]
+    Keymap(String),
+    #[error("Invalid gamepad mapping: {0}")]
+    Gamepad(String),
 }
 
 fn main() -> ExitCode {
-    match actual_main() {
+    let arguments = cli::Arguments::parse();
+    let cli = match arguments.command {
+        None => arguments.run,
+        Some(cli::Command::Run(cli)) => *cli,
+        Some(command) => {
+            return match tools::run(command) {
+                Ok(()) => ExitCode::SUCCESS,
+                Err(err) => {
+                    eprintln!("Error: {err}");
+                    ExitCode::FAILURE
+                }
+            };
+        }
+    };
+    let headless = cli.headless || cli.bench.is_some();
+    match actual_main(cli) {
         Ok(()) => ExitCode::SUCCESS,
+        // SuperChip programs can end themselves with 00FD
+        Err(Error::Emulation {
+            error: rs_chip8_core::Error::ProgramExited,
+            ..
+        }) => ExitCode::SUCCESS,
         Err(err) => {
             eprintln!("Error: {err}");
+            // Most people won't see stderr when starting the emulator from a file manager
+            if !headless
+                && let Err(err) = show_simple_message_box(
+                    MessageBoxFlag::ERROR,
+                    "rs_chip8 error",
+                    &err.to_string(),
+                    None,
+                )
+            {
+                eprintln!("Failed to show the error: {err}");
+            }
             ExitCode::FAILURE
         }
     }
 }
 
-fn actual_main() -> Result<(), Error> {
-    // Open and read the program
-    let rom_filepath = PathBuf::from(std::env::args().nth(1).ok_or(Error::Argument)?);
-    let program = std::fs::read(&rom_filepath)?;
+fn actual_main(cli: cli::Cli) -> Result<(), Error> {
+    let config = config::Config::load(cli.config.as_deref())?;
+    if cli.headless {
+        return headless::run(&cli, &config);
+    }
+    if let Some(seconds) = cli.bench {
+        return headless::bench(&cli, &config, Duration::from_secs(seconds));
+    }
 
-    // Initialise the machine state
-    // Choose the system to emulate based on the ROM file extension
-    let mut machine_state =
-        MachineState::new(match rom_filepath.extension().and_then(OsStr::to_str) {
-            Some("ch8") => EmulationSystem::Chip8,
-            Some("sc8") => EmulationSystem::SuperChip,
-            _ => EmulationSystem::default(),
-        });
-    machine_state.load_default_font();
-    machine_state.load_program(&program);
-    let machine_state = Mutex::new(machine_state);
+    let scale = cli.scale.unwrap_or(config.window.scale);
+    let off_colour = cli.off_colour.or(config.colours.off);
+    let on_colour = cli.on_colour.or(config.colours.on);
+    // The colours used until the ROM's own settings are loaded
+    let colours = {
+        let (themes, theme) = themes(cli.palette.unwrap_or(config.palette), off_colour, on_colour);
+        Mutex::new(themes[theme])
+    };
 
     // Initialise SDL
     let sdl_context = sdl3::init()?;
     let video_subsystem = sdl_context.video()?;
-    let _audio_subsystem = sdl_context.audio()?;
+    let audio_subsystem = sdl_context.audio()?;
     let event_subsystem = sdl_context.event()?;
-    let mut event_pump = sdl_context.event_pump()?;
+    let gamepad_subsystem = sdl_context.gamepad()?;
+    let text_input = video_subsystem.text_input();
+    let event_pump = sdl_context.event_pump()?;
 
-    let window = match video_subsystem
-        .window("rs_chip8", 1280, 640)
+    let rotation = Mutex::new(cli.rotation.unwrap_or(config.display.rotation));
+    let (width, height) = rotation.lock().logical_size();
+    let mut window_builder =
+        video_subsystem.window("rs_chip8", width as u32 * scale, height as u32 * scale);
+    // Render at the display's full resolution, rather than stretching a smaller image over it
+    window_builder
         .position_centered()
         .resizable()
-        .build()
-    {
+        .high_pixel_density();
+    let mut fullscreen = cli.fullscreen || config.window.fullscreen;
+    if fullscreen {
+        window_builder.fullscreen();
+    }
+
+    let mut window = match window_builder.build() {
         Ok(window) => window,
         Err(err) => match err {
             sdl3::video::WindowBuildError::SdlError(err) => return Err(err.into()),
@@ -99,122 +253,2022 @@ fn actual_main() -> Result<(), Error> {
         },
     };
 
-    // Set logical resolution to the DISPLAY_WIDTH x DISPLAY_HEIGHT
-    let canvas = Mutex::new(window.into_canvas());
-    if let Err(err) = canvas.lock().set_logical_size(
-        DISPLAY_WIDTH as u32,
-        DISPLAY_HEIGHT as u32,
-        sdl3::sys::render::SDL_RendererLogicalPresentation::LETTERBOX,
-    ) {
-        if let sdl3::IntegerOrSdlError::SdlError(err) = err {
-            return Err(err.into());
-        } else {
-            panic!("Expected display height and width to be valid");
+    // Window sizes are in pixels on some platforms, so make up for the display being scaled,
+    // otherwise the window would be tiny on HiDPI screens
+    let content_scale = window.display_scale() / window.pixel_density();
+    if !fullscreen && content_scale > 1. {
+        let (window_width, window_height) = window.size();
+        match window.set_size(
+            (window_width as f32 * content_scale) as u32,
+            (window_height as f32 * content_scale) as u32,
+        ) {
+            Ok(()) => window.set_position(WindowPos::Centered, WindowPos::Centered),
+            Err(sdl3::IntegerOrSdlError::SdlError(err)) => return Err(err.into()),
+            Err(err) => panic!("Expected the scaled window size to be valid, but {err}"),
         }
     }
 
-    // Time period of 60 Hz
-    let time_period = Duration::from_secs(1) / 60;
-    let prev_tick = Mutex::new(Instant::now());
+    // Presenting waits for the display to refresh, which then paces emulation
+    let vsync = cli.vsync || config.window.vsync;
+    if vsync {
+        sdl3::hint::set("SDL_RENDER_VSYNC", "1");
+    }
+    let canvas = Mutex::new(window.into_canvas());
+    let integer_scale = cli.integer_scale || config.window.integer_scale;
+    set_logical_size(&mut canvas.lock(), integer_scale, *rotation.lock())?;
 
-    let held_keys = Mutex::new(0_u16);
-    let rng = Mutex::new(rand::rng());
+    // Pumped partway through frames for new key presses, unless the main loop is polling it
+    let event_pump = Mutex::new(event_pump);
+    let monitor = cli.monitor.then(|| {
+        println!("Monitor ready, type help for the commands");
+        monitor::spawn()
+    });
+    let remote = match cli.remote {
+        Some(port) => {
+            let remote = remote::listen(port)?;
+            println!("Listening for remote control on port {port}");
+            Some(remote)
+        }
+        None => None,
+    };
+    let mut playlist = cli
+        .playlist
+        .as_deref()
+        .map(playlist::Playlist::load)
+        .transpose()?;
+    let attract_idle = Duration::from_secs(config.playlist.idle);
+    // Gamepads are only used while they're open, SDL reports the connected ones at startup,
+    // so they're kept open from one ROM to the next
+    let mut gamepads = Vec::new();
 
+    // SDL only lets an event type be registered once, so this can't be done for each ROM
     struct ExecutionErrorEvent(Error);
     event_subsystem.register_custom_event::<ExecutionErrorEvent>()?;
 
-    let execution_loop = || -> Result<(), Error> {
-        let mut machine_state = machine_state.lock();
-        let held_keys = held_keys.lock();
-        let mut rng = rng.lock();
+    // Each ROM in a playlist is run in turn, otherwise the only ROM is run until the emulator is closed
+    loop {
+        // Open and read the program
+        // Without a ROM, let the user pick one from their library, or from a file chooser
+        let entry = playlist.as_mut().map(playlist::Playlist::next);
+        let rom = match entry
+            .as_ref()
+            .map(|entry| entry.rom.clone())
+            .or_else(|| cli.rom.clone())
+        {
+            Some(rom) => Some(rom),
+            None => {
+                let roms = library::load(&config.library.directories)?;
+                if roms.is_empty() {
+                    library::pick_file()
+                } else {
+                    library::choose(
+                        &roms,
+                        &mut event_pump.lock(),
+                        &gamepad_subsystem,
+                        &mut gamepads,
+                        &mut canvas.lock(),
+                        *colours.lock(),
+                        *rotation.lock(),
+                    )?
+                }
+            }
+        };
+        // The user cancelled, so there's nothing to run
+        let Some(rom) = rom else {
+            return Ok(());
+        };
+        let program = std::fs::read(&rom).map_err(|source| Error::ReadRom {
+            path: rom.clone(),
+            source,
+        })?;
+
+        // Settings for this ROM take priority over the config file, but not the command line
+        let rom_config_path = config::RomConfig::path(&program);
+        let rom_config = match &rom_config_path {
+            Some(path) => config::RomConfig::load(path)?,
+            None => config::RomConfig::default(),
+        };
+        // Known ROMs get the settings they were written for, unless the user chose their own
+        let database_entry = database::lookup(&program)?;
 
-        machine_state.tick_timer();
+        let rom_title = match &database_entry {
+            Some(entry) => entry.title.clone(),
+            None => library::title(&rom),
+        };
+        canvas
+            .lock()
+            .window_mut()
+            .set_title(&format!("{rom_title} - rs_chip8"))
+            .expect("Title contained a null byte");
 
-        if machine_state.sound_timer > 0 {
-            // TODO: make sound
-        } else {
-            // TODO: stop the sound
+        let ipf = Mutex::new(
+            cli.ipf
+                .or(rom_config.ipf)
+                .or(database_entry.as_ref().and_then(|entry| entry.ipf))
+                .unwrap_or(config.ipf),
+        );
+        let palette = cli.palette.or(rom_config.palette);
+        let (off_colour, on_colour) = match &database_entry {
+            // The ROM's colours replace the configured ones, but not a palette chosen for it
+            Some(entry)
+                if palette.is_none() && cli.off_colour.is_none() && cli.on_colour.is_none() =>
+            {
+                (
+                    entry.colours.off.or(off_colour),
+                    entry.colours.on.or(on_colour),
+                )
+            }
+            _ => (off_colour, on_colour),
+        };
+        let (themes, mut theme) = themes(palette.unwrap_or(config.palette), off_colour, on_colour);
+        let (initial_ipf, initial_theme) = (*ipf.lock(), theme);
+        *colours.lock() = themes[theme];
+        let keymap = keymap::Keymap::from_preset(
+            cli.keymap
+                .or(rom_config.keymap.preset)
+                .or(config.keymap.preset)
+                .unwrap_or_default(),
+        )
+        .with_overrides(&config.keymap.keys)?
+        .with_overrides(&rom_config.keymap.keys)?;
+
+        let mut gamepad_map =
+            gamepad::GamepadMap::default().with_overrides(&config.gamepad.buttons)?;
+        if let Some(name) = rom_config
+            .gamepad
+            .profile
+            .as_ref()
+            .or(config.gamepad.profile.as_ref())
+        {
+            let profile = config
+                .gamepad_profiles
+                .get(name)
+                .ok_or_else(|| Error::Gamepad(format!("there is no profile named {name:?}")))?;
+            gamepad_map = gamepad_map.with_overrides(profile)?;
         }
+        let mut gamepad_map = gamepad_map.with_overrides(&rom_config.gamepad.buttons)?;
 
-        for _ in 0..=INSTR_PER_FRAME {
-            machine_state.tick(|| *held_keys, || rng.random())?;
+        let machine_state = load_machine_state(
+            &rom,
+            &program,
+            cli.system,
+            &config,
+            &rom_config,
+            database_entry.as_ref(),
+        )?;
+        // A playlist's ROMs are chosen for the user, so only ones they open are remembered
+        if playlist.is_none()
+            && let Err(err) = recent::add(&rom)
+        {
+            eprintln!("Failed to save the recent list: {err}");
         }
+        // Kept for resetting
+        let initial_machine_state = Mutex::new(machine_state.clone());
+        let initial_quirks = machine_state.quirks();
+        let mut machine_state = machine_state;
+        let data_dir = config::rom_data_dir(&rom, &program);
+        let save_states = savestate::SaveStates::new(data_dir.clone());
+        // Movies have to start from the ROM being loaded
+        let resume = config.resume
+            && playlist.is_none()
+            && cli.record_movie.is_none()
+            && cli.play_movie.is_none();
+        if resume && save_states.has_session() && ask_to_resume(&rom_title, canvas.lock().window())
+        {
+            match save_states.load_session(&mut machine_state) {
+                Ok(()) => println!("Resumed the last session"),
+                Err(err) => eprintln!("Failed to resume the last session: {err}"),
+            }
+        }
+        let machine_state = Mutex::new(machine_state);
+        let capture = capture::Capture::new(config.capture.directory.as_deref(), &rom);
 
-        let mut canvas = canvas.lock();
+        let texture_creator = canvas.lock().texture_creator();
+        let screen = Mutex::new(screen::Screen::new(&texture_creator)?);
 
-        canvas.set_draw_color(OFF_COLOUR);
-        canvas.clear();
+        let beeper = if config.audio.enabled {
+            Some(audio::Beeper::new(&audio_subsystem, &config.audio)?)
+        } else {
+            None
+        };
+        let beeper_switch = beeper
+            .as_ref()
+            .map(audio::Beeper::switch)
+            .unwrap_or_default();
 
-        canvas.set_draw_color(ON_COLOUR);
-        for y in 0..DISPLAY_HEIGHT {
-            for x in 0..DISPLAY_WIDTH {
-                if machine_state.display_buffer[x][y] {
-                    canvas.draw_point(Point::new(x as i32, y as i32))?;
+        // Time period of 60 Hz
+        let time_period = Duration::from_secs(1) / 60;
+        let prev_tick = Mutex::new(Instant::now());
+
+        let audio_muted = Mutex::new(false);
+        let held_keys = Mutex::new(0_u16);
+        let rng = Mutex::new(StdRng::seed_from_u64(rand::random()));
+        // Movies set the IPF and random numbers, so runs replay the same
+        // Playlists play the ROM's demo, if it has one
+        let play_movie = cli
+            .play_movie
+            .clone()
+            .or_else(|| entry.as_ref().and_then(|entry| entry.movie.clone()));
+        let movie = Mutex::new(match (&cli.record_movie, &play_movie) {
+            (Some(path), _) => Some(movie::Session::record(
+                movie::Movie::new(&program, rand::random(), *ipf.lock()),
+                path.clone(),
+            )),
+            (None, Some(path)) => {
+                let recorded = movie::Movie::load(path)?;
+                if !recorded.matches(&program) {
+                    eprintln!(
+                        "The movie was recorded with a different ROM, so it may not play back the same"
+                    );
                 }
+                *ipf.lock() = recorded.ipf;
+                Some(movie::Session::play(&recorded)?)
+            }
+            (None, None) => None,
+        });
+        // A second copy of the game with other quirks or speed, to see what they change
+        let comparison = Mutex::new(match &cli.compare {
+            Some(settings) => Some(compare::Comparison::open(
+                &video_subsystem,
+                canvas.lock().window(),
+                &rom_title,
+                settings,
+                &machine_state.lock(),
+            )?),
+            None => None,
+        });
+        // Restart the program, keeping any quirks changed since it was loaded
+        let reset = |machine_state: &mut MachineState| {
+            let quirks = machine_state.quirks();
+            *machine_state = initial_machine_state.lock().clone();
+            machine_state.set_quirks(quirks);
+            if let Some(comparison) = comparison.lock().as_mut() {
+                comparison.reset(machine_state);
             }
+        };
+        // Starting paused shows the debug overlay, so breakpoints can be set before anything runs
+        let paused = Mutex::new(cli.start_paused);
+        // Whether one of the emulator's windows has focus, it's throttled in the background
+        let focused = Mutex::new(true);
+        let step_request = Mutex::new(None);
+        let rewinding = Mutex::new(false);
+        let rewind = Mutex::new(rewind::Rewind::new());
+        let fast_forwarding = Mutex::new(false);
+        let slow_motion = Mutex::new(SlowMotion::Off);
+        let frame_count = Mutex::new(0_u32);
+        let osd = Mutex::new(osd::Osd::default());
+        if let Some(entry) = &database_entry {
+            let message = format!(
+                "{} ({:?}, IPF {})",
+                entry.title,
+                initial_machine_state.lock().system(),
+                ipf.lock()
+            );
+            println!("Recognised {message}");
+            osd.lock().show_for(&message, Duration::from_secs(3));
         }
+        // Set when the whole screen has to be drawn again, even if the display didn't change
+        let redraw = Mutex::new(true);
+        let gif_recorder = Mutex::new(None::<capture::GifRecorder>);
+        let trace = Mutex::new(match &cli.trace {
+            Some(path) => Some(trace::Trace::start(path.clone())?),
+            None => None,
+        });
+        let debug_overlay = Mutex::new(cli.start_paused);
+        let debug_window = Mutex::new(None::<debug_window::DebugWindow>);
+        // The debugger takes keys while it's shown on the display or in its own window
+        let debugging = || *debug_overlay.lock() || debug_window.lock().is_some();
+        let pacing = Mutex::new(pacing::Pacing::default());
+        let pacing_overlay = Mutex::new(false);
+        let search_panel = Mutex::new(false);
+        let quirk_menu = Mutex::new(None::<quirk_menu::QuirkMenu>);
+        // An error that paused emulation, shown until the user chooses how to carry on
+        let fault = Mutex::new(None::<Error>);
+        let speed_counter = Mutex::new(SpeedCounter::default());
+        let keypad_overlay = Mutex::new(cli.keypad || config.display.keypad);
+        let sound_indicator = cli.sound_indicator || config.display.sound_indicator;
+        let pixel_grid = Mutex::new(cli.grid || config.display.grid);
+        // Whether the sound indicator was drawn last time, to redraw when it changes
+        let sound_shown = Mutex::new(false);
+        // Index into the keypad of the key waiting for a gamepad button, while remapping
+        let gamepad_remap = Mutex::new(None::<usize>);
+        let cheats = Mutex::new(rom_config.cheats.clone());
+        let rom_watcher =
+            Mutex::new((cli.watch || config.watch).then(|| watch::RomWatcher::new(&rom)));
+        let debugger = Mutex::new(debug::Debugger::default());
+        let video_recorder = Mutex::new(match &cli.record {
+            Some(path) => Some(video::VideoRecorder::start(
+                path,
+                cli.record_args.as_deref().unwrap_or(video::DEFAULT_ARGS),
+                &config.audio,
+            )?),
+            None => None,
+        });
+        let finish_recordings = || {
+            if let Some(recorder) = gif_recorder.lock().take() {
+                finish_gif(recorder);
+            }
+            if let Some(recorder) = video_recorder.lock().take() {
+                match recorder.finish() {
+                    Ok(path) => println!("Saved video to {}", path.display()),
+                    Err(err) => eprintln!("Failed to save video: {err}"),
+                }
+            }
+            if let Some(session) = movie.lock().take() {
+                match session.finish() {
+                    Ok(Some(path)) => println!("Saved movie to {}", path.display()),
+                    Ok(None) => {}
+                    Err(err) => eprintln!("Failed to save movie: {err}"),
+                }
+            }
+            let machine_state = machine_state.lock();
+            if let Some(trace) = trace.lock().take() {
+                finish_trace(trace, &machine_state);
+            }
+        };
+        let filter = cli.filter.unwrap_or(config.display.filter);
+        let phosphor = (cli.phosphor || config.display.phosphor)
+            .then(|| Mutex::new(phosphor::Phosphor::new()));
 
-        canvas.present();
+        // Whether the event watch should apply key presses to the keypad
+        let polling_keys = Mutex::new(false);
 
-        Ok(())
-    };
+        // Emulate `frames` 60 Hz frames, which may be none to only redraw, then draw the display
+        let execution_loop = |frames: u32| -> Result<(), Error> {
+            let started = Instant::now();
+            let mut machine_state = machine_state.lock();
+            let pause = || *paused.lock() = true;
+            // Illegal instructions pause with the error shown, and programs exiting pause
+            // until they're reset, instead of ending emulation
+            let show_fault = |err| match err {
+                Error::Emulation {
+                    error:
+                        rs_chip8_core::Error::IllegalInstruction(_)
+                        | rs_chip8_core::Error::ProgramExited,
+                    ..
+                } => {
+                    *fault.lock() = Some(err);
+                    pause();
+                    Ok(())
+                }
+                err => Err(err),
+            };
+            let paused = *paused.lock();
 
-    let _window_update_eventwatch = event_subsystem.add_event_watch(|event| {
-        if let Event::Window {
-            win_event: WindowEvent::Exposed,
-            ..
-        } = event
-        {
-            let delta = prev_tick.lock().elapsed();
-            if delta > time_period {
-                *prev_tick.lock() += time_period;
-                if let Err(err) = execution_loop() {
-                    event_subsystem
-                        .push_custom_event(ExecutionErrorEvent(err))
-                        .expect("Custom event was not registered");
+            let rewinding = !paused && *rewinding.lock();
+            let fast_forwarding = !paused && *fast_forwarding.lock();
+
+            // Slow motion skips frames, which slows down the timers along with the instructions
+            let mut frame_count = frame_count.lock();
+            let divisor = slow_motion.lock().divisor();
+            let due = (0..frames)
+                .filter(|_| {
+                    *frame_count = frame_count.wrapping_add(1);
+                    fast_forwarding || *frame_count % divisor == 0
+                })
+                .count() as u32;
+            if paused {
+                pacing.lock().pause();
+            } else if frames > 0 {
+                pacing.lock().frame(started, time_period);
+            }
+            let step = if paused {
+                step_request.lock().take()
+            } else if due > 0 {
+                Some(Step::Frame)
+            } else {
+                None
+            };
+
+            if rewinding {
+                osd.lock().show("Rewinding");
+                if rewind.lock().pop(&mut machine_state)
+                    && let Some(session) = movie.lock().as_mut()
+                {
+                    session.rewind();
+                }
+            } else if let Some(step) = step {
+                let mut rng = rng.lock();
+
+                match step {
+                    Step::Frame => {
+                        // Without a cap, the speed is instead limited by how often the main loop runs
+                        let frames = if fast_forwarding {
+                            due * config.fast_forward.max(1)
+                        } else {
+                            due
+                        };
+                        let ipf = *ipf.lock();
+                        let mut debugger = debugger.lock();
+                        // Keys pressed partway through a frame reach the instructions after,
+                        // instead of waiting for the next frame, unless a menu is taking them
+                        let mut movie = movie.lock();
+                        let mut event_pump = if debugger.breakpoint_prompt.is_none()
+                            && movie.is_none()
+                            && quirk_menu.lock().is_none()
+                            && gamepad_remap.lock().is_none()
+                        {
+                            event_pump.try_lock()
+                        } else {
+                            None
+                        };
+                        let batch = (ipf / POLLS_PER_FRAME).max(1);
+                        let breakpoints = &mut debugger.breakpoints;
+                        let mut comparison = comparison.lock();
+                        let mut trace = trace.lock();
+                        for _ in 0..frames {
+                            rewind.lock().push(&machine_state);
+                            if movie.as_ref().is_some_and(movie::Session::finished) {
+                                println!("The movie has finished");
+                                *movie = None;
+                                *held_keys.lock() = 0;
+                            }
+                            if let Some(session) = movie.as_mut() {
+                                let (keys, frame_rng) = session.frame(*held_keys.lock());
+                                *held_keys.lock() = keys;
+                                *rng = frame_rng;
+                            }
+                            // The comparison runs the frame with the same keys and random numbers
+                            let (start_keys, start_rng) = (*held_keys.lock(), rng.clone());
+                            let mut executed = 0;
+                            let hit = match machine_state.run_frame_until(
+                                ipf,
+                                || *held_keys.lock(),
+                                || rng.random(),
+                                |machine_state| {
+                                    executed += 1;
+                                    if executed % batch == 0
+                                        && let Some(event_pump) = &mut event_pump
+                                    {
+                                        *polling_keys.lock() = true;
+                                        event_pump.pump_events();
+                                        *polling_keys.lock() = false;
+                                    }
+                                    let hit = breakpoints.check(machine_state);
+                                    if !hit {
+                                        trace_instruction(&mut trace, machine_state);
+                                    }
+                                    hit
+                                },
+                            ) {
+                                Ok(hit) => hit,
+                                Err(err) => {
+                                    // The instruction that failed is counted, but didn't run
+                                    speed_counter.lock().instructions += executed as u64 - 1;
+                                    show_fault(emulation_error(
+                                        &rom,
+                                        &data_dir,
+                                        &machine_state,
+                                        err,
+                                    ))?;
+                                    break;
+                                }
+                            };
+                            if let Some(comparison) = comparison.as_mut() {
+                                comparison.run_frame(ipf, start_keys, start_rng);
+                            }
+                            cheat::apply(&cheats.lock(), &mut machine_state);
+                            let mut speed_counter = speed_counter.lock();
+                            speed_counter.frames += 1;
+                            // A breakpoint stops the frame before the instruction it's counted for
+                            speed_counter.instructions += (executed - hit as u32) as u64;
+                            if hit {
+                                println!(
+                                    "Hit breakpoint at 0x{:03X}",
+                                    machine_state.program_counter()
+                                );
+                                pause();
+                                break;
+                            }
+                        }
+                    }
+                    Step::Instruction => {
+                        if let Some(comparison) = comparison.lock().as_mut() {
+                            comparison.step(*held_keys.lock(), rng.clone());
+                        }
+                        trace_instruction(&mut trace.lock(), &machine_state);
+                        if let Err(err) = machine_state.tick(|| *held_keys.lock(), || rng.random())
+                        {
+                            show_fault(emulation_error(&rom, &data_dir, &machine_state, err))?;
+                        }
+                        speed_counter.lock().instructions += 1;
+                    }
                 }
             }
-        }
-    });
 
-    loop {
-        let delta = prev_tick.lock().elapsed();
-        if delta < time_period {
-            if time_period - delta > Duration::from_millis(2) {
-                sleep(time_period - delta - Duration::from_millis(1));
+            if rewinding || step == Some(Step::Frame) {
+                let mut gif_recorder = gif_recorder.lock();
+                if let Some(recorder) = gif_recorder.as_mut()
+                    && let Err(err) = recorder.frame(machine_state.display())
+                {
+                    eprintln!("Stopped recording GIF: {err}");
+                    *gif_recorder = None;
+                }
+
+                let mut video_recorder = video_recorder.lock();
+                if let Some(recorder) = video_recorder.as_mut()
+                    && let Err(err) = recorder.frame(
+                        machine_state.display(),
+                        *colours.lock(),
+                        machine_state.sound_timer() > 0,
+                    )
+                {
+                    eprintln!("Stopped recording video: {err}");
+                    *video_recorder = None;
+                }
+            }
+
+            let muted = *audio_muted.lock()
+                || paused
+                || rewinding
+                || (fast_forwarding && config.audio.mute_fast_forward);
+            beeper_switch.set(!muted && machine_state.sound_timer() > 0);
+
+            let mut osd = osd.lock();
+            if osd.expire() {
+                *redraw.lock() = true;
+            }
+
+            pacing.lock().emulation.record(started.elapsed());
+            let drawing_started = Instant::now();
+
+            // Skip drawing entirely if nothing on screen could have changed
+            let dirty_rows = machine_state.take_dirty_rows();
+            let redraw = std::mem::take(&mut *redraw.lock()) || rewinding || phosphor.is_some();
+            // The debugger's window is kept up to date even when the game's is left as it is
+            let mut debug_window = debug_window.lock();
+            if let Some(debug_window) = debug_window.as_mut() {
+                debug_window.draw(&machine_state, *colours.lock(), paused, &debugger.lock())?;
+            }
+            let debug_overlay = *debug_overlay.lock() && debug_window.is_none();
+            if let Some(comparison) = comparison.lock().as_mut() {
+                comparison.draw(*colours.lock())?;
+            }
+            let search_panel = *search_panel.lock();
+            let quirk_menu = *quirk_menu.lock();
+            let gamepad_remap = *gamepad_remap.lock();
+            let pacing_overlay = *pacing_overlay.lock();
+            let sound = sound_indicator && machine_state.sound_timer() > 0;
+            let sound_changed = std::mem::replace(&mut *sound_shown.lock(), sound) != sound;
+            // With vsync, presenting every time is what keeps the main loop in time
+            if dirty_rows == 0
+                && !vsync
+                && !redraw
+                && (!paused || !*focused.lock())
+                && !osd.is_shown()
+                && !debug_overlay
+                && !search_panel
+                && quirk_menu.is_none()
+                && gamepad_remap.is_none()
+                && !pacing_overlay
+                && !sound_changed
+            {
+                return Ok(());
+            }
+
+            let mut canvas = canvas.lock();
+            let colours = *colours.lock();
+
+            canvas.set_draw_color(colours.off);
+            canvas.clear();
+
+            let mut screen = screen.lock();
+            let rows = if redraw { u64::MAX } else { dirty_rows };
+            if let Some(phosphor) = &phosphor {
+                let mut phosphor = phosphor.lock();
+                // Keep the image still while paused
+                if rewinding || step == Some(Step::Frame) {
+                    phosphor.update(machine_state.display());
+                }
+
+                screen.update(rows, |x, y| phosphor.colour(x, y, colours))?;
+            } else {
+                let display = machine_state.display();
+                screen.update(rows, |x, y| {
+                    if display.pixel(x, y) {
+                        colours.on
+                    } else {
+                        colours.off
+                    }
+                })?;
+            }
+            let rotation = *rotation.lock();
+            let (width, height) = rotation.logical_size();
+            screen.draw(&mut canvas, rotation)?;
+
+            filter.draw(&mut canvas, (width, height))?;
+
+            if *pixel_grid.lock() {
+                let pixel_size = if machine_state.display().is_high_res() {
+                    1
+                } else {
+                    2
+                };
+                draw_pixel_grid(&mut canvas, (width, height), pixel_size, colours)?;
+            }
+
+            if sound {
+                draw_sound_indicator(&mut canvas, (width, height), colours)?;
+            }
+
+            if let Some(index) = gamepad_remap {
+                let key = rotation::KEYPAD.as_flattened()[index];
+                keypad::draw(&mut canvas, 0b1 << key, colours, (width, height))?;
+                for (i, line) in [
+                    format!("PRESS A BUTTON FOR {key:X}"),
+                    String::from("SPACE: SKIP  ESCAPE: STOP"),
+                ]
+                .iter()
+                .enumerate()
+                {
+                    text::draw_boxed(
+                        &mut canvas,
+                        1.,
+                        1. + (i * (text::GLYPH_HEIGHT + 2)) as f32,
+                        1.,
+                        line,
+                        colours.on,
+                        colours.off,
+                    )?;
+                }
+            } else if *keypad_overlay.lock() {
+                keypad::draw(&mut canvas, *held_keys.lock(), colours, (width, height))?;
+            }
+
+            if paused {
+                draw_pause_indicator(&mut canvas, width, colours)?;
+            }
+
+            if debug_overlay {
+                debug::draw_overlay(
+                    &mut canvas,
+                    &machine_state,
+                    colours,
+                    paused,
+                    &debugger.lock(),
+                    (width, height),
+                )?;
             }
-            std::hint::spin_loop();
-            continue;
-        }
-        *prev_tick.lock() += time_period;
 
-        for event in event_pump.poll_iter() {
+            if search_panel {
+                debugger.lock().memory_search.draw(
+                    &mut canvas,
+                    &machine_state,
+                    &cheats.lock(),
+                    colours,
+                    paused,
+                    (width, height),
+                )?;
+            }
+
+            if let Some(quirk_menu) = quirk_menu {
+                quirk_menu.draw(&mut canvas, &machine_state, colours, (width, height))?;
+            }
+
+            if let Some(fault) = fault.lock().as_ref() {
+                debug::draw_fault(&mut canvas, &machine_state, fault, colours, (width, height))?;
+            }
+
+            osd.draw(&mut canvas, colours, (width, height))?;
+
+            if pacing_overlay {
+                pacing.lock().draw(&mut canvas, colours, (width, height))?;
+            }
+
+            canvas.present();
+            pacing.lock().render.record(drawing_started.elapsed());
+
+            Ok(())
+        };
+
+        let _window_update_eventwatch = event_subsystem.add_event_watch(|event| {
+            // Events pumped partway through a frame only update the keypad,
+            // the main loop handles them as usual afterwards
+            let polling_keys = *polling_keys.lock();
             match event {
-                Event::Quit { .. } => return Ok(()),
+                Event::Window {
+                    win_event: WindowEvent::Exposed,
+                    ..
+                } if !polling_keys => {
+                    *redraw.lock() = true;
+
+                    let delta = prev_tick.lock().elapsed();
+                    if delta > time_period {
+                        *prev_tick.lock() += time_period;
+                        if let Err(err) = execution_loop(1) {
+                            event_subsystem
+                                .push_custom_event(ExecutionErrorEvent(err))
+                                .expect("Custom event was not registered");
+                        }
+                    }
+                }
                 Event::KeyDown {
                     scancode: Some(scancode),
+                    keymod,
                     ..
-                } => {
-                    if let Some(i) = KEYMAP.iter().position(|key| key == &scancode) {
-                        *held_keys.lock() |= 0b1 << i;
+                } if polling_keys && !keymod.intersects(Mod::LALTMOD | Mod::RALTMOD) => {
+                    if let Some(key) = keymap.key(scancode) {
+                        *held_keys.lock() |= 0b1 << rotation.lock().rotate_key(key);
                     }
                 }
                 Event::KeyUp {
                     scancode: Some(scancode),
                     ..
-                } => {
-                    if let Some(i) = KEYMAP.iter().position(|key| key == &scancode) {
-                        *held_keys.lock() -= 0b1 << i;
+                } if polling_keys => {
+                    if let Some(key) = keymap.key(scancode) {
+                        *held_keys.lock() &= !(0b1 << rotation.lock().rotate_key(key));
                     }
                 }
-                _ => {
-                    if let Some(event) = event.as_user_event_type::<ExecutionErrorEvent>() {
-                        return Err(event.0);
+                _ => {}
+            }
+        });
+
+        // The key of the on-screen keypad held down with the mouse
+        let mut clicked_key = None;
+        // Buttons remapped in the emulator are saved for this ROM
+        let mut rom_gamepad_buttons = rom_config.gamepad.buttons.clone();
+        let mut speed_measured_at = Instant::now();
+        // When someone last pressed something, for moving on through a playlist
+        let mut last_input = Instant::now();
+        let mut next_rom = false;
+        // Set when losing focus paused emulation, so it's only resumed if the user didn't pause
+        let mut focus_paused = false;
+        // Execute instructions one at a time, for the monitor and remote control
+        let step_instructions =
+            |machine_state: &mut MachineState, count: u32| -> Result<(), Error> {
+                for _ in 0..count {
+                    trace_instruction(&mut trace.lock(), machine_state);
+                    machine_state
+                        .tick(|| *held_keys.lock(), || rng.lock().random())
+                        .map_err(|err| emulation_error(&rom, &data_dir, machine_state, err))?;
+                }
+                Ok(())
+            };
+
+        loop {
+            let background = !*focused.lock();
+            let frames = if background && *paused.lock() {
+                // Nothing can change until there's an event, so there's no need to check so often
+                sleep(BACKGROUND_POLL_INTERVAL);
+                *prev_tick.lock() = Instant::now();
+                0
+            } else if *fast_forwarding.lock() && config.fast_forward == 0 {
+                *prev_tick.lock() = Instant::now();
+                1
+            } else if vsync {
+                let mut prev_tick = prev_tick.lock();
+                // Don't try to catch up after a long stall, such as the window being dragged
+                if prev_tick.elapsed() > 4 * time_period {
+                    *prev_tick = Instant::now() - time_period;
+                }
+                // Frames that are nearly due are run now, so a display refreshing at slightly
+                // more or less than 60 Hz only drops or repeats a frame once the difference adds up
+                let mut frames = 0;
+                while prev_tick.elapsed() + time_period / 4 >= time_period {
+                    *prev_tick += time_period;
+                    frames += 1;
+                }
+                frames
+            } else {
+                let delta = prev_tick.lock().elapsed();
+                if delta < time_period {
+                    // Timing in the background doesn't need to be exact enough to be worth spinning
+                    if background {
+                        sleep(time_period - delta);
+                        continue;
+                    }
+                    if time_period - delta > Duration::from_millis(2) {
+                        sleep(time_period - delta - Duration::from_millis(1));
+                    }
+                    std::hint::spin_loop();
+                    continue;
+                }
+                *prev_tick.lock() += time_period;
+                1
+            };
+
+            for event in event_pump.lock().poll_iter() {
+                match event {
+                    Event::Window {
+                        win_event: WindowEvent::FocusGained,
+                        ..
+                    } => *focused.lock() = true,
+                    Event::Window {
+                        win_event: WindowEvent::FocusLost | WindowEvent::Minimized,
+                        ..
+                    } => *focused.lock() = false,
+                    _ => {}
+                }
+                if matches!(
+                    event,
+                    Event::KeyDown { .. }
+                        | Event::ControllerButtonDown { .. }
+                        | Event::MouseButtonDown { .. }
+                ) {
+                    last_input = Instant::now();
+                    // Someone taking over from a playlist's demo
+                    let mut movie = movie.lock();
+                    if playlist.is_some() && matches!(*movie, Some(movie::Session::Playing { .. }))
+                    {
+                        *movie = None;
+                        *held_keys.lock() = 0;
+                    }
+                }
+
+                // While an error is shown, keys only choose how to carry on
+                let faulted = fault.lock().is_some();
+                // There's nothing to skip to once the program has exited
+                let exited = matches!(
+                    *fault.lock(),
+                    Some(Error::Emulation {
+                        error: rs_chip8_core::Error::ProgramExited,
+                        ..
+                    })
+                );
+                let event = match event {
+                    Event::KeyDown {
+                        scancode: Some(Scancode::Q),
+                        timestamp,
+                        ..
+                    } if faulted => Event::Quit { timestamp },
+                    event => event,
+                };
+                if faulted && let Event::KeyDown { scancode, .. } = event {
+                    if let Some(scancode @ (Scancode::S | Scancode::R)) = scancode
+                        && (scancode == Scancode::R || !exited)
+                    {
+                        // The program counter is already past the faulting instruction
+                        if scancode == Scancode::R {
+                            reset(&mut machine_state.lock());
+                            rewind.lock().clear();
+                        }
+                        *fault.lock() = None;
+                        *paused.lock() = false;
+                        *redraw.lock() = true;
                     }
+                    continue;
+                }
+
+                // Typing in a breakpoint takes every key press until it's entered or cancelled
+                let mut finished_typing = false;
+                let typing = {
+                    let mut debugger = debugger.lock();
+                    let debug::Debugger {
+                        breakpoints,
+                        breakpoint_prompt,
+                        ..
+                    } = &mut *debugger;
+                    match (breakpoint_prompt.as_mut(), &event) {
+                        (Some(prompt), Event::TextInput { text, .. }) => {
+                            prompt.input.push_str(text);
+                            prompt.error = None;
+                            true
+                        }
+                        (
+                            Some(prompt),
+                            Event::KeyDown {
+                                scancode: Some(Scancode::Return | Scancode::KpEnter),
+                                ..
+                            },
+                        ) => {
+                            match breakpoints.toggle(&prompt.input) {
+                                Ok(()) => {
+                                    *breakpoint_prompt = None;
+                                    finished_typing = true;
+                                }
+                                Err(err) => prompt.error = Some(err),
+                            }
+                            true
+                        }
+                        (
+                            Some(_),
+                            Event::KeyDown {
+                                scancode: Some(Scancode::Escape),
+                                ..
+                            },
+                        ) => {
+                            *breakpoint_prompt = None;
+                            finished_typing = true;
+                            true
+                        }
+                        (
+                            Some(prompt),
+                            Event::KeyDown {
+                                scancode: Some(Scancode::Backspace),
+                                ..
+                            },
+                        ) => {
+                            prompt.input.pop();
+                            prompt.error = None;
+                            true
+                        }
+                        (Some(_), Event::KeyDown { .. }) => true,
+                        _ => false,
+                    }
+                };
+                if finished_typing {
+                    text_input.stop(canvas.lock().window());
+                }
+                if typing {
+                    continue;
                 }
+
+                // Remapping the gamepad takes every button press, asking for each key in turn
+                let remapping = *gamepad_remap.lock();
+                if let Some(index) = remapping {
+                    let key = rotation::KEYPAD.as_flattened()[index];
+                    let next = match event {
+                        // Start is kept for pausing
+                        Event::ControllerButtonDown { button, .. } if button != Button::Start => {
+                            gamepad_map.set(button, key);
+                            rom_gamepad_buttons.insert(button.string(), format!("{key:X}"));
+                            true
+                        }
+                        Event::KeyDown {
+                            scancode: Some(Scancode::Space),
+                            ..
+                        } => true,
+                        Event::KeyDown {
+                            scancode: Some(Scancode::Escape),
+                            ..
+                        } => {
+                            *gamepad_remap.lock() = None;
+                            *redraw.lock() = true;
+                            continue;
+                        }
+                        _ => false,
+                    };
+                    if next {
+                        let next = index + 1;
+                        if next == 16 {
+                            println!(
+                                "Remapped the gamepad, the buttons will be saved for this ROM"
+                            );
+                        }
+                        *gamepad_remap.lock() = (next < 16).then_some(next);
+                        *redraw.lock() = true;
+                        continue;
+                    }
+                }
+
+                // The quirk menu takes the keys it uses even while playing, so quirks can be tried live
+                if let Event::KeyDown {
+                    scancode: Some(scancode),
+                    keymod,
+                    ..
+                } = event
+                    && !keymod.intersects(Mod::LALTMOD | Mod::RALTMOD)
+                {
+                    let mut machine_state = machine_state.lock();
+                    if let Some(quirk_menu) = quirk_menu.lock().as_mut() {
+                        if scancode == Scancode::R {
+                            reset(&mut machine_state);
+                            rewind.lock().clear();
+                            *redraw.lock() = true;
+                            continue;
+                        }
+                        if quirk_menu.key_down(scancode, &mut machine_state) {
+                            *redraw.lock() = true;
+                            continue;
+                        }
+                    }
+                }
+
+                // While paused, the debug overlay and memory search take the keys they use,
+                // so the same keys still reach the game while playing
+                if let Event::KeyDown {
+                    scancode: Some(scancode),
+                    keymod,
+                    ..
+                } = event
+                    && !keymod.intersects(Mod::LALTMOD | Mod::RALTMOD)
+                    && *paused.lock()
+                {
+                    let debugging = debugging();
+                    let mut machine_state = machine_state.lock();
+                    let mut debugger = debugger.lock();
+                    if debugging
+                        && debugger
+                            .register_editor
+                            .key_down(scancode, &mut machine_state)
+                    {
+                        continue;
+                    }
+                    if *search_panel.lock()
+                        && debugger.memory_search.key_down(
+                            scancode,
+                            keymod,
+                            &machine_state,
+                            &mut cheats.lock(),
+                        )
+                    {
+                        continue;
+                    }
+                }
+
+                match event {
+                    Event::Quit { .. } => {
+                        // Don't leave a recording unfinished
+                        finish_recordings();
+                        if resume {
+                            match save_states.save_session(&machine_state.lock()) {
+                                Ok(()) => println!("Saved the session, to resume next time"),
+                                Err(err) => eprintln!("Failed to save the session: {err}"),
+                            }
+                        }
+                        if *pacing_overlay.lock() {
+                            println!("{}", pacing.lock());
+                        }
+
+                        // Remember speed and palette changes for the next time this ROM is run
+                        let mut tuned = rom_config.clone();
+                        if *ipf.lock() != initial_ipf {
+                            tuned.ipf = Some(*ipf.lock());
+                        }
+                        if theme != initial_theme {
+                            tuned.palette = palette::Palette::ALL.get(theme).copied();
+                        }
+                        let quirks = machine_state.lock().quirks();
+                        if quirks != initial_quirks {
+                            tuned.quirks = config::QuirksConfig::changes(
+                                Quirks::for_system(machine_state.lock().system()),
+                                quirks,
+                            );
+                        }
+                        tuned.cheats = cheats.lock().clone();
+                        tuned.gamepad.buttons = rom_gamepad_buttons.clone();
+                        if tuned != rom_config
+                            && let Some(path) = &rom_config_path
+                            && let Err(err) = tuned.save(path)
+                        {
+                            eprintln!("Failed to save settings for this ROM: {err}");
+                        }
+
+                        return Ok(());
+                    }
+                    // Keys mapped to the keypad go to the game before any hotkey,
+                    // so a keymap override can take any key
+                    Event::KeyDown {
+                        scancode: Some(scancode),
+                        ..
+                    } if keymap.key(scancode).is_some() => {
+                        if let Some(key) = keymap.key(scancode) {
+                            *held_keys.lock() |= 0b1 << rotation.lock().rotate_key(key);
+                            *redraw.lock() |= *keypad_overlay.lock();
+                        }
+                    }
+                    Event::KeyUp {
+                        scancode: Some(scancode),
+                        ..
+                    } if keymap.key(scancode).is_some() => {
+                        if let Some(key) = keymap.key(scancode) {
+                            *held_keys.lock() &= !(0b1 << rotation.lock().rotate_key(key));
+                            *redraw.lock() |= *keypad_overlay.lock();
+                        }
+                    }
+                    Event::KeyDown {
+                        scancode: Some(Scancode::P),
+                        repeat: false,
+                        ..
+                    } => {
+                        let mut paused = paused.lock();
+                        *paused = !*paused;
+                        osd.lock().show(if *paused { "Paused" } else { "Resumed" });
+                        *redraw.lock() = true;
+                    }
+                    Event::KeyDown {
+                        scancode: Some(scancode @ (Scancode::Period | Scancode::Comma)),
+                        ..
+                    } if *paused.lock() => {
+                        *step_request.lock() = Some(if scancode == Scancode::Period {
+                            Step::Frame
+                        } else {
+                            Step::Instruction
+                        });
+                    }
+                    Event::KeyDown {
+                        scancode:
+                            Some(
+                                scancode @ (Scancode::F1
+                                | Scancode::F2
+                                | Scancode::F3
+                                | Scancode::F4),
+                            ),
+                        keymod,
+                        repeat: false,
+                        ..
+                    } => {
+                        let slot = match scancode {
+                            Scancode::F1 => 1,
+                            Scancode::F2 => 2,
+                            Scancode::F3 => 3,
+                            _ => 4,
+                        };
+                        let mut machine_state = machine_state.lock();
+                        let mut movie = movie.lock();
+                        // Failing to save or load shouldn't end the game
+                        let message = if keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD) {
+                            if movie
+                                .as_ref()
+                                .is_some_and(|session| !session.can_load(slot))
+                            {
+                                eprintln!(
+                                    "Slot {slot} wasn't saved while recording this movie, so it can't be loaded"
+                                );
+                                format!("Slot {slot} isn't in the movie")
+                            } else {
+                                match save_states.load(slot, &mut machine_state) {
+                                    Ok(()) => {
+                                        rewind.lock().clear();
+                                        // Recording carries on from where the slot was saved
+                                        if let Some(session) = movie.as_mut() {
+                                            session.rerecord(slot);
+                                        }
+                                        println!("Loaded state from slot {slot}");
+                                        format!("Loaded slot {slot}")
+                                    }
+                                    Err(err) => {
+                                        eprintln!("Failed to load state from slot {slot}: {err}");
+                                        format!("Couldn't load slot {slot}")
+                                    }
+                                }
+                            }
+                        } else {
+                            match save_states.save(slot, &machine_state) {
+                                Ok(()) => {
+                                    if let Some(session) = movie.as_mut() {
+                                        session.saved(slot);
+                                    }
+                                    println!("Saved state to slot {slot}");
+                                    format!("Saved slot {slot}")
+                                }
+                                Err(err) => {
+                                    eprintln!("Failed to save state to slot {slot}: {err}");
+                                    format!("Couldn't save slot {slot}")
+                                }
+                            }
+                        };
+                        osd.lock().show(&message);
+                    }
+                    Event::KeyDown {
+                        scancode: Some(Scancode::Backspace),
+                        ..
+                    } => *rewinding.lock() = true,
+                    Event::KeyUp {
+                        scancode: Some(Scancode::Backspace),
+                        ..
+                    } => *rewinding.lock() = false,
+                    Event::KeyDown {
+                        scancode: Some(Scancode::F5),
+                        keymod,
+                        repeat: false,
+                        ..
+                    } if keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD) => {
+                        let mut rom_watcher = rom_watcher.lock();
+                        *rom_watcher = match *rom_watcher {
+                            Some(_) => {
+                                println!("Stopped watching the ROM for changes");
+                                osd.lock().show("Not watching ROM");
+                                None
+                            }
+                            None => {
+                                println!("Watching {} for changes", rom.display());
+                                osd.lock().show("Watching ROM");
+                                Some(watch::RomWatcher::new(&rom))
+                            }
+                        };
+                    }
+                    Event::KeyDown {
+                        scancode: Some(Scancode::F5),
+                        repeat: false,
+                        ..
+                    } => {
+                        let mut slow_motion = slow_motion.lock();
+                        *slow_motion = slow_motion.next();
+                        let message = format!("Speed: {}%", 100 / slow_motion.divisor());
+                        println!("{message}");
+                        osd.lock().show(&message);
+                    }
+                    Event::KeyDown {
+                        scancode: Some(scancode @ (Scancode::Equals | Scancode::Minus)),
+                        ..
+                    } => {
+                        let mut ipf = ipf.lock();
+                        *ipf = if scancode == Scancode::Equals {
+                            ipf.saturating_add(1)
+                        } else {
+                            ipf.saturating_sub(1).max(1)
+                        };
+                        osd.lock().show(&format!("IPF {ipf}"));
+                    }
+                    Event::KeyDown {
+                        scancode: Some(scancode @ (Scancode::F11 | Scancode::Return)),
+                        keymod,
+                        repeat: false,
+                        ..
+                    } if scancode == Scancode::F11
+                        || keymod.intersects(Mod::LALTMOD | Mod::RALTMOD) =>
+                    {
+                        fullscreen = !fullscreen;
+                        let mut canvas = canvas.lock();
+                        canvas.window_mut().set_fullscreen(fullscreen)?;
+                        // Make sure the display is letterboxed again when leaving fullscreen
+                        set_logical_size(&mut canvas, integer_scale, *rotation.lock())?;
+                        *redraw.lock() = true;
+                    }
+                    Event::KeyDown {
+                        scancode: Some(Scancode::F7),
+                        repeat: false,
+                        ..
+                    } => {
+                        let next = rotation.lock().next();
+                        *rotation.lock() = next;
+                        set_logical_size(&mut canvas.lock(), integer_scale, next)?;
+                        // Held keys were rotated the old way, so they would never be released
+                        *held_keys.lock() = 0;
+                        *redraw.lock() = true;
+                    }
+                    Event::KeyDown {
+                        scancode: Some(Scancode::F12),
+                        keymod,
+                        repeat: false,
+                        ..
+                    } if keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD) => {
+                        let machine_state = machine_state.lock();
+                        let mut trace = trace.lock();
+                        match trace.take() {
+                            Some(stopped) => {
+                                finish_trace(stopped, &machine_state);
+                                osd.lock().show("Saved trace");
+                            }
+                            None => match capture.path("trace.txt").and_then(trace::Trace::start) {
+                                Ok(started) => {
+                                    println!(
+                                        "Tracing instructions to {}, press Shift+F12 again to stop",
+                                        started.path().display()
+                                    );
+                                    osd.lock().show("Tracing instructions");
+                                    *trace = Some(started);
+                                }
+                                Err(err) => eprintln!("Failed to start tracing: {err}"),
+                            },
+                        }
+                    }
+                    Event::KeyDown {
+                        scancode: Some(Scancode::F12),
+                        repeat: false,
+                        ..
+                    } => {
+                        let display = machine_state.lock().display().clone();
+                        match capture.screenshot(&display, *colours.lock()) {
+                            Ok(path) => {
+                                println!("Saved screenshot to {}", path.display());
+                                osd.lock().show("Saved screenshot");
+                            }
+                            Err(err) => {
+                                eprintln!("Failed to save screenshot: {err}");
+                                osd.lock().show("Couldn't save screenshot");
+                            }
+                        }
+                    }
+                    Event::KeyDown {
+                        scancode: Some(Scancode::F9),
+                        keymod,
+                        repeat: false,
+                        ..
+                    } if keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD) => {
+                        let mut machine_state = machine_state.lock();
+                        let mut movie = movie.lock();
+                        match movie.take() {
+                            Some(session) => match session.finish() {
+                                Ok(Some(path)) => println!("Saved movie to {}", path.display()),
+                                Ok(None) => println!("Stopped playing the movie"),
+                                Err(err) => eprintln!("Failed to save movie: {err}"),
+                            },
+                            None => match capture.path("movie.json") {
+                                Ok(path) => {
+                                    // Movies play back from the ROM being loaded
+                                    reset(&mut machine_state);
+                                    rewind.lock().clear();
+                                    *movie = Some(movie::Session::record(
+                                        movie::Movie::new(&program, rand::random(), *ipf.lock()),
+                                        path,
+                                    ));
+                                    println!(
+                                        "Recording a movie from the start, press Shift+F9 again to stop"
+                                    );
+                                }
+                                Err(err) => eprintln!("Failed to start recording a movie: {err}"),
+                            },
+                        }
+                        *redraw.lock() = true;
+                    }
+                    Event::KeyDown {
+                        scancode: Some(Scancode::F9),
+                        repeat: false,
+                        ..
+                    } => {
+                        let mut gif_recorder = gif_recorder.lock();
+                        match gif_recorder.take() {
+                            Some(recorder) => finish_gif(recorder),
+                            None => match capture.start_gif(*colours.lock()) {
+                                Ok(recorder) => {
+                                    println!("Recording GIF, press F9 again to stop");
+                                    osd.lock().show("Recording GIF");
+                                    *gif_recorder = Some(recorder);
+                                }
+                                Err(err) => eprintln!("Failed to start recording GIF: {err}"),
+                            },
+                        }
+                    }
+                    Event::KeyDown {
+                        scancode: Some(Scancode::Grave),
+                        keymod,
+                        repeat: false,
+                        ..
+                    } if keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD) => {
+                        let mut pacing_overlay = pacing_overlay.lock();
+                        *pacing_overlay = !*pacing_overlay;
+                        if *pacing_overlay {
+                            *pacing.lock() = pacing::Pacing::default();
+                        } else {
+                            println!("{}", pacing.lock());
+                        }
+                        *redraw.lock() = true;
+                    }
+                    Event::KeyDown {
+                        scancode: Some(Scancode::Grave),
+                        keymod,
+                        repeat: false,
+                        ..
+                    } if keymod.intersects(Mod::LCTRLMOD | Mod::RCTRLMOD) => {
+                        let mut debug_window = debug_window.lock();
+                        *debug_window = match *debug_window {
+                            Some(_) => None,
+                            None => Some(debug_window::DebugWindow::open(&video_subsystem)?),
+                        };
+                        *redraw.lock() = true;
+                    }
+                    Event::Window {
+                        win_event: WindowEvent::FocusLost,
+                        window_id,
+                        ..
+                    } if config.window.pause_unfocused
+                        && window_id == canvas.lock().window().id() =>
+                    {
+                        let mut paused = paused.lock();
+                        if !*paused {
+                            *paused = true;
+                            focus_paused = true;
+                            *redraw.lock() = true;
+                        }
+                    }
+                    // Focus moving to the debugger's window also resumes
+                    Event::Window {
+                        win_event: WindowEvent::FocusGained,
+                        ..
+                    } if focus_paused => {
+                        *paused.lock() = false;
+                        focus_paused = false;
+                        *redraw.lock() = true;
+                    }
+                    Event::Window {
+                        win_event: WindowEvent::CloseRequested,
+                        window_id,
+                        ..
+                    } => {
+                        let mut debug_window = debug_window.lock();
+                        let mut comparison = comparison.lock();
+                        if debug_window
+                            .as_ref()
+                            .is_some_and(|debug_window| debug_window.id() == window_id)
+                        {
+                            *debug_window = None;
+                            *redraw.lock() = true;
+                        } else if comparison
+                            .as_ref()
+                            .is_some_and(|comparison| comparison.id() == window_id)
+                        {
+                            *comparison = None;
+                        } else if debug_window.is_some() || comparison.is_some() {
+                            // SDL only quits by itself once every window is closed
+                            event_subsystem.push_event(Event::Quit { timestamp: 0 })?;
+                        }
+                    }
+                    Event::KeyDown {
+                        scancode: Some(Scancode::Grave),
+                        repeat: false,
+                        ..
+                    } => {
+                        let mut debug_overlay = debug_overlay.lock();
+                        *debug_overlay = !*debug_overlay;
+                        if *debug_overlay {
+                            *search_panel.lock() = false;
+                        }
+                        *redraw.lock() = true;
+                    }
+                    Event::KeyDown {
+                        scancode: Some(Scancode::F10),
+                        keymod,
+                        repeat: false,
+                        ..
+                    } if keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD) => {
+                        if gamepads.is_empty() {
+                            println!("Connect a gamepad to remap it");
+                        } else {
+                            *gamepad_remap.lock() = Some(0);
+                        }
+                    }
+                    Event::KeyDown {
+                        scancode: Some(Scancode::F10),
+                        repeat: false,
+                        ..
+                    } => {
+                        let mut keypad_overlay = keypad_overlay.lock();
+                        *keypad_overlay = !*keypad_overlay;
+                        *redraw.lock() = true;
+                    }
+                    Event::KeyDown {
+                        scancode: Some(Scancode::F8),
+                        keymod,
+                        repeat: false,
+                        ..
+                    } if keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD) => {
+                        let mut quirk_menu = quirk_menu.lock();
+                        *quirk_menu = match *quirk_menu {
+                            Some(_) => None,
+                            None => Some(quirk_menu::QuirkMenu::default()),
+                        };
+                        *redraw.lock() = true;
+                    }
+                    Event::KeyDown {
+                        scancode: Some(Scancode::F8),
+                        repeat: false,
+                        ..
+                    } => {
+                        let mut search_panel = search_panel.lock();
+                        *search_panel = !*search_panel;
+                        if *search_panel {
+                            *debug_overlay.lock() = false;
+                        }
+                        *redraw.lock() = true;
+                    }
+                    Event::KeyDown {
+                        scancode:
+                            Some(
+                                scancode @ (Scancode::LeftBracket
+                                | Scancode::RightBracket
+                                | Scancode::Backslash),
+                            ),
+                        keymod,
+                        ..
+                    } if debugging() => {
+                        // Lock in the same order as the execution loop
+                        let machine_state = machine_state.lock();
+                        let sprite_viewer = &mut debugger.lock().sprite_viewer;
+                        // Shift moves a whole 16x16 sprite at a time
+                        let step = if keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD) {
+                            32
+                        } else {
+                            1
+                        };
+                        match scancode {
+                            Scancode::LeftBracket => sprite_viewer.scroll(&machine_state, -step),
+                            Scancode::RightBracket => sprite_viewer.scroll(&machine_state, step),
+                            _ => sprite_viewer.follow_index(),
+                        }
+                    }
+                    Event::KeyDown {
+                        scancode: Some(Scancode::B),
+                        repeat: false,
+                        ..
+                    } if *paused.lock() && debugging() => {
+                        debugger.lock().breakpoint_prompt =
+                            Some(debug::BreakpointPrompt::default());
+                        text_input.start(canvas.lock().window());
+                    }
+                    Event::KeyDown {
+                        scancode: Some(Scancode::M),
+                        repeat: false,
+                        ..
+                    } => {
+                        let mut audio_muted = audio_muted.lock();
+                        *audio_muted = !*audio_muted;
+                        let message = if *audio_muted {
+                            "Sound muted"
+                        } else {
+                            "Sound on"
+                        };
+                        println!("{message}");
+                        osd.lock().show(message);
+                    }
+                    Event::KeyDown {
+                        scancode: Some(Scancode::PageDown),
+                        repeat: false,
+                        ..
+                    } if playlist.is_some() => next_rom = true,
+                    Event::KeyDown {
+                        scancode: Some(Scancode::F6),
+                        keymod,
+                        repeat: false,
+                        ..
+                    } if keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD) => {
+                        let mut pixel_grid = pixel_grid.lock();
+                        *pixel_grid = !*pixel_grid;
+                        *redraw.lock() = true;
+                    }
+                    Event::KeyDown {
+                        scancode: Some(Scancode::F6),
+                        repeat: false,
+                        ..
+                    } => {
+                        theme = (theme + 1) % themes.len();
+                        *colours.lock() = themes[theme];
+                        *redraw.lock() = true;
+                    }
+                    Event::KeyDown {
+                        scancode: Some(Scancode::Tab),
+                        ..
+                    } => {
+                        *fast_forwarding.lock() = true;
+                        osd.lock().show("Fast forward");
+                    }
+                    Event::KeyUp {
+                        scancode: Some(Scancode::Tab),
+                        ..
+                    } => *fast_forwarding.lock() = false,
+                    Event::ControllerDeviceAdded { which, .. } => {
+                        gamepad::open(&gamepad_subsystem, which, &mut gamepads);
+                    }
+                    Event::ControllerDeviceRemoved { which, .. } => {
+                        gamepads.retain(|gamepad| gamepad.id() != which);
+                    }
+                    Event::ControllerButtonDown {
+                        button: Button::Start,
+                        ..
+                    } => {
+                        let mut paused = paused.lock();
+                        *paused = !*paused;
+                        osd.lock().show(if *paused { "Paused" } else { "Resumed" });
+                        *redraw.lock() = true;
+                    }
+                    Event::ControllerButtonDown { button, .. } => {
+                        if let Some(key) = gamepad_map.key(button) {
+                            *held_keys.lock() |= 0b1 << rotation.lock().rotate_key(key);
+                            *redraw.lock() |= *keypad_overlay.lock();
+                        }
+                    }
+                    Event::ControllerButtonUp { button, .. } => {
+                        if let Some(key) = gamepad_map.key(button) {
+                            *held_keys.lock() &= !(0b1 << rotation.lock().rotate_key(key));
+                            *redraw.lock() |= *keypad_overlay.lock();
+                        }
+                    }
+                    Event::MouseButtonDown {
+                        mouse_btn: MouseButton::Left,
+                        x,
+                        y,
+                        ..
+                    } if *keypad_overlay.lock() => {
+                        let rotation = *rotation.lock();
+                        let point =
+                            window_to_logical(&canvas.lock(), integer_scale, rotation, (x, y));
+                        clicked_key = keypad::key_at(point, rotation.logical_size());
+                        if let Some(key) = clicked_key {
+                            *held_keys.lock() |= 0b1 << key;
+                            *redraw.lock() = true;
+                        }
+                    }
+                    Event::MouseButtonUp {
+                        mouse_btn: MouseButton::Left,
+                        ..
+                    } => {
+                        if let Some(key) = clicked_key.take() {
+                            *held_keys.lock() &= !(0b1 << key);
+                            *redraw.lock() = true;
+                        }
+                    }
+                    _ => {
+                        if let Some(event) = event.as_user_event_type::<ExecutionErrorEvent>() {
+                            finish_recordings();
+                            return Err(event.0);
+                        }
+                    }
+                }
+            }
+
+            // Move on through a playlist once nobody has played for a while, after any demo
+            if playlist.is_some() && movie.lock().is_none() && last_input.elapsed() >= attract_idle
+            {
+                next_rom = true;
+            }
+            if next_rom {
+                finish_recordings();
+                break;
+            }
+
+            // Restart with the ROM once it's rebuilt, keeping the settings it was opened with
+            if rom_watcher
+                .lock()
+                .as_mut()
+                .is_some_and(watch::RomWatcher::changed)
+            {
+                // The old program keeps running if the new one can't be read or doesn't fit
+                match std::fs::read(&rom)
+                    .map_err(Error::from)
+                    .and_then(|program| {
+                        load_machine_state(
+                            &rom,
+                            &program,
+                            cli.system,
+                            &config,
+                            &rom_config,
+                            database_entry.as_ref(),
+                        )
+                    }) {
+                    Ok(reloaded) => {
+                        *initial_machine_state.lock() = reloaded;
+                        reset(&mut machine_state.lock());
+                        rewind.lock().clear();
+                        *fault.lock() = None;
+                        *redraw.lock() = true;
+                        println!("Reloaded {}", rom.display());
+                        osd.lock().show("Reloaded ROM");
+                    }
+                    Err(err) => eprintln!("Couldn't reload {}: {err}", rom.display()),
+                }
+            }
+
+            // Commands typed into the monitor since the last frame
+            while let Some(line) = monitor.as_ref().and_then(|monitor| monitor.try_recv().ok()) {
+                if line.trim().is_empty() {
+                    continue;
+                }
+                let command = match monitor::Command::parse(&line) {
+                    Ok(command) => command,
+                    Err(err) => {
+                        eprintln!("{err}");
+                        continue;
+                    }
+                };
+
+                let mut machine_state = machine_state.lock();
+                match command {
+                    monitor::Command::Step(count) => {
+                        *paused.lock() = true;
+                        if let Err(err) = step_instructions(&mut machine_state, count) {
+                            eprintln!("{err}");
+                        }
+                        let next = monitor::Command::Disassemble {
+                            address: None,
+                            count: 1,
+                        };
+                        let mut debugger = debugger.lock();
+                        if let Ok(output) =
+                            monitor::execute(&next, &mut machine_state, &mut debugger.breakpoints)
+                        {
+                            print!("{output}");
+                        }
+                    }
+                    monitor::Command::Continue => *paused.lock() = false,
+                    monitor::Command::Pause => *paused.lock() = true,
+                    monitor::Command::Quit => {
+                        event_subsystem.push_event(Event::Quit { timestamp: 0 })?
+                    }
+                    command => {
+                        let mut debugger = debugger.lock();
+                        match monitor::execute(
+                            &command,
+                            &mut machine_state,
+                            &mut debugger.breakpoints,
+                        ) {
+                            Ok(output) => print!("{output}"),
+                            Err(err) => eprintln!("{err}"),
+                        }
+                    }
+                }
+                *redraw.lock() = true;
+            }
+
+            // Requests from remote control clients since the last frame
+            while let Some((request, respond)) =
+                remote.as_ref().and_then(|remote| remote.try_recv().ok())
+            {
+                let response = remote::handle(
+                    request,
+                    &mut machine_state.lock(),
+                    &mut paused.lock(),
+                    |machine_state, count| {
+                        step_instructions(machine_state, count).map_err(|err| err.to_string())
+                    },
+                );
+                // The client may have disconnected while waiting
+                let _ = respond.send(response);
+                *redraw.lock() = true;
+            }
+
+            // Show the speed over the last second in the title
+            let elapsed = speed_measured_at.elapsed();
+            if elapsed >= Duration::from_secs(1) {
+                let SpeedCounter {
+                    frames,
+                    instructions,
+                } = std::mem::take(&mut *speed_counter.lock());
+                speed_measured_at = Instant::now();
+                let fps = frames as f64 / elapsed.as_secs_f64();
+                let ips = instructions as f64 / elapsed.as_secs_f64();
+                canvas
+                    .lock()
+                    .window_mut()
+                    .set_title(&format!(
+                        "{rom_title} - {fps:.0} FPS, {ips:.0} IPS - rs_chip8"
+                    ))
+                    .expect("Title contained a null byte");
+            }
+
+            if let Err(err) = execution_loop(frames) {
+                finish_recordings();
+                return Err(err);
             }
         }
+    }
+}
+
+/// The palettes that can be switched between, and the index of the one to start with
+///
+/// Custom colours are added after the presets, based on the chosen palette.
+fn themes(
+    palette: palette::Palette,
+    off_colour: Option<palette::HexColour>,
+    on_colour: Option<palette::HexColour>,
+) -> (Vec<palette::Colours>, usize) {
+    let mut themes = palette::Palette::ALL
+        .map(palette::Palette::colours)
+        .to_vec();
+    let mut theme = palette::Palette::ALL
+        .iter()
+        .position(|&preset| preset == palette)
+        .unwrap_or_default();
+    if off_colour.is_some() || on_colour.is_some() {
+        let default = palette.colours();
+        themes.push(palette::Colours {
+            off: off_colour.map_or(default.off, |colour| colour.0),
+            on: on_colour.map_or(default.on, |colour| colour.0),
+        });
+        theme = themes.len() - 1;
+    }
+
+    (themes, theme)
+}
+
+fn finish_gif(recorder: capture::GifRecorder) {
+    match recorder.finish() {
+        Ok(path) => println!("Saved GIF to {}", path.display()),
+        Err(err) => eprintln!("Failed to save GIF: {err}"),
+    }
+}
+
+fn finish_trace(trace: trace::Trace, machine_state: &MachineState) {
+    match trace.finish(machine_state) {
+        Ok(path) => println!("Saved trace to {}", path.display()),
+        Err(err) => eprintln!("Failed to save trace: {err}"),
+    }
+}
+
+/// Add the instruction about to run to the trace, stopping the trace if it can't be written
+fn trace_instruction(trace: &mut Option<trace::Trace>, machine_state: &MachineState) {
+    if let Some(writer) = trace.as_mut()
+        && let Err(err) = writer.instruction(machine_state)
+    {
+        eprintln!("Stopped tracing: {err}");
+        *trace = None;
+    }
+}
+
+/// Set the logical resolution to the rotated display size, letterboxed to fit the window
+///
+/// With `integer_scale`, the display is only scaled by whole multiples so every pixel is the same size.
+fn set_logical_size(
+    canvas: &mut sdl3::render::Canvas<sdl3::video::Window>,
+    integer_scale: bool,
+    rotation: rotation::Rotation,
+) -> Result<(), Error> {
+    let presentation = if integer_scale {
+        sdl3::sys::render::SDL_RendererLogicalPresentation::INTEGER_SCALE
+    } else {
+        sdl3::sys::render::SDL_RendererLogicalPresentation::LETTERBOX
+    };
+
+    let (width, height) = rotation.logical_size();
+    if let Err(err) = canvas.set_logical_size(width as u32, height as u32, presentation) {
+        if let sdl3::IntegerOrSdlError::SdlError(err) = err {
+            return Err(err.into());
+        } else {
+            panic!("Expected display height and width to be valid");
+        }
+    }
+
+    Ok(())
+}
+
+/// Add the instruction that caused an error, which is just before the program counter,
+/// and save a crash report unless the program exited by itself
+fn emulation_error(
+    rom: &Path,
+    data_dir: &Path,
+    machine_state: &MachineState,
+    error: rs_chip8_core::Error,
+) -> Error {
+    let address = machine_state.program_counter().wrapping_sub(2);
+    let report = match error {
+        rs_chip8_core::Error::ProgramExited => None,
+        _ => match crash::save_report(rom, data_dir, machine_state, &error, address) {
+            Ok(path) => Some(path),
+            Err(err) => {
+                eprintln!("Failed to save crash report: {err}");
+                None
+            }
+        },
+    };
+    let memory = machine_state.memory();
+    let opcode = u16::from_be_bytes([
+        memory[address as usize % RAM_SIZE],
+        memory[(address as usize + 1) % RAM_SIZE],
+    ]);
+    Error::Emulation {
+        error,
+        address,
+        opcode,
+        instruction: disasm::disassemble(opcode, machine_state.system()).to_string(),
+        report,
+    }
+}
+
+/// The system a ROM is for judging by its file extension, or the configured one if it's unknown
+fn system_from_extension(rom: &Path, config: &config::Config) -> EmulationSystem {
+    match rom.extension().and_then(OsStr::to_str) {
+        Some("ch8") => EmulationSystem::Chip8,
+        Some("sc8") => EmulationSystem::SuperChip,
+        _ => config.system.map(Into::into).unwrap_or_default(),
+    }
+}
+
+/// A machine with the program loaded, emulating the system and quirks chosen for it
+fn load_machine_state(
+    rom: &Path,
+    program: &[u8],
+    system: Option<cli::System>,
+    config: &config::Config,
+    rom_config: &config::RomConfig,
+    database_entry: Option<&database::Entry>,
+) -> Result<MachineState, Error> {
+    let max_len = RAM_SIZE - PROGRAM_ADDRESS as usize;
+    if program.len() > max_len {
+        return Err(Error::RomTooBig {
+            path: rom.to_path_buf(),
+            len: program.len(),
+            max_len,
+        });
+    }
+
+    // Unless specified or known, choose the system to emulate based on the ROM file extension
+    let system = match system
+        .or(rom_config.system)
+        .or(database_entry.and_then(|entry| entry.system))
+    {
+        Some(system) => system.into(),
+        None => system_from_extension(rom, config),
+    };
 
-        execution_loop()?;
+    let mut machine_state = MachineState::new(system);
+    let mut quirks = machine_state.quirks();
+    if let Some(entry) = database_entry {
+        quirks = entry.quirks.apply(quirks);
     }
+    machine_state.set_quirks(rom_config.quirks.apply(quirks));
+    machine_state.load_default_font();
+    machine_state.load_program(program);
+    Ok(machine_state)
+}
+
+/// Convert a point in the window to logical units, undoing the scaling and letterboxing
+fn window_to_logical(
+    canvas: &sdl3::render::Canvas<sdl3::video::Window>,
+    integer_scale: bool,
+    rotation: rotation::Rotation,
+    (x, y): (f32, f32),
+) -> (f32, f32) {
+    let (width, height) = rotation.logical_size();
+    let (window_width, window_height) = canvas.window().size();
+    let mut scale = (window_width as f32 / width as f32).min(window_height as f32 / height as f32);
+    if integer_scale {
+        // Scaling is done in whole pixels, which can be smaller than window units
+        let density = canvas.window().size_in_pixels().0 as f32 / window_width as f32;
+        scale = (scale * density).floor().max(1.) / density;
+    }
+
+    let offset_x = (window_width as f32 - width as f32 * scale) / 2.;
+    let offset_y = (window_height as f32 - height as f32 * scale) / 2.;
+    ((x - offset_x) / scale, (y - offset_y) / scale)
+}
+
+/// Outline each of the display's pixels, which are `pixel_size` logical units wide
+///
+/// The lines are one screen pixel wide, so are only drawn once pixels are big enough to see them.
+fn draw_pixel_grid(
+    canvas: &mut sdl3::render::Canvas<sdl3::video::Window>,
+    (width, height): (usize, usize),
+    pixel_size: usize,
+    colours: palette::Colours,
+) -> Result<(), Error> {
+    let (output_width, output_height) = canvas.output_size()?;
+    let logical_scale =
+        (output_width as f32 / width as f32).min(output_height as f32 / height as f32);
+    if logical_scale * (pixel_size as f32) < 6. {
+        return Ok(());
+    }
+    let line_width = 1. / logical_scale;
+
+    // Halfway between the colours, so the lines show over pixels that are on and off
+    let (on, off) = (colours.on, colours.off);
+    canvas.set_draw_color(sdl3::pixels::Color::RGB(
+        ((on.r as u16 + off.r as u16) / 2) as u8,
+        ((on.g as u16 + off.g as u16) / 2) as u8,
+        ((on.b as u16 + off.b as u16) / 2) as u8,
+    ));
+    let columns = (pixel_size..width)
+        .step_by(pixel_size)
+        .map(|x| FRect::new(x as f32, 0., line_width, height as f32));
+    let rows = (pixel_size..height)
+        .step_by(pixel_size)
+        .map(|y| FRect::new(0., y as f32, width as f32, line_width));
+    canvas.fill_rects(&columns.chain(rows).collect::<Vec<_>>())?;
+
+    Ok(())
+}
+
+/// Ask whether to carry on from the session saved when the ROM was last closed
+fn ask_to_resume(rom_title: &str, window: &sdl3::video::Window) -> bool {
+    let buttons = [
+        ButtonData {
+            flags: MessageBoxButtonFlag::RETURNKEY_DEFAULT,
+            button_id: 1,
+            text: "Resume",
+        },
+        ButtonData {
+            flags: MessageBoxButtonFlag::ESCAPEKEY_DEFAULT,
+            button_id: 0,
+            text: "Start over",
+        },
+    ];
+    match show_message_box(
+        MessageBoxFlag::INFORMATION,
+        &buttons,
+        "rs_chip8",
+        &format!("Carry on from where you left off in {rom_title}?"),
+        window,
+        None,
+    ) {
+        Ok(ClickedButton::CustomButton(button)) => button.button_id == 1,
+        Ok(ClickedButton::CloseButton) => false,
+        Err(err) => {
+            eprintln!("Failed to ask about resuming: {err}");
+            false
+        }
+    }
+}
+
+/// Outline the display, showing when the beeper is playing without needing to hear it
+fn draw_sound_indicator(
+    canvas: &mut sdl3::render::Canvas<sdl3::video::Window>,
+    (width, height): (usize, usize),
+    colours: palette::Colours,
+) -> Result<(), Error> {
+    let (width, height) = (width as f32, height as f32);
+
+    canvas.set_draw_color(colours.on);
+    canvas.fill_rect(FRect::new(0., 0., width, 1.))?;
+    canvas.fill_rect(FRect::new(0., height - 1., width, 1.))?;
+    canvas.fill_rect(FRect::new(0., 0., 1., height))?;
+    canvas.fill_rect(FRect::new(width - 1., 0., 1., height))?;
+
+    Ok(())
+}
+
+/// Draw a pause symbol in the top right corner of a display `width` pixels wide
+fn draw_pause_indicator(
+    canvas: &mut sdl3::render::Canvas<sdl3::video::Window>,
+    width: usize,
+    colours: palette::Colours,
+) -> Result<(), Error> {
+    let x = (width - 11) as f32;
+
+    canvas.set_draw_color(colours.off);
+    canvas.fill_rect(FRect::new(x, 1., 10., 10.))?;
+
+    canvas.set_draw_color(colours.on);
+    canvas.fill_rect(FRect::new(x + 2., 2., 2., 8.))?;
+    canvas.fill_rect(FRect::new(x + 6., 2., 2., 8.))?;
+
+    Ok(())
 }
@@ -0,0 +1,176 @@
+use crate::{breakpoint::Breakpoints, savestate};
+use rs_chip8_core::{MachineState, disasm};
+use std::{
+    fmt::Write,
+    io::BufRead,
+    path::PathBuf,
+    sync::mpsc::{self, Receiver},
+};
+
+const HELP: &str = "\
+Commands:
+  regs                   Show the registers, timers and stack
+  mem <addr> [len]       Show memory, in hex
+  poke <addr> <byte>     Write a byte of memory, in hex
+  dis [addr] [count]     Disassemble instructions, from the program counter by default
+  step [count]           Pause, then execute instructions one at a time
+  break <addr> [if ...]  Toggle a breakpoint, e.g. `break 2A4 if V0 == 3`
+  continue               Resume execution
+  pause                  Pause execution
+  save <path>            Save the machine state to a file
+  load <path>            Load the machine state from a file
+  quit                   Close the emulator
+";
+
+/// A command typed into the monitor
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    Registers,
+    Memory { address: u16, length: u16 },
+    Poke { address: u16, value: u8 },
+    Disassemble { address: Option<u16>, count: u16 },
+    Step(u32),
+    Break(String),
+    Continue,
+    Pause,
+    Save(PathBuf),
+    Load(PathBuf),
+    Quit,
+    Help,
+}
+
+/// A number in hex, optionally prefixed with `0x`
+fn parse_hex(s: &str) -> Result<u16, String> {
+    let digits = s
+        .strip_prefix("0x")
+        .or_else(|| s.strip_prefix("0X"))
+        .unwrap_or(s);
+    u16::from_str_radix(digits, 16).map_err(|_| format!("{s:?} is not a hex number"))
+}
+
+impl Command {
+    pub fn parse(line: &str) -> Result<Self, String> {
+        let line = line.trim();
+        let (name, arguments) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        let arguments = arguments.trim();
+        let mut words = arguments.split_whitespace();
+        let mut hex = |default: Option<u16>| match words.next() {
+            Some(word) => parse_hex(word),
+            None => default.ok_or_else(|| format!("{name} needs more arguments, see help")),
+        };
+
+        Ok(match name {
+            "regs" | "r" => Self::Registers,
+            "mem" | "m" => Self::Memory {
+                address: hex(None)?,
+                length: hex(Some(0x10))?,
+            },
+            "poke" => Self::Poke {
+                address: hex(None)?,
+                value: u8::try_from(hex(None)?).map_err(|_| "Values are one byte".to_string())?,
+            },
+            "dis" | "d" => {
+                let address = words.next().map(parse_hex).transpose()?;
+                Self::Disassemble {
+                    address,
+                    count: words.next().map(parse_hex).transpose()?.unwrap_or(0x10),
+                }
+            }
+            "step" | "s" => Self::Step(match words.next() {
+                Some(count) => count
+                    .parse()
+                    .map_err(|_| format!("{count:?} is not a number"))?,
+                None => 1,
+            }),
+            "break" | "b" if !arguments.is_empty() => Self::Break(arguments.to_string()),
+            "continue" | "c" => Self::Continue,
+            "pause" | "p" => Self::Pause,
+            "save" if !arguments.is_empty() => Self::Save(PathBuf::from(arguments)),
+            "load" if !arguments.is_empty() => Self::Load(PathBuf::from(arguments)),
+            "quit" | "q" => Self::Quit,
+            "help" | "h" | "?" => Self::Help,
+            _ => return Err(format!("{line:?} is not a command, see help")),
+        })
+    }
+}
+
+/// Read lines from stdin on another thread, so the emulator can check for them without blocking
+pub fn spawn() -> Receiver<String> {
+    let (sender, receiver) = mpsc::channel();
+    std::thread::spawn(move || {
+        for line in std::io::stdin().lock().lines() {
+            let Ok(line) = line else {
+                break;
+            };
+            if sender.send(line).is_err() {
+                break;
+            }
+        }
+    });
+    receiver
+}
+
+/// Run a command that only looks at or changes the machine state, returning what to print
+///
+/// Stepping, pausing, and quitting need the emulator's own state, so are left to the caller.
+pub fn execute(
+    command: &Command,
+    machine_state: &mut MachineState,
+    breakpoints: &mut Breakpoints,
+) -> Result<String, String> {
+    let mut output = String::new();
+    match command {
+        Command::Registers => {
+            let _ = machine_state.dump(&mut output);
+        }
+        Command::Memory { address, length } => {
+            for row in (0..*length).step_by(16) {
+                let start = address.wrapping_add(row);
+                let _ = write!(output, "{start:03X}:");
+                for offset in 0..16.min(length - row) {
+                    let _ = write!(
+                        output,
+                        " {:02X}",
+                        machine_state.peek(start.wrapping_add(offset))
+                    );
+                }
+                output.push('\n');
+            }
+        }
+        Command::Poke { address, value } => machine_state.poke(*address, *value),
+        Command::Disassemble { address, count } => {
+            let start = address.unwrap_or(machine_state.program_counter());
+            for i in 0..*count {
+                let address = start.wrapping_add(2 * i);
+                let opcode = u16::from_be_bytes([
+                    machine_state.peek(address),
+                    machine_state.peek(address.wrapping_add(1)),
+                ]);
+                let _ = writeln!(
+                    output,
+                    "{address:03X}  {opcode:04X}  {}",
+                    disasm::disassemble(opcode, machine_state.system())
+                );
+            }
+        }
+        Command::Break(breakpoint) => {
+            breakpoints.toggle(breakpoint)?;
+            for breakpoint in breakpoints.iter() {
+                let _ = write!(output, "{:03X}", breakpoint.address);
+                if let Some(condition) = &breakpoint.condition {
+                    let _ = write!(output, " if {condition}");
+                }
+                let _ = writeln!(output, " ({} hits)", breakpoint.hits);
+            }
+        }
+        Command::Save(path) => {
+            savestate::save_to(path, machine_state).map_err(|err| err.to_string())?
+        }
+        Command::Load(path) => {
+            savestate::load_from(path, machine_state).map_err(|err| err.to_string())?
+        }
+        Command::Help => output.push_str(HELP),
+        Command::Step(_) | Command::Continue | Command::Pause | Command::Quit => {}
+    }
+    Ok(output)
+}
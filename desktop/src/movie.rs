@@ -0,0 +1,257 @@
+use crate::{Error, config};
+use rand::{SeedableRng, rngs::StdRng};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+/// Keys to hold from a frame onwards, as hex digits
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct InputChange {
+    pub frame: u32,
+    pub keys: Vec<String>,
+}
+
+impl InputChange {
+    fn new(frame: u32, held_keys: u16) -> Self {
+        Self {
+            frame,
+            keys: (0..16)
+                .filter(|key| held_keys & (0b1 << key) != 0)
+                .map(|key| format!("{key:X}"))
+                .collect(),
+        }
+    }
+
+    /// The keys as a bitmask, with bit n set if key n is held
+    fn held_keys(&self) -> Result<u16, Error> {
+        self.keys.iter().try_fold(0, |keys, key| {
+            u8::from_str_radix(key, 16)
+                .ok()
+                .filter(|&key| key < 16)
+                .map(|key| keys | 0b1 << key)
+                .ok_or_else(|| Error::Input(format!("{key:?} is not a CHIP-8 key")))
+        })
+    }
+}
+
+/// Everything needed to replay a run exactly, starting from the ROM being loaded
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Movie {
+    /// Hash of the ROM, to warn when a movie is played on a different one
+    pub rom: String,
+    pub seed: u64,
+    pub ipf: u32,
+    /// Length of the movie in frames
+    pub frames: u32,
+    /// Times part of the movie was recorded again, by loading a save state while recording
+    #[serde(default)]
+    pub rerecords: u32,
+    pub input: Vec<InputChange>,
+}
+
+impl Movie {
+    pub fn new(program: &[u8], seed: u64, ipf: u32) -> Self {
+        Self {
+            rom: format!("{:016x}", config::hash(program)),
+            seed,
+            ipf,
+            frames: 0,
+            rerecords: 0,
+            input: Vec::new(),
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Self, Error> {
+        serde_json::from_str(&std::fs::read_to_string(path)?)
+            .map_err(|err| Error::Input(err.to_string()))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), Error> {
+        let json = serde_json::to_string_pretty(self).expect("Movies can always be serialised");
+        Ok(std::fs::write(path, json)?)
+    }
+
+    /// Whether the movie was recorded on this program
+    pub fn matches(&self, program: &[u8]) -> bool {
+        self.rom == format!("{:016x}", config::hash(program))
+    }
+}
+
+/// Random numbers for a frame of a movie
+///
+/// Each frame gets its own, so rewinding while recording doesn't change the numbers replayed.
+pub fn frame_rng(seed: u64, frame: u32) -> StdRng {
+    StdRng::seed_from_u64(seed.wrapping_add(frame as u64))
+}
+
+/// Read a list of input changes into the held keys for each frame they change on, in order
+pub fn input(changes: &[InputChange]) -> Result<Vec<(u32, u16)>, Error> {
+    let mut input = changes
+        .iter()
+        .map(|change| Ok((change.frame, change.held_keys()?)))
+        .collect::<Result<Vec<_>, Error>>()?;
+    input.sort_by_key(|&(frame, _)| frame);
+    Ok(input)
+}
+
+/// A movie being recorded or played back
+pub enum Session {
+    Recording {
+        movie: Movie,
+        path: PathBuf,
+        frame: u32,
+        /// The frame each save state slot was saved on, so recording can continue from there
+        slots: BTreeMap<u8, u32>,
+    },
+    Playing {
+        seed: u64,
+        frames: u32,
+        input: Vec<(u32, u16)>,
+        frame: u32,
+        held_keys: u16,
+    },
+}
+
+impl Session {
+    pub fn record(movie: Movie, path: PathBuf) -> Self {
+        Self::Recording {
+            movie,
+            path,
+            frame: 0,
+            slots: BTreeMap::new(),
+        }
+    }
+
+    pub fn play(movie: &Movie) -> Result<Self, Error> {
+        Ok(Self::Playing {
+            seed: movie.seed,
+            frames: movie.frames,
+            input: input(&movie.input)?,
+            frame: 0,
+            held_keys: 0,
+        })
+    }
+
+    /// The keys to hold for the next frame, given the ones held by the player,
+    /// and the random numbers to use during it
+    ///
+    /// Recordings remember the player's keys, playback replaces them until it ends.
+    pub fn frame(&mut self, held_keys: u16) -> (u16, StdRng) {
+        match self {
+            Self::Recording { movie, frame, .. } => {
+                let previous = movie.input.last().map(InputChange::held_keys);
+                if previous.is_none_or(|previous| previous.ok() != Some(held_keys)) {
+                    movie.input.push(InputChange::new(*frame, held_keys));
+                }
+                *frame += 1;
+                (held_keys, frame_rng(movie.seed, *frame - 1))
+            }
+            Self::Playing {
+                seed,
+                input,
+                frame,
+                held_keys,
+                ..
+            } => {
+                for &(_, keys) in input.iter().filter(|&&(change, _)| change == *frame) {
+                    *held_keys = keys;
+                }
+                *frame += 1;
+                (*held_keys, frame_rng(*seed, *frame - 1))
+            }
+        }
+    }
+
+    /// Go back a frame along with rewinding, forgetting input recorded after it
+    pub fn rewind(&mut self) {
+        match self {
+            Self::Recording {
+                movie,
+                frame,
+                slots,
+                ..
+            } => {
+                *frame = frame.saturating_sub(1);
+                movie.input.retain(|change| change.frame < *frame);
+                slots.retain(|_, saved| *saved <= *frame);
+            }
+            Self::Playing {
+                input,
+                frame,
+                held_keys,
+                ..
+            } => {
+                *frame = frame.saturating_sub(1);
+                *held_keys = input
+                    .iter()
+                    .rev()
+                    .find(|&&(change, _)| change < *frame)
+                    .map_or(0, |&(_, keys)| keys);
+            }
+        }
+    }
+
+    /// Remember the frame a save state slot was saved on while recording
+    pub fn saved(&mut self, slot: u8) {
+        if let Self::Recording { frame, slots, .. } = self {
+            slots.insert(slot, *frame);
+        }
+    }
+
+    /// Whether a save state slot can be loaded without the movie losing track of the game,
+    /// which while recording means the slot was saved during the recording
+    pub fn can_load(&self, slot: u8) -> bool {
+        match self {
+            Self::Recording { slots, .. } => slots.contains_key(&slot),
+            Self::Playing { .. } => true,
+        }
+    }
+
+    /// Continue recording from the frame a save state slot was saved on,
+    /// forgetting input recorded after it
+    pub fn rerecord(&mut self, slot: u8) {
+        if let Self::Recording {
+            movie,
+            frame,
+            slots,
+            ..
+        } = self
+            && let Some(&saved) = slots.get(&slot)
+        {
+            *frame = saved;
+            movie.input.retain(|change| change.frame < saved);
+            // Slots saved later belong to input that's gone
+            slots.retain(|_, later| *later <= saved);
+            movie.rerecords += 1;
+        }
+    }
+
+    /// Whether playback has reached the end of the movie
+    pub fn finished(&self) -> bool {
+        match self {
+            Self::Recording { .. } => false,
+            Self::Playing { frames, frame, .. } => frame >= frames,
+        }
+    }
+
+    /// Stop the session, saving the movie if recording
+    pub fn finish(self) -> Result<Option<PathBuf>, Error> {
+        match self {
+            Self::Recording {
+                mut movie,
+                path,
+                frame,
+                ..
+            } => {
+                movie.frames = frame;
+                movie.save(&path)?;
+                Ok(Some(path))
+            }
+            Self::Playing { .. } => Ok(None),
+        }
+    }
+}
@@ -0,0 +1,59 @@
+use crate::{palette::Colours, text};
+use sdl3::{render::Canvas, video::Window};
+use std::time::{Duration, Instant};
+
+/// How long messages are shown for by default
+const DURATION: Duration = Duration::from_secs(2);
+
+/// A message shown briefly in the corner of the display, so hotkeys have visible feedback
+#[derive(Debug, Default)]
+pub struct Osd {
+    message: Option<(String, Instant)>,
+}
+
+impl Osd {
+    pub fn show(&mut self, message: &str) {
+        self.show_for(message, DURATION);
+    }
+
+    /// Show `message` in place of any other, until `duration` has passed
+    pub fn show_for(&mut self, message: &str, duration: Duration) {
+        self.message = Some((message.to_uppercase(), Instant::now() + duration));
+    }
+
+    pub fn is_shown(&self) -> bool {
+        self.message.is_some()
+    }
+
+    /// Hide the message once its time is up, returning whether it was hidden
+    pub fn expire(&mut self) -> bool {
+        let expired = self
+            .message
+            .as_ref()
+            .is_some_and(|(_, until)| Instant::now() >= *until);
+        if expired {
+            self.message = None;
+        }
+        expired
+    }
+
+    pub fn draw(
+        &self,
+        canvas: &mut Canvas<Window>,
+        colours: Colours,
+        (_, height): (usize, usize),
+    ) -> Result<(), sdl3::Error> {
+        let Some((message, _)) = &self.message else {
+            return Ok(());
+        };
+        text::draw_boxed(
+            canvas,
+            1.,
+            (height - text::GLYPH_HEIGHT - 3) as f32,
+            1.,
+            message,
+            colours.on,
+            colours.off,
+        )
+    }
+}
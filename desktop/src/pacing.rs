@@ -0,0 +1,185 @@
+use crate::{Error, debug::font_scale, palette::Colours, text};
+use sdl3::{
+    pixels::Color,
+    rect::FRect,
+    render::{BlendMode, Canvas},
+    video::Window,
+};
+use std::{
+    fmt,
+    time::{Duration, Instant},
+};
+
+/// Number of 1 ms buckets, the last one also counts anything longer
+const BUCKETS: usize = 34;
+
+/// How often durations fell into each millisecond
+#[derive(Debug, Clone)]
+pub struct Histogram {
+    counts: [u32; BUCKETS],
+    total: Duration,
+    max: Duration,
+}
+
+impl Default for Histogram {
+    fn default() -> Self {
+        Self {
+            counts: [0; BUCKETS],
+            total: Duration::ZERO,
+            max: Duration::ZERO,
+        }
+    }
+}
+
+impl Histogram {
+    pub fn record(&mut self, duration: Duration) {
+        let bucket = (duration.as_millis() as usize).min(BUCKETS - 1);
+        self.counts[bucket] += 1;
+        self.total += duration;
+        self.max = self.max.max(duration);
+    }
+
+    fn count(&self) -> u32 {
+        self.counts.iter().sum()
+    }
+
+    fn mean(&self) -> Duration {
+        self.total.checked_div(self.count()).unwrap_or_default()
+    }
+
+    /// The millisecond that 99% of durations were shorter than
+    fn percentile_99(&self) -> usize {
+        let target = self.count() - self.count() / 100;
+        let mut seen = 0;
+        self.counts
+            .iter()
+            .position(|&count| {
+                seen += count;
+                seen >= target
+            })
+            .map_or(0, |bucket| bucket + 1)
+    }
+}
+
+impl fmt::Display for Histogram {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "avg {:.1} ms, 99% < {} ms, max {:.1} ms",
+            self.mean().as_secs_f64() * 1000.,
+            self.percentile_99(),
+            self.max.as_secs_f64() * 1000.,
+        )
+    }
+}
+
+/// Timings of the main loop, for diagnosing stutter
+#[derive(Debug, Clone, Default)]
+pub struct Pacing {
+    /// Time between emulated frames
+    pub frame: Histogram,
+    /// Time spent emulating, each time the display is drawn
+    pub emulation: Histogram,
+    /// Time spent drawing and presenting the display
+    pub render: Histogram,
+    /// Frames that came late enough to miss a refresh
+    pub dropped: u32,
+    last_frame: Option<Instant>,
+}
+
+impl Pacing {
+    /// Record that frames were emulated at `now`, `period` after the last ones were due
+    pub fn frame(&mut self, now: Instant, period: Duration) {
+        if let Some(last_frame) = self.last_frame {
+            let interval = now - last_frame;
+            self.frame.record(interval);
+            if interval > period * 3 / 2 {
+                self.dropped += (interval.as_secs_f64() / period.as_secs_f64()).round() as u32 - 1;
+            }
+        }
+        self.last_frame = Some(now);
+    }
+
+    /// Forget the time of the last frame, so pausing isn't counted as a stutter
+    pub fn pause(&mut self) {
+        self.last_frame = None;
+    }
+
+    /// Draw the timings, with a chart of how long frames took
+    pub fn draw(
+        &self,
+        canvas: &mut Canvas<Window>,
+        colours: Colours,
+        (width, height): (usize, usize),
+    ) -> Result<(), Error> {
+        let scale = font_scale(canvas, (width, height))?;
+        let line_height = (text::GLYPH_HEIGHT + 2) as f32 * scale;
+
+        canvas.set_blend_mode(BlendMode::Blend);
+        let off = colours.off;
+        canvas.set_draw_color(Color::RGBA(off.r, off.g, off.b, 0xd0));
+        canvas.fill_rect(FRect::new(0., 0., width as f32, height as f32))?;
+        canvas.set_blend_mode(BlendMode::None);
+
+        let lines = [
+            format!("FRAME    {}", self.frame).to_uppercase(),
+            format!("EMULATE  {}", self.emulation).to_uppercase(),
+            format!("RENDER   {}", self.render).to_uppercase(),
+            format!("DROPPED  {}", self.dropped),
+        ];
+        for (i, line) in lines.iter().enumerate() {
+            text::draw(
+                canvas,
+                scale,
+                scale + i as f32 * line_height,
+                scale,
+                line,
+                colours.on,
+            )?;
+        }
+
+        // One bar per millisecond of frame time, scaled to the most common
+        let top = (lines.len() + 1) as f32 * line_height;
+        let bottom = height as f32 - 2. * line_height;
+        let bar_width = (width as f32 - 2. * scale) / BUCKETS as f32;
+        let most = self
+            .frame
+            .counts
+            .iter()
+            .copied()
+            .max()
+            .unwrap_or_default()
+            .max(1);
+        canvas.set_draw_color(colours.on);
+        for (bucket, &count) in self.frame.counts.iter().enumerate() {
+            let bar_height = (bottom - top) * count as f32 / most as f32;
+            canvas.fill_rect(FRect::new(
+                scale + bucket as f32 * bar_width,
+                bottom - bar_height,
+                (bar_width - scale).max(scale),
+                bar_height,
+            ))?;
+        }
+        for milliseconds in [0, 16, 33] {
+            text::draw(
+                canvas,
+                scale + milliseconds as f32 * bar_width,
+                bottom + scale,
+                scale,
+                &format!("{milliseconds}"),
+                colours.on,
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for Pacing {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Frame time: {}", self.frame)?;
+        writeln!(f, "Emulation time: {}", self.emulation)?;
+        writeln!(f, "Render time: {}", self.render)?;
+        write!(f, "Dropped frames: {}", self.dropped)
+    }
+}
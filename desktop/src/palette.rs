@@ -0,0 +1,82 @@
+use clap::ValueEnum;
+use sdl3::pixels::Color;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Palette {
+    /// Dark pixels on a grey LCD
+    #[default]
+    Lcd,
+    /// Dark green pixels on a pale green screen, like the original Game Boy
+    ClassicGreen,
+    /// Amber pixels on black, like a monochrome monitor
+    Amber,
+    /// White pixels on black
+    HighContrast,
+}
+
+/// The colours of pixels that are off and on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Colours {
+    pub off: Color,
+    pub on: Color,
+}
+
+impl Palette {
+    pub const ALL: [Palette; 4] = [
+        Palette::Lcd,
+        Palette::ClassicGreen,
+        Palette::Amber,
+        Palette::HighContrast,
+    ];
+
+    pub fn colours(self) -> Colours {
+        match self {
+            Palette::Lcd => Colours {
+                off: Color::RGB(0x8f, 0x91, 0x85),
+                on: Color::RGB(0x11, 0x1d, 0x2b),
+            },
+            Palette::ClassicGreen => Colours {
+                off: Color::RGB(0x9b, 0xbc, 0x0f),
+                on: Color::RGB(0x0f, 0x38, 0x0f),
+            },
+            Palette::Amber => Colours {
+                off: Color::RGB(0x1a, 0x0f, 0x00),
+                on: Color::RGB(0xff, 0xb0, 0x00),
+            },
+            Palette::HighContrast => Colours {
+                off: Color::RGB(0x00, 0x00, 0x00),
+                on: Color::RGB(0xff, 0xff, 0xff),
+            },
+        }
+    }
+}
+
+/// A colour written as `#rrggbb`, the `#` being optional
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct HexColour(pub Color);
+
+impl FromStr for HexColour {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let hex = s.strip_prefix('#').unwrap_or(s);
+        if hex.len() != 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(format!("{s:?} is not a colour in the form #rrggbb"));
+        }
+
+        let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).unwrap();
+        Ok(Self(Color::RGB(channel(0), channel(2), channel(4))))
+    }
+}
+
+impl TryFrom<String> for HexColour {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
@@ -0,0 +1,51 @@
+use crate::palette::Colours;
+use rs_chip8_core::{DISPLAY_HEIGHT, DISPLAY_WIDTH, Display};
+use sdl3::pixels::Color;
+
+/// Fraction of its brightness an unlit pixel keeps each frame
+const DECAY: f32 = 0.6;
+
+/// Below this, a pixel is drawn as fully off
+const CUTOFF: f32 = 0.05;
+
+/// Simulates phosphor persistence, so pixels fade out instead of turning off instantly
+///
+/// This hides most of the flicker caused by games erasing and redrawing sprites with XOR.
+pub struct Phosphor {
+    intensity: Box<[[f32; DISPLAY_HEIGHT]; DISPLAY_WIDTH]>,
+}
+
+impl Phosphor {
+    pub fn new() -> Self {
+        Self {
+            intensity: Box::new([[0.; DISPLAY_HEIGHT]; DISPLAY_WIDTH]),
+        }
+    }
+
+    /// Blend the latest frame into the persisted image
+    pub fn update(&mut self, display: &Display) {
+        for (x, column) in self.intensity.iter_mut().enumerate() {
+            for (y, intensity) in column.iter_mut().enumerate() {
+                *intensity = if display.pixel(x, y) {
+                    1.
+                } else if *intensity * DECAY < CUTOFF {
+                    0.
+                } else {
+                    *intensity * DECAY
+                };
+            }
+        }
+    }
+
+    /// Colour of the pixel at `(x, y)`, between the off and on colours
+    pub fn colour(&self, x: usize, y: usize, colours: Colours) -> Color {
+        let intensity = self.intensity[x][y];
+
+        let blend = |off: u8, on: u8| (off as f32 + (on as f32 - off as f32) * intensity) as u8;
+        Color::RGB(
+            blend(colours.off.r, colours.on.r),
+            blend(colours.off.g, colours.on.g),
+            blend(colours.off.b, colours.on.b),
+        )
+    }
+}
@@ -0,0 +1,62 @@
+use crate::{Error, library};
+use std::path::{Path, PathBuf};
+
+/// A ROM in a playlist, with the movie to play as a demo if there is one
+#[derive(Debug, Clone)]
+pub struct Entry {
+    pub rom: PathBuf,
+    pub movie: Option<PathBuf>,
+}
+
+/// ROMs cycled through one after another, for showing games off unattended
+///
+/// Demos are movies saved next to a ROM, named after it without its extension,
+/// so `game.ch8` plays `game.movie.json`.
+#[derive(Debug)]
+pub struct Playlist {
+    entries: Vec<Entry>,
+    next: usize,
+}
+
+impl Playlist {
+    /// Every ROM in a directory, or the ones listed in a file, one path per line
+    ///
+    /// Paths in a file are relative to it, blank lines and lines starting with `#` are skipped.
+    pub fn load(path: &Path) -> Result<Self, Error> {
+        let roms = if path.is_dir() {
+            library::scan(&[path.to_path_buf()])
+        } else {
+            let directory = path.parent().unwrap_or(Path::new(""));
+            std::fs::read_to_string(path)?
+                .lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                .map(|line| directory.join(line))
+                .collect()
+        };
+        if roms.is_empty() {
+            return Err(Error::Playlist(format!(
+                "{} has no ROMs in it",
+                path.display()
+            )));
+        }
+
+        let entries = roms
+            .into_iter()
+            .map(|rom| {
+                let mut file_name = rom.file_stem().unwrap_or_default().to_os_string();
+                file_name.push(".movie.json");
+                let movie = Some(rom.with_file_name(file_name)).filter(|movie| movie.is_file());
+                Entry { rom, movie }
+            })
+            .collect();
+        Ok(Self { entries, next: 0 })
+    }
+
+    /// The next ROM to run, starting again from the first after the last
+    pub fn next(&mut self) -> Entry {
+        let entry = self.entries[self.next].clone();
+        self.next = (self.next + 1) % self.entries.len();
+        entry
+    }
+}
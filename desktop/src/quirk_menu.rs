@@ -0,0 +1,108 @@
+use crate::{Error, debug::font_scale, palette::Colours, text};
+use rs_chip8_core::{MachineState, Quirks};
+use sdl3::{
+    keyboard::Scancode,
+    pixels::Color,
+    rect::FRect,
+    render::{BlendMode, Canvas},
+    video::Window,
+};
+
+/// Names of the quirks, in the order they're listed
+const NAMES: [&str; 4] = [
+    "LOGIC RESETS VF",
+    "SHIFT USES VY",
+    "JUMP USES VX",
+    "MEMORY INCREMENTS I",
+];
+
+fn quirk(quirks: &mut Quirks, index: usize) -> &mut bool {
+    match index {
+        0 => &mut quirks.logic_resets_flag,
+        1 => &mut quirks.shift_uses_vy,
+        2 => &mut quirks.jump_uses_vx,
+        _ => &mut quirks.memory_increments_index,
+    }
+}
+
+/// Toggles quirks while the program runs, to find the combination a misbehaving ROM needs
+///
+/// Up and down select a quirk and enter or space toggles it.
+/// Programs often set up state that depends on the quirks, so R restarts them.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct QuirkMenu {
+    selected: usize,
+}
+
+impl QuirkMenu {
+    /// Handle a key press, returning whether the menu used it
+    pub fn key_down(&mut self, scancode: Scancode, machine_state: &mut MachineState) -> bool {
+        match scancode {
+            Scancode::Up => self.selected = self.selected.saturating_sub(1),
+            Scancode::Down => self.selected = (self.selected + 1).min(NAMES.len() - 1),
+            Scancode::Return | Scancode::KpEnter | Scancode::Space => {
+                let mut quirks = machine_state.quirks();
+                let quirk = quirk(&mut quirks, self.selected);
+                *quirk = !*quirk;
+                machine_state.set_quirks(quirks);
+            }
+            _ => return false,
+        }
+        true
+    }
+
+    /// Draw the quirks, marking the ones changed from the system's defaults
+    pub fn draw(
+        &self,
+        canvas: &mut Canvas<Window>,
+        machine_state: &MachineState,
+        colours: Colours,
+        (width, height): (usize, usize),
+    ) -> Result<(), Error> {
+        let scale = font_scale(canvas, (width, height))?;
+        let line_height = (text::GLYPH_HEIGHT + 2) as f32 * scale;
+
+        canvas.set_blend_mode(BlendMode::Blend);
+        let off = colours.off;
+        canvas.set_draw_color(Color::RGBA(off.r, off.g, off.b, 0xd0));
+        canvas.fill_rect(FRect::new(0., 0., width as f32, height as f32))?;
+        canvas.set_blend_mode(BlendMode::None);
+
+        text::draw(canvas, scale, scale, scale, "QUIRKS", colours.on)?;
+
+        let mut quirks = machine_state.quirks();
+        let mut defaults = Quirks::for_system(machine_state.system());
+        for (i, name) in NAMES.iter().enumerate() {
+            let enabled = *quirk(&mut quirks, i);
+            let line = format!(
+                "{} [{}] {name}{}",
+                if i == self.selected { ">" } else { " " },
+                if enabled { "X" } else { " " },
+                if enabled == *quirk(&mut defaults, i) {
+                    ""
+                } else {
+                    " (CHANGED)"
+                },
+            );
+            text::draw(
+                canvas,
+                scale,
+                scale + (i + 2) as f32 * line_height,
+                scale,
+                &line,
+                colours.on,
+            )?;
+        }
+
+        text::draw(
+            canvas,
+            scale,
+            height as f32 - line_height,
+            scale,
+            "UP/DOWN: SELECT  ENTER: TOGGLE  R: RESTART  SHIFT+F8: HIDE",
+            colours.on,
+        )?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,47 @@
+use crate::{Error, config};
+use std::path::{Path, PathBuf};
+
+/// ROMs kept in the recent list
+const MAX_RECENT: usize = 10;
+
+/// Where the recent list is stored, one path per line, in the platform's data directory
+fn path() -> Option<PathBuf> {
+    config::project_dirs().map(|dirs| dirs.data_dir().join("recent.txt"))
+}
+
+/// The ROMs opened most recently, newest first, leaving out any that have been moved or deleted
+pub fn load() -> Vec<PathBuf> {
+    let Some(contents) = path().and_then(|path| std::fs::read_to_string(path).ok()) else {
+        return Vec::new();
+    };
+    contents
+        .lines()
+        .map(PathBuf::from)
+        .filter(|rom| rom.is_file())
+        .collect()
+}
+
+/// Move the ROM to the top of the recent list
+pub fn add(rom: &Path) -> Result<(), Error> {
+    let Some(path) = path() else {
+        return Ok(());
+    };
+    // Stored in full, so the same ROM reached by another path is only listed once
+    let rom = std::fs::canonicalize(rom)?;
+
+    let mut roms = load();
+    roms.retain(|recent| *recent != rom);
+    roms.insert(0, rom);
+    roms.truncate(MAX_RECENT);
+
+    let mut contents = String::new();
+    for rom in roms {
+        contents.push_str(&rom.to_string_lossy());
+        contents.push('\n');
+    }
+    if let Some(directory) = path.parent() {
+        std::fs::create_dir_all(directory)?;
+    }
+    std::fs::write(path, contents)?;
+    Ok(())
+}
@@ -0,0 +1,144 @@
+use rs_chip8_core::{DISPLAY_HEIGHT, DISPLAY_WIDTH, MachineState};
+use serde::Deserialize;
+use serde_json::{Value, json};
+use std::{
+    io::{BufRead, BufReader, Write},
+    net::{Ipv4Addr, TcpListener, TcpStream},
+    sync::mpsc::{self, Receiver, Sender},
+};
+
+/// A request from a remote client, sent as a line of JSON such as
+/// `{"command": "peek", "address": 512, "length": 4}`
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "command", rename_all = "snake_case", deny_unknown_fields)]
+pub enum Request {
+    Pause,
+    Resume,
+    /// Pause, then execute instructions one at a time
+    Step {
+        #[serde(default = "one")]
+        count: u32,
+    },
+    Peek {
+        address: u16,
+        #[serde(default = "one")]
+        length: u16,
+    },
+    Poke {
+        address: u16,
+        bytes: Vec<u8>,
+    },
+    Registers,
+    /// The display as rows of `0` and `1` characters
+    Screenshot,
+}
+
+fn one<T: From<u8>>() -> T {
+    T::from(1)
+}
+
+/// A request along with where to send its response
+pub type Message = (Request, Sender<Value>);
+
+/// Accept clients on `port` of the loopback interface, passing their requests on
+///
+/// Each line a client sends is answered with a line of JSON,
+/// with `"ok"` false and an `"error"` if the request failed.
+pub fn listen(port: u16) -> std::io::Result<Receiver<Message>> {
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, port))?;
+    let (sender, receiver) = mpsc::channel();
+    std::thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let sender = sender.clone();
+            std::thread::spawn(move || serve(stream, sender));
+        }
+    });
+    Ok(receiver)
+}
+
+fn serve(stream: TcpStream, sender: Sender<Message>) {
+    let Ok(mut writer) = stream.try_clone() else {
+        return;
+    };
+    for line in BufReader::new(stream).lines() {
+        let Ok(line) = line else {
+            return;
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let response = match serde_json::from_str(&line) {
+            Ok(request) => {
+                let (respond, response) = mpsc::channel();
+                // The emulator has closed if either end of the channel is gone
+                if sender.send((request, respond)).is_err() {
+                    return;
+                }
+                let Ok(response) = response.recv() else {
+                    return;
+                };
+                response
+            }
+            Err(err) => json!({ "ok": false, "error": err.to_string() }),
+        };
+        if writeln!(writer, "{response}").is_err() {
+            return;
+        }
+    }
+}
+
+/// Answer a request, using `step` to execute instructions
+pub fn handle(
+    request: Request,
+    machine_state: &mut MachineState,
+    paused: &mut bool,
+    step: impl FnOnce(&mut MachineState, u32) -> Result<(), String>,
+) -> Value {
+    match request {
+        Request::Pause => *paused = true,
+        Request::Resume => *paused = false,
+        Request::Step { count } => {
+            *paused = true;
+            if let Err(err) = step(machine_state, count) {
+                return json!({ "ok": false, "error": err });
+            }
+            return json!({ "ok": true, "pc": machine_state.program_counter() });
+        }
+        Request::Peek { address, length } => {
+            let bytes = (0..length)
+                .map(|offset| machine_state.peek(address.wrapping_add(offset)))
+                .collect::<Vec<_>>();
+            return json!({ "ok": true, "bytes": bytes });
+        }
+        Request::Poke { address, bytes } => {
+            for (offset, byte) in (0..).zip(bytes) {
+                machine_state.poke(address.wrapping_add(offset), byte);
+            }
+        }
+        Request::Registers => {
+            return json!({
+                "ok": true,
+                "pc": machine_state.program_counter(),
+                "i": machine_state.index_register(),
+                "v": machine_state.registers(),
+                "stack": machine_state.stack(),
+                "delay_timer": machine_state.delay_timer(),
+                "sound_timer": machine_state.sound_timer(),
+                "paused": *paused,
+            });
+        }
+        Request::Screenshot => {
+            let display = machine_state.display();
+            let rows = (0..DISPLAY_HEIGHT)
+                .map(|y| {
+                    (0..DISPLAY_WIDTH)
+                        .map(|x| if display.pixel(x, y) { '1' } else { '0' })
+                        .collect::<String>()
+                })
+                .collect::<Vec<_>>();
+            return json!({ "ok": true, "high_res": display.is_high_res(), "rows": rows });
+        }
+    }
+    json!({ "ok": true })
+}
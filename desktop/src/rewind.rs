@@ -0,0 +1,41 @@
+use rs_chip8_core::MachineState;
+use std::collections::VecDeque;
+
+/// Seconds of history kept for rewinding
+const REWIND_SECONDS: usize = 10;
+
+/// Per-frame snapshots of the machine state, oldest first
+pub struct Rewind {
+    snapshots: VecDeque<MachineState>,
+}
+
+impl Rewind {
+    pub fn new() -> Self {
+        Self {
+            snapshots: VecDeque::with_capacity(REWIND_SECONDS * 60),
+        }
+    }
+
+    /// Record the state at the start of a frame
+    pub fn push(&mut self, machine_state: &MachineState) {
+        if self.snapshots.len() == REWIND_SECONDS * 60 {
+            self.snapshots.pop_front();
+        }
+        self.snapshots.push_back(machine_state.clone());
+    }
+
+    /// Step one frame back, returning `false` once the history has run out
+    pub fn pop(&mut self, machine_state: &mut MachineState) -> bool {
+        match self.snapshots.pop_back() {
+            Some(snapshot) => {
+                *machine_state = snapshot;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.snapshots.clear();
+    }
+}
@@ -0,0 +1,91 @@
+use clap::ValueEnum;
+use rs_chip8_core::{DISPLAY_HEIGHT, DISPLAY_WIDTH};
+use serde::Deserialize;
+
+/// The COSMAC VIP keypad layout
+pub const KEYPAD: [[usize; 4]; 4] = [
+    [0x1, 0x2, 0x3, 0xC],
+    [0x4, 0x5, 0x6, 0xD],
+    [0x7, 0x8, 0x9, 0xE],
+    [0xA, 0x0, 0xB, 0xF],
+];
+
+/// Clockwise rotation of the display
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum, Deserialize)]
+#[serde(try_from = "u32")]
+pub enum Rotation {
+    #[default]
+    #[value(name = "0")]
+    None,
+    #[value(name = "90")]
+    Quarter,
+    #[value(name = "180")]
+    Half,
+    #[value(name = "270")]
+    ThreeQuarters,
+}
+
+impl TryFrom<u32> for Rotation {
+    type Error = String;
+
+    fn try_from(degrees: u32) -> Result<Self, Self::Error> {
+        match degrees {
+            0 => Ok(Rotation::None),
+            90 => Ok(Rotation::Quarter),
+            180 => Ok(Rotation::Half),
+            270 => Ok(Rotation::ThreeQuarters),
+            _ => Err(format!(
+                "{degrees} is not a rotation of 0, 90, 180 or 270 degrees"
+            )),
+        }
+    }
+}
+
+impl Rotation {
+    fn quarter_turns(self) -> usize {
+        match self {
+            Rotation::None => 0,
+            Rotation::Quarter => 1,
+            Rotation::Half => 2,
+            Rotation::ThreeQuarters => 3,
+        }
+    }
+
+    pub fn degrees(self) -> f64 {
+        self.quarter_turns() as f64 * 90.
+    }
+
+    pub fn next(self) -> Self {
+        match self {
+            Rotation::None => Rotation::Quarter,
+            Rotation::Quarter => Rotation::Half,
+            Rotation::Half => Rotation::ThreeQuarters,
+            Rotation::ThreeQuarters => Rotation::None,
+        }
+    }
+
+    /// Size of the rotated display
+    pub fn logical_size(self) -> (usize, usize) {
+        match self {
+            Rotation::None | Rotation::Half => (DISPLAY_WIDTH, DISPLAY_HEIGHT),
+            Rotation::Quarter | Rotation::ThreeQuarters => (DISPLAY_HEIGHT, DISPLAY_WIDTH),
+        }
+    }
+
+    /// The key the game sees when `key` is pressed, so directions match the rotated display
+    ///
+    /// The keypad is rotated around 5, the centre of the 2/4/6/8 directional keys,
+    /// with keys that fall off one edge wrapping around to the other.
+    pub fn rotate_key(self, key: usize) -> usize {
+        let (mut row, mut column) = (0..16)
+            .map(|i| (i / 4, i % 4))
+            .find(|&(row, column)| KEYPAD[row][column] == key)
+            .expect("Keys are in the range 0x0 to 0xF");
+
+        for _ in 0..self.quarter_turns() {
+            (row, column) = ((2 + 4 - column) % 4, row);
+        }
+
+        KEYPAD[row][column]
+    }
+}
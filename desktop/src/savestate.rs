@@ -0,0 +1,61 @@
+use crate::Error;
+use rs_chip8_core::{MachineState, STATE_SIZE};
+use std::path::{Path, PathBuf};
+
+/// Numbered save state slots, stored as `<slot>.state` in the ROM's data folder,
+/// and the session saved on quit as `session.state`
+pub struct SaveStates {
+    directory: PathBuf,
+}
+
+impl SaveStates {
+    /// Use the ROM's data folder
+    pub fn new(data_dir: PathBuf) -> Self {
+        Self {
+            directory: data_dir,
+        }
+    }
+
+    fn path(&self, name: impl std::fmt::Display) -> PathBuf {
+        self.directory.join(format!("{name}.state"))
+    }
+
+    pub fn save(&self, slot: u8, machine_state: &MachineState) -> Result<(), Error> {
+        save_to(&self.path(slot), machine_state)
+    }
+
+    pub fn load(&self, slot: u8, machine_state: &mut MachineState) -> Result<(), Error> {
+        load_from(&self.path(slot), machine_state)
+    }
+
+    pub fn has_session(&self) -> bool {
+        self.path("session").is_file()
+    }
+
+    pub fn save_session(&self, machine_state: &MachineState) -> Result<(), Error> {
+        save_to(&self.path("session"), machine_state)
+    }
+
+    /// Resume the saved session, removing it so an old session isn't offered again
+    pub fn load_session(&self, machine_state: &mut MachineState) -> Result<(), Error> {
+        load_from(&self.path("session"), machine_state)?;
+        std::fs::remove_file(self.path("session"))?;
+        Ok(())
+    }
+}
+
+pub fn save_to(path: &Path, machine_state: &MachineState) -> Result<(), Error> {
+    if let Some(directory) = path.parent() {
+        std::fs::create_dir_all(directory)?;
+    }
+    let mut buffer = [0; STATE_SIZE];
+    machine_state.save_state(&mut buffer);
+    std::fs::write(path, buffer)?;
+    Ok(())
+}
+
+pub fn load_from(path: &Path, machine_state: &mut MachineState) -> Result<(), Error> {
+    let buffer = std::fs::read(path)?;
+    machine_state.load_state(&buffer)?;
+    Ok(())
+}
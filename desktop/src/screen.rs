@@ -0,0 +1,86 @@
+use crate::{Error, rotation::Rotation};
+use rs_chip8_core::{DISPLAY_HEIGHT, DISPLAY_WIDTH};
+use sdl3::{
+    pixels::{Color, PixelFormat},
+    rect::{FRect, Rect},
+    render::{Canvas, ScaleMode, Texture, TextureCreator, TextureValueError},
+    sys::pixels::SDL_PixelFormat,
+    video::{Window, WindowContext},
+};
+
+/// Draws the display by streaming it to a texture, which is then stretched over the canvas
+pub struct Screen<'a> {
+    texture: Texture<'a>,
+}
+
+impl<'a> Screen<'a> {
+    pub fn new(texture_creator: &'a TextureCreator<WindowContext>) -> Result<Self, Error> {
+        let mut texture = match texture_creator.create_texture_streaming(
+            PixelFormat::from(SDL_PixelFormat::RGB24),
+            DISPLAY_WIDTH as u32,
+            DISPLAY_HEIGHT as u32,
+        ) {
+            Ok(texture) => texture,
+            Err(TextureValueError::SdlError(err)) => return Err(err.into()),
+            Err(err) => panic!("Expected texture dimensions to be valid, but {err}"),
+        };
+        texture.set_scale_mode(ScaleMode::Nearest);
+
+        Ok(Self { texture })
+    }
+
+    /// Upload the colour of every pixel in the rows set in the `rows` bitmask
+    pub fn update(
+        &mut self,
+        rows: u64,
+        pixel: impl Fn(usize, usize) -> Color,
+    ) -> Result<(), Error> {
+        let mut y = 0;
+        while y < DISPLAY_HEIGHT {
+            if (rows >> y) & 1 == 0 {
+                y += 1;
+                continue;
+            }
+
+            // Upload each run of changed rows in one go
+            let run = (y..DISPLAY_HEIGHT)
+                .take_while(|&row| (rows >> row) & 1 == 1)
+                .count();
+            let rect = Rect::new(0, y as i32, DISPLAY_WIDTH as u32, run as u32);
+            self.texture.with_lock(rect, |buffer, pitch| {
+                for (i, row) in buffer.chunks_mut(pitch).take(run).enumerate() {
+                    for (x, rgb) in row[..DISPLAY_WIDTH * 3].chunks_exact_mut(3).enumerate() {
+                        let colour = pixel(x, y + i);
+                        rgb.copy_from_slice(&[colour.r, colour.g, colour.b]);
+                    }
+                }
+            })?;
+            y += run;
+        }
+
+        Ok(())
+    }
+
+    /// Copy the texture onto the canvas, whose logical size must match the rotation
+    pub fn draw(&self, canvas: &mut Canvas<Window>, rotation: Rotation) -> Result<(), Error> {
+        // Position the unrotated display so it fills the canvas once rotated around its centre
+        let (width, height) = rotation.logical_size();
+        let dst = FRect::new(
+            (width as f32 - DISPLAY_WIDTH as f32) / 2.,
+            (height as f32 - DISPLAY_HEIGHT as f32) / 2.,
+            DISPLAY_WIDTH as f32,
+            DISPLAY_HEIGHT as f32,
+        );
+        canvas.copy_ex(
+            &self.texture,
+            None,
+            dst,
+            rotation.degrees(),
+            None,
+            false,
+            false,
+        )?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,233 @@
+use crate::{
+    Error,
+    cheat::{self, Cheat},
+    debug::{font_scale, hex_digit},
+    palette::Colours,
+    text,
+};
+use rs_chip8_core::{MachineState, RAM_SIZE};
+use sdl3::{
+    keyboard::{Mod, Scancode},
+    pixels::Color,
+    rect::FRect,
+    render::{BlendMode, Canvas},
+    video::Window,
+};
+
+/// Finds the addresses of variables such as lives or score,
+/// by narrowing down RAM addresses between snapshots
+///
+/// N starts a new search with every address as a candidate.
+/// C and U keep the candidates that changed or stayed the same since the last snapshot,
+/// and enter followed by a hex value keeps the candidates equal to it.
+/// F freezes the selected candidate at its current value, or unfreezes it.
+///
+/// The number keys enable or disable the frozen addresses, or remove them with shift.
+#[derive(Debug, Clone, Default)]
+pub struct MemorySearch {
+    /// RAM when the candidates were last filtered, if searching
+    snapshot: Option<Box<[u8; RAM_SIZE]>>,
+    candidates: Vec<u16>,
+    /// Index of the selected candidate
+    selected: usize,
+    /// Digits of the value typed so far, if typing one
+    input: Option<String>,
+}
+
+impl MemorySearch {
+    pub fn start(&mut self, machine_state: &MachineState) {
+        self.snapshot = Some(Box::new(*machine_state.memory()));
+        self.candidates = (0..RAM_SIZE as u16).collect();
+        self.selected = 0;
+    }
+
+    /// Keep the candidates `keep` returns true for, given their previous and current values
+    fn filter(&mut self, machine_state: &MachineState, keep: impl Fn(u8, u8) -> bool) {
+        let Some(snapshot) = &mut self.snapshot else {
+            return;
+        };
+        let memory = machine_state.memory();
+        self.candidates
+            .retain(|&address| keep(snapshot[address as usize], memory[address as usize]));
+        **snapshot = *memory;
+        self.selected = 0;
+    }
+
+    /// Handle a key press, returning whether the search used it
+    pub fn key_down(
+        &mut self,
+        scancode: Scancode,
+        keymod: Mod,
+        machine_state: &MachineState,
+        cheats: &mut Vec<Cheat>,
+    ) -> bool {
+        let Some(input) = &mut self.input else {
+            match scancode {
+                Scancode::N => self.start(machine_state),
+                Scancode::C => self.filter(machine_state, |previous, value| previous != value),
+                Scancode::U => self.filter(machine_state, |previous, value| previous == value),
+                Scancode::Return | Scancode::KpEnter if self.snapshot.is_some() => {
+                    self.input = Some(String::new())
+                }
+                Scancode::Up => self.selected = self.selected.saturating_sub(1),
+                Scancode::Down => {
+                    self.selected = (self.selected + 1).min(self.candidates.len().saturating_sub(1))
+                }
+                Scancode::F => {
+                    if let Some(&address) = self.candidates.get(self.selected) {
+                        cheat::toggle_freeze(cheats, machine_state, address);
+                    }
+                }
+                _ => {
+                    let Some(index) = hex_digit(scancode)
+                        .and_then(|digit| digit.to_digit(10))
+                        .filter(|&digit| digit > 0)
+                        .map(|digit| digit as usize - 1)
+                        .filter(|&index| index < cheats.len())
+                    else {
+                        return false;
+                    };
+                    if keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD) {
+                        cheats.remove(index);
+                    } else {
+                        cheats[index].enabled = !cheats[index].enabled;
+                    }
+                }
+            }
+            return true;
+        };
+
+        match scancode {
+            Scancode::Return | Scancode::KpEnter => {
+                if let Ok(equal) = u8::from_str_radix(input, 16) {
+                    self.filter(machine_state, |_, value| value == equal);
+                }
+                self.input = None;
+            }
+            Scancode::Escape => self.input = None,
+            Scancode::Backspace => {
+                input.pop();
+            }
+            _ => {
+                if let Some(digit) = hex_digit(scancode)
+                    && input.len() < 2
+                {
+                    input.push(digit);
+                }
+            }
+        }
+        // Keep other keys from reaching the game while typing
+        true
+    }
+
+    /// Draw the candidates with their previous and current values, and the controls
+    pub fn draw(
+        &self,
+        canvas: &mut Canvas<Window>,
+        machine_state: &MachineState,
+        cheats: &[Cheat],
+        colours: Colours,
+        paused: bool,
+        (width, height): (usize, usize),
+    ) -> Result<(), Error> {
+        let scale = font_scale(canvas, (width, height))?;
+        let line_height = (text::GLYPH_HEIGHT + 2) as f32 * scale;
+        let char_width = (text::GLYPH_WIDTH + 1) as f32 * scale;
+
+        canvas.set_blend_mode(BlendMode::Blend);
+        let off = colours.off;
+        canvas.set_draw_color(Color::RGBA(off.r, off.g, off.b, 0xd0));
+        canvas.fill_rect(FRect::new(0., 0., width as f32, height as f32))?;
+        canvas.set_blend_mode(BlendMode::None);
+
+        let heading = match &self.snapshot {
+            Some(_) => format!("MEMORY SEARCH: {} CANDIDATES", self.candidates.len()),
+            None => String::from("MEMORY SEARCH"),
+        };
+        text::draw(canvas, scale, scale, scale, &heading, colours.on)?;
+
+        // Up to 9 cheats can be toggled with the number keys, so they're listed in groups of 5
+        let cheat_lines = cheats
+            .chunks(5)
+            .enumerate()
+            .map(|(line, cheats)| {
+                cheats
+                    .iter()
+                    .enumerate()
+                    .map(|(i, cheat)| {
+                        format!(
+                            "{} {:03X}={:02X}{}",
+                            line * 5 + i + 1,
+                            cheat.address,
+                            cheat.value,
+                            if cheat.enabled { "" } else { " OFF" }
+                        )
+                    })
+                    .collect::<Vec<_>>()
+                    .join("  ")
+            })
+            .collect::<Vec<_>>();
+        for (i, line) in cheat_lines.iter().enumerate() {
+            text::draw(
+                canvas,
+                scale,
+                height as f32 - (cheat_lines.len() - i + 1) as f32 * line_height,
+                scale,
+                line,
+                colours.on,
+            )?;
+        }
+
+        // Each candidate is shown as `address: previous > current`, in columns
+        if let Some(snapshot) = &self.snapshot {
+            const ENTRY_WIDTH: usize = "000: 00>00  ".len();
+            let columns = ((width as f32 / char_width) as usize / ENTRY_WIDTH).max(1);
+            let rows = ((height as f32 / line_height) as usize)
+                .saturating_sub(cheat_lines.len() + 3)
+                .max(1);
+            let page = columns * rows;
+            let first = self.selected / page * page;
+            let memory = machine_state.memory();
+            for (i, &address) in self.candidates.iter().skip(first).take(page).enumerate() {
+                let entry = format!(
+                    "{address:03X}: {:02X}>{:02X}",
+                    snapshot[address as usize], memory[address as usize]
+                );
+                let x = scale + (i / rows * ENTRY_WIDTH) as f32 * char_width;
+                let y = scale + (i % rows + 1) as f32 * line_height;
+                let colour = if first + i == self.selected {
+                    canvas.set_draw_color(colours.on);
+                    canvas.fill_rect(FRect::new(
+                        x - scale,
+                        y - scale,
+                        text::width(&entry) as f32 * scale + 2. * scale,
+                        line_height,
+                    ))?;
+                    colours.off
+                } else {
+                    colours.on
+                };
+                text::draw(canvas, x, y, scale, &entry, colour)?;
+            }
+        }
+
+        let controls = match &self.input {
+            Some(input) => format!("EQUAL TO: {input:_<2}  ENTER: FILTER  ESCAPE: CANCEL"),
+            None if !paused => String::from("P: PAUSE TO SEARCH  F8: HIDE"),
+            None if self.snapshot.is_none() => String::from("N: NEW SEARCH  F8: HIDE"),
+            None => String::from(
+                "N: NEW  C: CHANGED  U: UNCHANGED  ENTER: EQUAL TO  F: FREEZE  1-9: CHEAT ON/OFF  F8: HIDE",
+            ),
+        };
+        text::draw(
+            canvas,
+            scale,
+            height as f32 - line_height,
+            scale,
+            &controls,
+            colours.on,
+        )?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,279 @@
+use crate::{
+    Error,
+    cli::{Command, System},
+    config, database, system_from_extension,
+};
+use rs_chip8_core::{
+    EmulationSystem, PROGRAM_ADDRESS, Quirks, disasm,
+    sprite::{self, Bitmap, Pbm, SpriteFormat},
+};
+use std::path::Path;
+
+/// Addresses of each kind of instruction listed by `check`, shown before the rest are counted
+const SHOWN_ADDRESSES: usize = 8;
+
+/// Run a command that looks at a ROM without emulating it
+pub fn run(command: Command) -> Result<(), Error> {
+    match command {
+        Command::Run(_) => unreachable!("ROMs are run by the emulator"),
+        Command::Disasm { rom, system } => disassemble(&rom, system),
+        Command::Info { rom } => info(&rom),
+        Command::Check { rom, system } => check(&rom, system),
+        Command::Sprites {
+            image,
+            format,
+            label,
+        } => sprites(&image, format.into(), label),
+        Command::ImportDatabase { programs } => {
+            print!("{}", database::import(&std::fs::read_to_string(programs)?)?);
+            Ok(())
+        }
+    }
+}
+
+fn read(rom: &Path) -> Result<Vec<u8>, Error> {
+    std::fs::read(rom).map_err(|source| Error::ReadRom {
+        path: rom.to_path_buf(),
+        source,
+    })
+}
+
+fn system(rom: &Path, system: Option<System>) -> Result<EmulationSystem, Error> {
+    Ok(match system {
+        Some(system) => system.into(),
+        None => system_from_extension(rom, &config::Config::load(None)?),
+    })
+}
+
+fn disassemble(rom: &Path, system_arg: Option<System>) -> Result<(), Error> {
+    let program = read(rom)?;
+    let mut listing = String::new();
+    disasm::write_listing(&program, system(rom, system_arg)?, &mut listing)
+        .expect("Writing to a String can't fail");
+    print!("{listing}");
+    Ok(())
+}
+
+fn info(rom: &Path) -> Result<(), Error> {
+    let program = read(rom)?;
+    println!("File: {}", rom.display());
+    println!("Size: {} bytes", program.len());
+    println!("SHA-1: {}", database::hash(&program));
+    println!("Settings hash: {:016x}", config::hash(&program));
+    if let Some(path) = config::RomConfig::path(&program).filter(|path| path.is_file()) {
+        println!("Settings: {}", path.display());
+    }
+
+    match database::lookup(&program)? {
+        Some(entry) => {
+            println!("Title: {}", entry.title);
+            if let Some(system) = entry.system {
+                println!("System: {:?}", EmulationSystem::from(system));
+            }
+            if let Some(ipf) = entry.ipf {
+                println!("IPF: {ipf}");
+            }
+            let quirks = entry.quirks;
+            for (name, value) in [
+                ("logic_resets_flag", quirks.logic_resets_flag),
+                ("shift_uses_vy", quirks.shift_uses_vy),
+                ("jump_uses_vx", quirks.jump_uses_vx),
+                ("memory_increments_index", quirks.memory_increments_index),
+            ] {
+                if let Some(value) = value {
+                    println!("Quirk {name}: {value}");
+                }
+            }
+        }
+        None => println!("Not in the ROM database"),
+    }
+    Ok(())
+}
+
+/// A PNG image, converted to monochrome
+struct Png {
+    width: usize,
+    height: usize,
+    pixels: Vec<bool>,
+}
+
+impl Png {
+    fn decode(file: &[u8]) -> Result<Self, Error> {
+        let mut decoder = png::Decoder::new(std::io::Cursor::new(file));
+        decoder.set_transformations(png::Transformations::normalize_to_color8());
+        let mut reader = decoder.read_info()?;
+        let size = reader
+            .output_buffer_size()
+            .ok_or(rs_chip8_core::Error::InvalidImage(
+                "image dimensions are too large",
+            ))?;
+        let mut buffer = vec![0; size];
+        let info = reader.next_frame(&mut buffer)?;
+
+        let (width, height) = (info.width as usize, info.height as usize);
+        let samples = info.color_type.samples();
+        let pixels = buffer[..info.buffer_size()]
+            .chunks_exact(info.line_size)
+            .flat_map(|line| line.chunks_exact(samples).take(width))
+            .map(|pixel| {
+                // Grey or RGB, then alpha if there is any
+                let (colour, alpha) = match samples {
+                    1 | 3 => (pixel, u8::MAX),
+                    _ => (&pixel[..samples - 1], pixel[samples - 1]),
+                };
+                let brightness =
+                    colour.iter().map(|&sample| sample as usize).sum::<usize>() / colour.len();
+                alpha >= 0x80 && brightness < 0x80
+            })
+            .collect();
+        Ok(Self {
+            width,
+            height,
+            pixels,
+        })
+    }
+}
+
+impl Bitmap for Png {
+    fn width(&self) -> usize {
+        self.width
+    }
+
+    fn height(&self) -> usize {
+        self.height
+    }
+
+    fn pixel(&self, x: usize, y: usize) -> bool {
+        self.pixels[y * self.width + x]
+    }
+}
+
+fn sprites(image: &Path, format: SpriteFormat, label: Option<String>) -> Result<(), Error> {
+    let file = std::fs::read(image)?;
+    let label = label.unwrap_or_else(|| {
+        image
+            .file_stem()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .into_owned()
+    });
+
+    let mut octo = String::new();
+    if file.starts_with(b"\x89PNG") {
+        sprite::write_octo(&Png::decode(&file)?, format, &label, &mut octo)
+    } else {
+        sprite::write_octo(&Pbm::parse(&file)?, format, &label, &mut octo)
+    }
+    .expect("Writing to a String can't fail");
+    print!("{octo}");
+    Ok(())
+}
+
+/// Instructions whose behaviour depends on a quirk
+struct Sensitive {
+    quirk: &'static str,
+    instructions: &'static str,
+    /// Whether the quirk is on for the system being checked
+    default: bool,
+    matches: fn(u16) -> bool,
+    addresses: Vec<usize>,
+}
+
+/// List instructions affected by quirks, so it's clear which quirks a ROM could need changed
+///
+/// Every pair of bytes is treated as an instruction, so sprite data can show up too.
+fn check(rom: &Path, system_arg: Option<System>) -> Result<(), Error> {
+    let program = read(rom)?;
+    let system = system(rom, system_arg)?;
+    let defaults = Quirks::for_system(system);
+
+    let mut sensitive = [
+        Sensitive {
+            quirk: "logic_resets_flag",
+            default: defaults.logic_resets_flag,
+            instructions: "8XY1, 8XY2, 8XY3",
+            matches: |opcode| opcode & 0xF00F >= 0x8001 && opcode & 0xF00F <= 0x8003,
+            addresses: Vec::new(),
+        },
+        Sensitive {
+            quirk: "shift_uses_vy",
+            default: defaults.shift_uses_vy,
+            instructions: "8XY6, 8XYE",
+            matches: |opcode| matches!(opcode & 0xF00F, 0x8006 | 0x800E),
+            addresses: Vec::new(),
+        },
+        Sensitive {
+            quirk: "jump_uses_vx",
+            default: defaults.jump_uses_vx,
+            instructions: "BNNN",
+            matches: |opcode| opcode & 0xF000 == 0xB000,
+            addresses: Vec::new(),
+        },
+        Sensitive {
+            quirk: "memory_increments_index",
+            default: defaults.memory_increments_index,
+            instructions: "FX55, FX65",
+            matches: |opcode| matches!(opcode & 0xF0FF, 0xF055 | 0xF065),
+            addresses: Vec::new(),
+        },
+    ];
+    let mut invalid = Vec::new();
+    for (offset, pair) in program.chunks_exact(2).enumerate() {
+        let address = PROGRAM_ADDRESS as usize + 2 * offset;
+        let opcode = u16::from_be_bytes([pair[0], pair[1]]);
+        for sensitive in &mut sensitive {
+            if (sensitive.matches)(opcode) {
+                sensitive.addresses.push(address);
+            }
+        }
+        if !disasm::disassemble(opcode, system).is_valid() {
+            invalid.push(address);
+        }
+    }
+
+    println!("Checking for {system:?}, which defaults to:");
+    for sensitive in &sensitive {
+        println!("  {} = {}", sensitive.quirk, sensitive.default);
+    }
+    println!();
+
+    for sensitive in sensitive
+        .iter()
+        .filter(|sensitive| !sensitive.addresses.is_empty())
+    {
+        println!(
+            "{} is used by {} {}: {}",
+            sensitive.quirk,
+            sensitive.addresses.len(),
+            sensitive.instructions,
+            addresses(&sensitive.addresses)
+        );
+    }
+    if sensitive
+        .iter()
+        .all(|sensitive| sensitive.addresses.is_empty())
+    {
+        println!("No instructions depend on quirks");
+    }
+    if !invalid.is_empty() {
+        println!(
+            "{} words aren't {system:?} instructions, they may be data or need another system: {}",
+            invalid.len(),
+            addresses(&invalid)
+        );
+    }
+    Ok(())
+}
+
+fn addresses(addresses: &[usize]) -> String {
+    let mut list = addresses
+        .iter()
+        .take(SHOWN_ADDRESSES)
+        .map(|address| format!("{address:03X}"))
+        .collect::<Vec<_>>()
+        .join(", ");
+    if addresses.len() > SHOWN_ADDRESSES {
+        list.push_str(&format!(" and {} more", addresses.len() - SHOWN_ADDRESSES));
+    }
+    list
+}
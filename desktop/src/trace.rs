@@ -0,0 +1,129 @@
+use crate::Error;
+use rs_chip8_core::{MachineState, disasm};
+use std::{
+    ffi::OsString,
+    fs::File,
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+/// Size a trace file grows to before it's rotated
+const MAX_SIZE: u64 = 16 * 1024 * 1024;
+/// Rotated files kept, from `<trace>.1`, the newest, to `<trace>.<KEPT>`
+const KEPT: u32 = 3;
+
+/// An instruction about to run, and the registers from before it
+struct Pending {
+    address: u16,
+    opcode: u16,
+    registers: [u8; 16],
+    index: u16,
+}
+
+/// Writes every instruction executed to a file, with the registers each one changed
+///
+/// Once the file is too big it's moved to `<trace>.1`, moving older files along,
+/// so a long session only keeps the most recent instructions.
+pub struct Trace {
+    path: PathBuf,
+    file: BufWriter<File>,
+    size: u64,
+    /// An instruction's line is only finished once its changes are known, before the next one
+    pending: Option<Pending>,
+}
+
+impl Trace {
+    pub fn start(path: PathBuf) -> Result<Self, Error> {
+        let file = BufWriter::new(File::create(&path)?);
+        Ok(Self {
+            path,
+            file,
+            size: 0,
+            pending: None,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Record the instruction about to be executed, finishing the line for the one before
+    pub fn instruction(&mut self, machine_state: &MachineState) -> Result<(), Error> {
+        self.write_pending(machine_state)?;
+
+        let address = machine_state.program_counter();
+        self.pending = Some(Pending {
+            address,
+            opcode: u16::from_be_bytes([
+                machine_state.peek(address),
+                machine_state.peek(address.wrapping_add(1)),
+            ]),
+            registers: *machine_state.registers(),
+            index: machine_state.index_register(),
+        });
+        Ok(())
+    }
+
+    fn write_pending(&mut self, machine_state: &MachineState) -> Result<(), Error> {
+        let Some(pending) = self.pending.take() else {
+            return Ok(());
+        };
+
+        let mut line = format!(
+            "{:03X}  {:04X}  {:<16}",
+            pending.address,
+            pending.opcode,
+            disasm::disassemble(pending.opcode, machine_state.system()).to_string()
+        );
+        for (i, (before, after)) in pending
+            .registers
+            .iter()
+            .zip(machine_state.registers())
+            .enumerate()
+        {
+            if before != after {
+                line.push_str(&format!(" V{i:X}={after:02X}"));
+            }
+        }
+        if pending.index != machine_state.index_register() {
+            line.push_str(&format!(" I={:03X}", machine_state.index_register()));
+        }
+        let line = line.trim_end().to_owned() + "\n";
+
+        self.file.write_all(line.as_bytes())?;
+        self.size += line.len() as u64;
+        if self.size >= MAX_SIZE {
+            self.rotate()?;
+        }
+        Ok(())
+    }
+
+    fn rotate(&mut self) -> Result<(), Error> {
+        self.file.flush()?;
+        for n in (1..KEPT).rev() {
+            let older = rotated(&self.path, n);
+            if older.is_file() {
+                std::fs::rename(older, rotated(&self.path, n + 1))?;
+            }
+        }
+        std::fs::rename(&self.path, rotated(&self.path, 1))?;
+
+        self.file = BufWriter::new(File::create(&self.path)?);
+        self.size = 0;
+        Ok(())
+    }
+
+    /// Write the last instruction and flush the file, returning its path
+    pub fn finish(mut self, machine_state: &MachineState) -> Result<PathBuf, Error> {
+        self.write_pending(machine_state)?;
+        self.file.flush()?;
+        Ok(self.path)
+    }
+}
+
+/// The path of the `n`th newest rotated file
+fn rotated(path: &Path, n: u32) -> PathBuf {
+    let mut path = OsString::from(path);
+    path.push(format!(".{n}"));
+    path.into()
+}
@@ -0,0 +1,149 @@
+use crate::{Error, audio::Waveform, config::AudioConfig, palette::Colours};
+use rs_chip8_core::{DISPLAY_HEIGHT, DISPLAY_WIDTH, Display};
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+    process::{Child, ChildStdin, Command, Stdio},
+};
+
+const SAMPLE_RATE: u32 = 44100;
+const SAMPLES_PER_FRAME: u32 = SAMPLE_RATE / 60;
+
+/// Encoder settings used when none are given, upscaling the display so it stays sharp
+pub const DEFAULT_ARGS: &str = "-vf scale=iw*8:ih*8:flags=neighbor -pix_fmt yuv420p";
+
+/// Records video by piping raw frames to ffmpeg
+///
+/// The beeper is recorded separately as raw samples,
+/// then muxed with the video by a second ffmpeg run once recording finishes.
+pub struct VideoRecorder {
+    ffmpeg: Child,
+    stdin: Option<BufWriter<ChildStdin>>,
+    audio: BufWriter<File>,
+    waveform: Waveform,
+    frequency: f32,
+    volume: f32,
+    /// How far through a cycle of the tone the next sample is
+    phase: f32,
+    output: PathBuf,
+    video_path: PathBuf,
+    audio_path: PathBuf,
+}
+
+/// `<stem>.<suffix>.<extension>` next to `path`
+fn temp_path(path: &Path, suffix: &str) -> PathBuf {
+    let mut file_name = path.file_stem().unwrap_or_default().to_os_string();
+    file_name.push(format!(".{suffix}"));
+    if let Some(extension) = path.extension() {
+        file_name.push(".");
+        file_name.push(extension);
+    }
+    path.with_file_name(file_name)
+}
+
+impl VideoRecorder {
+    /// Start ffmpeg, encoding to `output` with the given encoder settings
+    ///
+    /// The container is chosen by ffmpeg from the file extension, e.g. `.mp4` or `.webm`.
+    pub fn start(output: &Path, args: &str, audio: &AudioConfig) -> Result<Self, Error> {
+        let video_path = temp_path(output, "video");
+        let audio_path = temp_path(output, "audio");
+
+        let mut ffmpeg = Command::new("ffmpeg")
+            .args(["-y", "-loglevel", "error"])
+            .args(["-f", "rawvideo", "-pixel_format", "rgb24"])
+            .args(["-video_size", &format!("{DISPLAY_WIDTH}x{DISPLAY_HEIGHT}")])
+            .args(["-framerate", "60", "-i", "-"])
+            .args(args.split_whitespace())
+            .arg(&video_path)
+            .stdin(Stdio::piped())
+            .spawn()?;
+        let stdin = ffmpeg.stdin.take().map(BufWriter::new);
+
+        Ok(Self {
+            ffmpeg,
+            stdin,
+            audio: BufWriter::new(File::create(&audio_path)?),
+            waveform: audio.waveform,
+            frequency: audio.frequency,
+            volume: audio.volume,
+            phase: 0.,
+            output: output.to_path_buf(),
+            video_path,
+            audio_path,
+        })
+    }
+
+    /// Record a 60 Hz frame, along with the beeper's tone if it's playing
+    pub fn frame(
+        &mut self,
+        display: &Display,
+        colours: Colours,
+        beeping: bool,
+    ) -> Result<(), Error> {
+        let mut frame = Vec::with_capacity(DISPLAY_WIDTH * DISPLAY_HEIGHT * 3);
+        for y in 0..DISPLAY_HEIGHT {
+            for x in 0..DISPLAY_WIDTH {
+                let colour = if display.pixel(x, y) {
+                    colours.on
+                } else {
+                    colours.off
+                };
+                frame.extend([colour.r, colour.g, colour.b]);
+            }
+        }
+        if let Some(stdin) = &mut self.stdin {
+            stdin.write_all(&frame)?;
+        }
+
+        let amplitude = self.volume * i16::MAX as f32;
+        for _ in 0..SAMPLES_PER_FRAME {
+            let sample = if beeping {
+                (amplitude * self.waveform.sample(self.phase)) as i16
+            } else {
+                0
+            };
+            self.audio.write_all(&sample.to_le_bytes())?;
+            self.phase = (self.phase + self.frequency / SAMPLE_RATE as f32) % 1.;
+        }
+
+        Ok(())
+    }
+
+    /// Wait for ffmpeg to finish the video, then add the audio, returning the output's path
+    pub fn finish(mut self) -> Result<PathBuf, Error> {
+        if let Some(mut stdin) = self.stdin.take() {
+            stdin.flush()?;
+        }
+        self.audio.flush()?;
+
+        let video_status = self.ffmpeg.wait()?;
+        let mux_status = if video_status.success() {
+            Command::new("ffmpeg")
+                .args(["-y", "-loglevel", "error"])
+                .arg("-i")
+                .arg(&self.video_path)
+                .args(["-f", "s16le", "-ar", &SAMPLE_RATE.to_string(), "-ac", "1"])
+                .arg("-i")
+                .arg(&self.audio_path)
+                .args(["-c:v", "copy", "-shortest"])
+                .arg(&self.output)
+                .status()?
+        } else {
+            video_status
+        };
+
+        let _ = std::fs::remove_file(&self.video_path);
+        let _ = std::fs::remove_file(&self.audio_path);
+
+        if mux_status.success() {
+            Ok(self.output)
+        } else {
+            Err(
+                std::io::Error::other(format!("ffmpeg failed to encode {}", self.output.display()))
+                    .into(),
+            )
+        }
+    }
+}
@@ -0,0 +1,58 @@
+use std::{
+    path::{Path, PathBuf},
+    time::{Duration, Instant, SystemTime},
+};
+
+/// How often the ROM's modification time is checked
+const INTERVAL: Duration = Duration::from_millis(250);
+
+/// Notices when the ROM file is rebuilt, so it can be reloaded
+pub struct RomWatcher {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+    /// A modification time seen once, which is only reported when it's seen again,
+    /// so a file still being written isn't loaded
+    pending: Option<SystemTime>,
+    checked_at: Instant,
+}
+
+impl RomWatcher {
+    pub fn new(path: &Path) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            modified: modified(path),
+            pending: None,
+            checked_at: Instant::now(),
+        }
+    }
+
+    /// Whether the file has changed since it was last loaded, checking at most every `INTERVAL`
+    pub fn changed(&mut self) -> bool {
+        if self.checked_at.elapsed() < INTERVAL {
+            return false;
+        }
+        self.checked_at = Instant::now();
+
+        // The file may be missing for a moment while it's replaced
+        let Some(modified) = modified(&self.path) else {
+            return false;
+        };
+        if Some(modified) == self.modified {
+            self.pending = None;
+            false
+        } else if Some(modified) == self.pending {
+            self.modified = Some(modified);
+            self.pending = None;
+            true
+        } else {
+            self.pending = Some(modified);
+            false
+        }
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
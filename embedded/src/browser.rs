@@ -0,0 +1,115 @@
+use crate::{Keypad, Monotonic, Screen, menu};
+use embedded_sdmmc::{
+    BlockDevice, Mode, ShortFileName, TimeSource, Timestamp, VolumeIdx, VolumeManager,
+};
+use heapless::Vec;
+use rs_chip8_core::EmulationSystem;
+
+/// ROMs past this many in the root directory aren't listed
+pub const MAX_ROMS: usize = 64;
+
+/// The card is only read, so files' timestamps are never written
+struct NoClock;
+
+impl TimeSource for NoClock {
+    fn get_timestamp(&self) -> Timestamp {
+        Timestamp {
+            year_since_1970: 0,
+            zero_indexed_month: 0,
+            zero_indexed_day: 0,
+            hours: 0,
+            minutes: 0,
+            seconds: 0,
+        }
+    }
+}
+
+/// A ROM chosen with [`choose_rom`]
+pub struct ChosenRom<'a> {
+    pub program: &'a [u8],
+    /// SUPER-CHIP for `.sc8` files, CHIP-8 otherwise
+    pub system: EmulationSystem,
+}
+
+/// Why [`choose_rom`] couldn't give a ROM
+#[derive(Debug)]
+pub enum BrowseError<C, S> {
+    /// The card, or its filesystem, couldn't be read
+    Card(embedded_sdmmc::Error<C>),
+    /// The screen couldn't be drawn to
+    Screen(S),
+    /// There are no `.ch8` or `.sc8` files in the card's root directory
+    NoRoms,
+    /// The chosen ROM, `len` bytes long, doesn't fit in the buffer
+    RomTooBig { len: u32 },
+}
+
+impl<C, S> From<embedded_sdmmc::Error<C>> for BrowseError<C, S> {
+    fn from(err: embedded_sdmmc::Error<C>) -> Self {
+        Self::Card(err)
+    }
+}
+
+/// List the ROMs in the root directory of the card's first partition, let a ROM be chosen
+/// with the keypad, then read it into `buffer`
+///
+/// Keys 2 and 8 move the selection, and 5 chooses. A ROM too long for `buffer` is an error,
+/// so it should be as long as a program can be.
+pub fn choose_rom<'a, D: BlockDevice, S: Screen>(
+    card: D,
+    screen: &mut S,
+    keypad: &mut impl Keypad,
+    monotonic: &mut impl Monotonic,
+    buffer: &'a mut [u8],
+) -> Result<ChosenRom<'a>, BrowseError<D::Error, S::Error>> {
+    let volume_manager = VolumeManager::new(card, NoClock);
+    let volume = volume_manager.open_volume(VolumeIdx(0))?;
+    let root_dir = volume.open_root_dir()?;
+
+    let mut roms: Vec<ShortFileName, MAX_ROMS> = Vec::new();
+    root_dir.iterate_dir(|entry| {
+        if !entry.attributes.is_directory() && matches!(entry.name.extension(), b"CH8" | b"SC8") {
+            // A full list just leaves the rest out
+            let _ = roms.push(entry.name.clone());
+        }
+    })?;
+    if roms.is_empty() {
+        return Err(BrowseError::NoRoms);
+    }
+    roms.sort_unstable_by(|a, b| a.base_name().cmp(b.base_name()));
+
+    let chosen = menu::choose(
+        roms.len(),
+        |i| {
+            let mut name: Vec<u8, 12> = Vec::new();
+            // Short file names are at most 8 characters, a dot, and 3 more, so they always fit
+            let _ = name.extend_from_slice(roms[i].base_name());
+            let _ = name.push(b'.');
+            let _ = name.extend_from_slice(roms[i].extension());
+            name
+        },
+        screen,
+        keypad,
+        monotonic,
+    )
+    .map_err(BrowseError::Screen)?;
+    let name = &roms[chosen];
+    let file = root_dir.open_file_in_dir(name, Mode::ReadOnly)?;
+    let mut length = 0;
+    while length < buffer.len() && !file.is_eof() {
+        length += file.read(&mut buffer[length..])?;
+    }
+    // Running part of a program would only go wrong later, in ways that are harder to tell
+    if !file.is_eof() {
+        return Err(BrowseError::RomTooBig { len: file.length() });
+    }
+
+    Ok(ChosenRom {
+        program: &buffer[..length],
+        system: if name.extension() == b"SC8" {
+            EmulationSystem::SuperChip
+        } else {
+            EmulationSystem::Chip8
+        },
+    })
+}
@@ -0,0 +1,50 @@
+use crate::Keypad;
+
+/// HID usage IDs for each key, on the 1234/QWER/ASDF/ZXCV block like the desktop frontend
+pub const QWERTY_USAGES: [u8; 16] = [
+    0x1B, 0x1E, 0x1F, 0x20, 0x14, 0x1A, 0x08, 0x04, 0x16, 0x07, 0x1D, 0x06, 0x21, 0x15, 0x09, 0x19,
+];
+/// HID usage IDs for each key, with hex digits on the matching number pad keys
+pub const NUMPAD_USAGES: [u8; 16] = [
+    0x62, 0x59, 0x5A, 0x5B, 0x5C, 0x5D, 0x5E, 0x5F, 0x60, 0x61, 0x63, 0x58, 0x57, 0x56, 0x55, 0x54,
+];
+
+/// Sent in every key slot when more keys are held than a report can list
+const ERROR_ROLL_OVER: u8 = 0x01;
+
+/// The keys held on a USB keyboard or number pad, from its boot protocol reports
+///
+/// The board's USB host stack passes in each report the keyboard sends.
+pub struct HidKeypad {
+    usages: [u8; 16],
+    held_keys: u16,
+}
+
+impl HidKeypad {
+    pub fn new(usages: [u8; 16]) -> Self {
+        Self {
+            usages,
+            held_keys: 0,
+        }
+    }
+
+    /// Take in a boot protocol report: a byte of modifiers, a reserved byte, then up to 6 usage IDs
+    pub fn report(&mut self, report: &[u8]) {
+        let held_usages = report.get(2..).unwrap_or_default();
+        // Keep the keys from before, as which ones are held isn't known
+        if held_usages.contains(&ERROR_ROLL_OVER) {
+            return;
+        }
+
+        self.held_keys = held_usages
+            .iter()
+            .filter_map(|usage| self.usages.iter().position(|key_usage| key_usage == usage))
+            .fold(0, |held_keys, key| held_keys | 1 << key);
+    }
+}
+
+impl Keypad for HidKeypad {
+    fn scan(&mut self) -> u16 {
+        self.held_keys
+    }
+}
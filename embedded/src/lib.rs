@@ -0,0 +1,129 @@
+//! The parts of a board needed to run a ROM, and a loop that runs one on any board that has them
+//!
+//! A new board only needs to implement these traits for its own hardware, then call [`run`].
+
+#![no_std]
+
+#[cfg(feature = "sd-card")]
+mod browser;
+mod hid;
+mod matrix;
+mod menu;
+
+use rs_chip8_core::{Display, Error, MachineState};
+
+#[cfg(feature = "sd-card")]
+pub use browser::{BrowseError, ChosenRom, MAX_ROMS, choose_rom};
+pub use hid::{HidKeypad, NUMPAD_USAGES, QWERTY_USAGES};
+pub use matrix::{COSMAC_VIP_LAYOUT, MatrixKeypad};
+pub use menu::choose;
+
+/// Length of a 60 Hz frame in microseconds
+pub const FRAME_LENGTH: u64 = 1_000_000 / 60;
+
+/// Where the display is shown
+pub trait Screen {
+    type Error;
+
+    /// Show the rows of `display` set in `dirty_rows`, bit n for row n,
+    /// the rest haven't changed since the last flush
+    fn flush(&mut self, display: &Display, dirty_rows: u64) -> Result<(), Self::Error>;
+}
+
+/// The 16 keys
+pub trait Keypad {
+    /// The keys held right now, with bit n set for key n
+    fn scan(&mut self) -> u16;
+
+    /// Sleep until the keys held aren't `_held_keys` any more, woken by the keys if the board can
+    ///
+    /// [`run`] calls this while the program is waiting for a key. By default it returns
+    /// straight away, so the keypad is scanned every frame like usual.
+    fn sleep_until_changed(&mut self, _held_keys: u16) {}
+
+    /// Whether [`run`] should stop after this frame, so the board can do something with the
+    /// machine, like saving it, before calling [`run`] again to carry on
+    ///
+    /// By default it never does.
+    fn pause_requested(&mut self) -> bool {
+        false
+    }
+}
+
+/// Makes the sound while the sound timer is running
+pub trait Beeper {
+    fn set_beeping(&mut self, beeping: bool);
+}
+
+/// For boards without a beeper
+impl Beeper for () {
+    fn set_beeping(&mut self, _beeping: bool) {}
+}
+
+/// Random numbers for CXNN
+pub trait Rng {
+    fn random_byte(&mut self) -> u8;
+}
+
+/// A clock that only counts up, in microseconds
+pub trait Monotonic {
+    fn now_micros(&mut self) -> u64;
+
+    /// Wait until the clock reaches `micros`, spinning unless the board can do better
+    fn wait_until(&mut self, micros: u64) {
+        while self.now_micros() < micros {}
+    }
+}
+
+/// Why [`run`] stopped
+#[derive(Debug)]
+pub enum Stopped<E> {
+    /// The program failed or exited
+    Program(Error),
+    /// The screen couldn't be drawn to
+    Screen(E),
+    /// The keypad asked to pause, with [`Keypad::pause_requested`]
+    Paused,
+}
+
+/// Run frames at 60 Hz until the program stops, the screen fails, or the keypad asks to pause
+///
+/// The screen is left showing the last frame, and the beeper is turned off.
+pub fn run<S: Screen>(
+    machine_state: &mut MachineState,
+    ipf: u32,
+    screen: &mut S,
+    keypad: &mut impl Keypad,
+    beeper: &mut impl Beeper,
+    rng: &mut impl Rng,
+    monotonic: &mut impl Monotonic,
+) -> Stopped<S::Error> {
+    let mut next_frame = monotonic.now_micros();
+    loop {
+        let held_keys = keypad.scan();
+        if let Err(err) = machine_state.run_frame(ipf, || held_keys, || rng.random_byte()) {
+            beeper.set_beeping(false);
+            return Stopped::Program(err);
+        }
+        beeper.set_beeping(machine_state.sound_timer() > 0);
+
+        let dirty_rows = machine_state.take_dirty_rows();
+        if let Err(err) = screen.flush(machine_state.display(), dirty_rows) {
+            beeper.set_beeping(false);
+            return Stopped::Screen(err);
+        }
+        if keypad.pause_requested() {
+            beeper.set_beeping(false);
+            return Stopped::Paused;
+        }
+
+        if machine_state.is_waiting_for_key() {
+            keypad.sleep_until_changed(held_keys);
+            // Frames missed while asleep are skipped, not caught up on
+            next_frame = next_frame.max(monotonic.now_micros());
+        }
+
+        next_frame += FRAME_LENGTH;
+        monotonic.wait_until(next_frame);
+    }
+}
@@ -0,0 +1,83 @@
+use crate::Keypad;
+use embedded_hal::{
+    delay::DelayNs,
+    digital::{InputPin, OutputPin},
+};
+
+/// The keys on a COSMAC VIP's keypad, by row then column
+pub const COSMAC_VIP_LAYOUT: [[u8; 4]; 4] = [
+    [0x1, 0x2, 0x3, 0xC],
+    [0x4, 0x5, 0x6, 0xD],
+    [0x7, 0x8, 0x9, 0xE],
+    [0xA, 0x0, 0xB, 0xF],
+];
+
+/// Scans a key's reading has to stay the same for before it counts, longer than a switch bounces
+const DEBOUNCE_SCANS: u8 = 2;
+
+/// A 4x4 keypad, scanned by pulling each row low and reading which columns follow it
+///
+/// Rows should be open drain, so two keys held in a column can't short them,
+/// and columns pulled up.
+pub struct MatrixKeypad<R, C, D> {
+    rows: [R; 4],
+    columns: [C; 4],
+    delay: D,
+    layout: [[u8; 4]; 4],
+    held_keys: u16,
+    /// Scans each key has read differently to `held_keys` for in a row
+    changing: [u8; 16],
+}
+
+impl<R: OutputPin, C: InputPin, D: DelayNs> MatrixKeypad<R, C, D> {
+    /// A keypad laid out like the COSMAC VIP's
+    pub fn new(rows: [R; 4], columns: [C; 4], delay: D) -> Self {
+        Self::with_layout(rows, columns, delay, COSMAC_VIP_LAYOUT)
+    }
+
+    pub fn with_layout(rows: [R; 4], columns: [C; 4], delay: D, layout: [[u8; 4]; 4]) -> Self {
+        Self {
+            rows,
+            columns,
+            delay,
+            layout,
+            held_keys: 0,
+            changing: [0; 16],
+        }
+    }
+
+    /// The keys held right now, before debouncing
+    fn read(&mut self) -> u16 {
+        let mut held_keys = 0;
+        for (row, row_pin) in self.rows.iter_mut().enumerate() {
+            // A pin that can't be read or driven counts as released
+            let _ = row_pin.set_low();
+            self.delay.delay_us(5);
+            for (column, column_pin) in self.columns.iter_mut().enumerate() {
+                if column_pin.is_low().unwrap_or(false) {
+                    held_keys |= 1 << self.layout[row][column];
+                }
+            }
+            let _ = row_pin.set_high();
+        }
+        held_keys
+    }
+}
+
+impl<R: OutputPin, C: InputPin, D: DelayNs> Keypad for MatrixKeypad<R, C, D> {
+    fn scan(&mut self) -> u16 {
+        let changed = self.read() ^ self.held_keys;
+        for (key, changing) in self.changing.iter_mut().enumerate() {
+            if changed >> key & 1 == 0 {
+                *changing = 0;
+                continue;
+            }
+            *changing += 1;
+            if *changing >= DEBOUNCE_SCANS {
+                self.held_keys ^= 1 << key;
+                *changing = 0;
+            }
+        }
+        self.held_keys
+    }
+}
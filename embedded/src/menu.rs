@@ -0,0 +1,148 @@
+use crate::{FRAME_LENGTH, Keypad, Monotonic, Screen};
+use rs_chip8_core::{DISPLAY_HEIGHT, DISPLAY_WIDTH, Display};
+
+/// Keys that move the selection and choose, where 2, 8, and 5 are on the COSMAC VIP keypad
+const UP_KEY: u8 = 0x2;
+const DOWN_KEY: u8 = 0x8;
+const CHOOSE_KEY: u8 = 0x5;
+
+/// Each character is 3x5 pixels, with a gap after it
+const CHARACTER_WIDTH: usize = 4;
+const LINE_HEIGHT: usize = 6;
+const LINES: usize = DISPLAY_HEIGHT / LINE_HEIGHT;
+
+/// Rows of each glyph, with the leftmost pixel in bit 2
+const DIGITS: [[u8; 5]; 10] = [
+    [7, 5, 5, 5, 7],
+    [2, 6, 2, 2, 7],
+    [7, 1, 7, 4, 7],
+    [7, 1, 7, 1, 7],
+    [5, 5, 7, 1, 1],
+    [7, 4, 7, 1, 7],
+    [7, 4, 7, 5, 7],
+    [7, 1, 1, 2, 2],
+    [7, 5, 7, 5, 7],
+    [7, 5, 7, 1, 7],
+];
+const LETTERS: [[u8; 5]; 26] = [
+    [2, 5, 7, 5, 5],
+    [6, 5, 6, 5, 6],
+    [3, 4, 4, 4, 3],
+    [6, 5, 5, 5, 6],
+    [7, 4, 6, 4, 7],
+    [7, 4, 6, 4, 4],
+    [3, 4, 5, 5, 3],
+    [5, 5, 7, 5, 5],
+    [7, 2, 2, 2, 7],
+    [1, 1, 1, 5, 2],
+    [5, 5, 6, 5, 5],
+    [4, 4, 4, 4, 7],
+    [5, 7, 7, 5, 5],
+    [6, 5, 5, 5, 5],
+    [2, 5, 5, 5, 2],
+    [6, 5, 6, 4, 4],
+    [2, 5, 5, 6, 3],
+    [6, 5, 6, 5, 5],
+    [3, 4, 2, 1, 6],
+    [7, 2, 2, 2, 2],
+    [5, 5, 5, 5, 7],
+    [5, 5, 5, 5, 2],
+    [5, 5, 7, 7, 5],
+    [5, 5, 2, 5, 5],
+    [5, 5, 2, 2, 2],
+    [7, 1, 2, 4, 7],
+];
+
+/// There are only upper case letters, lower case ones are drawn with them
+fn glyph(character: u8) -> [u8; 5] {
+    match character.to_ascii_uppercase() {
+        b'0'..=b'9' => DIGITS[(character - b'0') as usize],
+        b'A'..=b'Z' => LETTERS[(character - b'A') as usize],
+        b'.' => [0, 0, 0, 0, 2],
+        b'-' => [0, 0, 7, 0, 0],
+        b'_' => [0, 0, 0, 0, 7],
+        b'~' => [0, 0, 3, 6, 0],
+        b'>' => [4, 2, 1, 2, 4],
+        b' ' => [0; 5],
+        _ => [7, 1, 2, 0, 2],
+    }
+}
+
+/// Show a list of `count` names until one is chosen with the keypad, returning its index
+///
+/// Keys 2 and 8 move the selection, and 5 chooses. `name` gives the name at an index,
+/// so the names don't all have to be in RAM at once.
+pub fn choose<S: Screen, N: AsRef<[u8]>>(
+    count: usize,
+    mut name: impl FnMut(usize) -> N,
+    screen: &mut S,
+    keypad: &mut impl Keypad,
+    monotonic: &mut impl Monotonic,
+) -> Result<usize, S::Error> {
+    let mut selected = 0;
+    let mut redraw = true;
+    // Keys already held, like the one that chose the last ROM, have to be let go first
+    let mut held_keys = u16::MAX;
+    let mut next_frame = monotonic.now_micros();
+    loop {
+        if redraw {
+            screen.flush(&draw_menu(count, &mut name, selected), u64::MAX)?;
+            redraw = false;
+        }
+
+        let now_held = keypad.scan();
+        let pressed = now_held & !held_keys;
+        held_keys = now_held;
+        if pressed >> CHOOSE_KEY & 1 == 1 {
+            return Ok(selected);
+        }
+        if pressed >> UP_KEY & 1 == 1 && selected > 0 {
+            selected -= 1;
+            redraw = true;
+        }
+        if pressed >> DOWN_KEY & 1 == 1 && selected + 1 < count {
+            selected += 1;
+            redraw = true;
+        }
+
+        next_frame += FRAME_LENGTH;
+        monotonic.wait_until(next_frame);
+    }
+}
+
+/// A page of the list, scrolled so the selected name is on it, with an arrow next to it
+fn draw_menu<N: AsRef<[u8]>>(
+    count: usize,
+    name: &mut impl FnMut(usize) -> N,
+    selected: usize,
+) -> Display {
+    let mut display = Display::default();
+    let first = selected.saturating_sub(LINES - 1);
+    for (line, i) in (first..count).take(LINES).enumerate() {
+        let marker: &[u8] = if i == selected { b">" } else { b" " };
+        let name = name(i);
+        let text = [marker, b" ", name.as_ref()];
+        for (column, &character) in text.into_iter().flatten().enumerate() {
+            draw_character(
+                &mut display,
+                column * CHARACTER_WIDTH,
+                line * LINE_HEIGHT,
+                character,
+            );
+        }
+    }
+    display
+}
+
+fn draw_character(display: &mut Display, x: usize, y: usize, character: u8) {
+    if x + 3 > DISPLAY_WIDTH {
+        return;
+    }
+    for (dy, row) in glyph(character).into_iter().enumerate() {
+        for dx in 0..3 {
+            if row >> (2 - dx) & 1 == 1 {
+                display.set_pixel(x + dx, y + dy, true);
+            }
+        }
+    }
+}
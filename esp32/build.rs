@@ -0,0 +1,8 @@
+use std::env;
+
+fn main() {
+    // defmt's linker script keeps its log strings out of flash
+    if env::var_os("CARGO_FEATURE_DEFMT").is_some() {
+        println!("cargo:rustc-link-arg=-Tdefmt.x");
+    }
+}
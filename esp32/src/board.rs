@@ -0,0 +1,18 @@
+use esp_hal::{rng::Rng, time::Instant};
+use rs_chip8_embedded::Monotonic;
+
+pub struct HardwareRng(pub Rng);
+
+impl rs_chip8_embedded::Rng for HardwareRng {
+    fn random_byte(&mut self) -> u8 {
+        self.0.random() as u8
+    }
+}
+
+pub struct Clock;
+
+impl Monotonic for Clock {
+    fn now_micros(&mut self) -> u64 {
+        Instant::now().duration_since_epoch().as_micros()
+    }
+}
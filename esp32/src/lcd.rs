@@ -0,0 +1,111 @@
+use display_interface::{DataFormat, DisplayError, WriteOnlyDataCommand};
+use embedded_hal::{delay::DelayNs, digital::OutputPin};
+use rs_chip8_core::{DISPLAY_HEIGHT, DISPLAY_WIDTH, Display};
+use rs_chip8_embedded::Screen;
+
+/// The LCD's size, turned on its side
+const WIDTH: usize = 320;
+const HEIGHT: usize = 240;
+/// Each of the display's pixels is drawn as a square this many LCD pixels wide
+const SCALE: usize = 2;
+/// Where the display is drawn, centred on the LCD
+const LEFT: usize = (WIDTH - DISPLAY_WIDTH * SCALE) / 2;
+const TOP: usize = (HEIGHT - DISPLAY_HEIGHT * SCALE) / 2;
+
+/// RGB565 colours of pixels that are on and off
+const ON: u16 = 0xFFFF;
+const OFF: u16 = 0x0000;
+
+const SOFTWARE_RESET: u8 = 0x01;
+const SLEEP_OUT: u8 = 0x11;
+const INVERSION_ON: u8 = 0x21;
+const DISPLAY_ON: u8 = 0x29;
+const COLUMN_ADDRESS: u8 = 0x2A;
+const ROW_ADDRESS: u8 = 0x2B;
+const MEMORY_WRITE: u8 = 0x2C;
+const MEMORY_ACCESS: u8 = 0x36;
+const PIXEL_FORMAT: u8 = 0x3A;
+
+/// A 240x320 ST7789 LCD, with only what's needed to show the display
+pub struct Lcd<DI> {
+    interface: DI,
+}
+
+impl<DI: WriteOnlyDataCommand> Lcd<DI> {
+    pub fn new(
+        interface: DI,
+        reset: &mut impl OutputPin,
+        delay: &mut impl DelayNs,
+    ) -> Result<Self, DisplayError> {
+        reset.set_low().map_err(|_| DisplayError::RSError)?;
+        delay.delay_ms(10);
+        reset.set_high().map_err(|_| DisplayError::RSError)?;
+        delay.delay_ms(120);
+
+        let mut lcd = Self { interface };
+        lcd.command(SOFTWARE_RESET, &[])?;
+        delay.delay_ms(150);
+        lcd.command(SLEEP_OUT, &[])?;
+        delay.delay_ms(10);
+        // 16 bit colour
+        lcd.command(PIXEL_FORMAT, &[0x55])?;
+        // Swap rows and columns, and mirror columns, to turn it into landscape
+        lcd.command(MEMORY_ACCESS, &[0x60])?;
+        // These panels show colours inverted without this
+        lcd.command(INVERSION_ON, &[])?;
+        lcd.command(DISPLAY_ON, &[])?;
+
+        lcd.set_window(0, 0, WIDTH, HEIGHT)?;
+        lcd.interface
+            .send_data(DataFormat::U16BEIter(&mut (0..WIDTH * HEIGHT).map(|_| OFF)))?;
+        Ok(lcd)
+    }
+
+    fn command(&mut self, command: u8, parameters: &[u8]) -> Result<(), DisplayError> {
+        self.interface.send_commands(DataFormat::U8(&[command]))?;
+        if !parameters.is_empty() {
+            self.interface.send_data(DataFormat::U8(parameters))?;
+        }
+        Ok(())
+    }
+
+    /// Set the area the next pixels are written to, then start writing them
+    fn set_window(
+        &mut self,
+        x: usize,
+        y: usize,
+        width: usize,
+        height: usize,
+    ) -> Result<(), DisplayError> {
+        let [left_high, left_low] = (x as u16).to_be_bytes();
+        let [right_high, right_low] = ((x + width - 1) as u16).to_be_bytes();
+        self.command(
+            COLUMN_ADDRESS,
+            &[left_high, left_low, right_high, right_low],
+        )?;
+        let [top_high, top_low] = (y as u16).to_be_bytes();
+        let [bottom_high, bottom_low] = ((y + height - 1) as u16).to_be_bytes();
+        self.command(ROW_ADDRESS, &[top_high, top_low, bottom_high, bottom_low])?;
+        self.command(MEMORY_WRITE, &[])
+    }
+}
+
+impl<DI: WriteOnlyDataCommand> Screen for Lcd<DI> {
+    type Error = DisplayError;
+
+    fn flush(&mut self, display: &Display, dirty_rows: u64) -> Result<(), Self::Error> {
+        for y in (0..DISPLAY_HEIGHT).filter(|y| dirty_rows >> y & 1 == 1) {
+            self.set_window(LEFT, TOP + y * SCALE, DISPLAY_WIDTH * SCALE, SCALE)?;
+            let mut pixels = (0..DISPLAY_WIDTH * SCALE * SCALE).map(|i| {
+                if display.pixel(i % (DISPLAY_WIDTH * SCALE) / SCALE, y) {
+                    ON
+                } else {
+                    OFF
+                }
+            });
+            self.interface
+                .send_data(DataFormat::U16BEIter(&mut pixels))?;
+        }
+        Ok(())
+    }
+}
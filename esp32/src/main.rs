@@ -0,0 +1,103 @@
+//! Runs a ROM on an ESP32-C3, with an ST7789 LCD over SPI and a 4x4 matrix keypad
+//!
+//! The ROM is built in, from the absolute path in the `CHIP8_ROM` environment variable.
+//!
+//! | Part            | Pins                                                     |
+//! |-----------------|----------------------------------------------------------|
+//! | ST7789          | GPIO4 RESET, GPIO5 DC, GPIO6 SCK, GPIO7 MOSI, GPIO10 CS  |
+//! | Keypad rows     | GPIO0 to GPIO3, top to bottom                            |
+//! | Keypad columns  | GPIO8, GPIO9, GPIO18, GPIO19, left to right              |
+//!
+//! GPIO18 and GPIO19 are the USB pins, so flash it over the UART.
+
+#![no_std]
+#![no_main]
+
+mod board;
+mod lcd;
+
+use board::{Clock, HardwareRng};
+use display_interface_spi::SPIInterface;
+use embedded_hal_bus::spi::ExclusiveDevice;
+use esp_hal::{
+    delay::Delay,
+    gpio::{DriveMode, Input, InputConfig, Level, Output, OutputConfig, Pull},
+    rng::{Rng, TrngSource},
+    spi::{
+        Mode,
+        master::{Config, Spi},
+    },
+    time::Rate,
+};
+#[cfg(feature = "defmt")]
+use esp_println as _;
+use lcd::Lcd;
+use panic_halt as _;
+use rs_chip8_core::{EmulationSystem, MachineState};
+use rs_chip8_embedded::{MatrixKeypad, run};
+
+esp_bootloader_esp_idf::esp_app_desc!();
+
+const PROGRAM: &[u8] = include_bytes!(env!("CHIP8_ROM"));
+const SYSTEM: EmulationSystem = EmulationSystem::SuperChip;
+const IPF: u32 = 10;
+
+#[esp_hal::main]
+fn main() -> ! {
+    let peripherals = esp_hal::init(esp_hal::Config::default());
+    let mut delay = Delay::new();
+
+    let spi = Spi::new(
+        peripherals.SPI2,
+        Config::default()
+            .with_frequency(Rate::from_mhz(40))
+            .with_mode(Mode::_0),
+    )
+    .unwrap()
+    .with_sck(peripherals.GPIO6)
+    .with_mosi(peripherals.GPIO7);
+    let cs = Output::new(peripherals.GPIO10, Level::High, OutputConfig::default());
+    let dc = Output::new(peripherals.GPIO5, Level::Low, OutputConfig::default());
+    let mut reset = Output::new(peripherals.GPIO4, Level::High, OutputConfig::default());
+    let spi = ExclusiveDevice::new_no_delay(spi, cs).unwrap();
+    let mut lcd = Lcd::new(SPIInterface::new(spi, dc), &mut reset, &mut delay).unwrap();
+
+    // Rows are pulled low one at a time, open drain so two keys held in a column can't short them
+    let row_config = OutputConfig::default().with_drive_mode(DriveMode::OpenDrain);
+    let rows = [
+        Output::new(peripherals.GPIO0, Level::High, row_config),
+        Output::new(peripherals.GPIO1, Level::High, row_config),
+        Output::new(peripherals.GPIO2, Level::High, row_config),
+        Output::new(peripherals.GPIO3, Level::High, row_config),
+    ];
+    let column_config = InputConfig::default().with_pull(Pull::Up);
+    let columns = [
+        Input::new(peripherals.GPIO8, column_config),
+        Input::new(peripherals.GPIO9, column_config),
+        Input::new(peripherals.GPIO18, column_config),
+        Input::new(peripherals.GPIO19, column_config),
+    ];
+
+    // The RNG is only truly random while it has a source of noise
+    let _trng_source = TrngSource::new(peripherals.RNG, peripherals.ADC1);
+
+    let mut machine_state = MachineState::new(SYSTEM);
+    machine_state.load_default_font();
+    machine_state.load_program(PROGRAM);
+    #[cfg(feature = "defmt")]
+    defmt::info!("Running a {=usize} byte program", PROGRAM.len());
+
+    // Leave the last frame on the display, and stop
+    run(
+        &mut machine_state,
+        IPF,
+        &mut lcd,
+        &mut MatrixKeypad::new(rows, columns, delay),
+        &mut (),
+        &mut HardwareRng(Rng::new()),
+        &mut Clock,
+    );
+    loop {
+        core::hint::spin_loop();
+    }
+}
@@ -0,0 +1,122 @@
+//! The interpreter for JavaScript, to embed in other sites and tools
+//!
+//! Build the npm package with `wasm-pack build --target bundler`, then publish `pkg/`.
+//! The page is left to draw the framebuffer, read the keyboard, and time frames at 60 Hz:
+//!
+//! ```js
+//! import { Chip8 } from "rs_chip8_js";
+//!
+//! const chip8 = new Chip8(false);
+//! chip8.load_rom(new Uint8Array(await (await fetch("pong.ch8")).arrayBuffer()));
+//! function frame() {
+//!   chip8.set_keys(heldKeys);
+//!   chip8.run_frame(10);
+//!   draw(chip8.get_framebuffer(), Chip8.width(), Chip8.height());
+//!   requestAnimationFrame(frame);
+//! }
+//! ```
+
+use rs_chip8_core::{
+    DISPLAY_HEIGHT, DISPLAY_WIDTH, EmulationSystem, MachineState, PROGRAM_ADDRESS, RAM_SIZE,
+    STATE_SIZE,
+};
+use wasm_bindgen::prelude::*;
+
+#[wasm_bindgen]
+pub struct Chip8 {
+    machine_state: MachineState,
+    held_keys: u16,
+}
+
+#[wasm_bindgen]
+impl Chip8 {
+    /// An interpreter with nothing loaded, for SUPER-CHIP programs if `super_chip` is set
+    #[wasm_bindgen(constructor)]
+    pub fn new(super_chip: bool) -> Self {
+        let system = if super_chip {
+            EmulationSystem::SuperChip
+        } else {
+            EmulationSystem::Chip8
+        };
+
+        Self {
+            machine_state: MachineState::new(system),
+            held_keys: 0,
+        }
+    }
+
+    /// Start a program from scratch, clearing whatever was running before,
+    /// throwing if it's too big to fit in memory
+    pub fn load_rom(&mut self, rom: &[u8]) -> Result<(), JsError> {
+        let max_len = RAM_SIZE - PROGRAM_ADDRESS as usize;
+        if rom.len() > max_len {
+            return Err(JsError::new(&format!(
+                "The ROM is too big, at {} bytes when {max_len} fit in memory",
+                rom.len()
+            )));
+        }
+
+        let mut machine_state = MachineState::new(self.machine_state.system());
+        machine_state.set_quirks(self.machine_state.quirks());
+        machine_state.load_default_font();
+        machine_state.load_program(rom);
+        self.machine_state = machine_state;
+        Ok(())
+    }
+
+    /// Run one 60 Hz frame of `ipf` instructions, throwing if the program fails or exits
+    pub fn run_frame(&mut self, ipf: u32) -> Result<(), JsError> {
+        let held_keys = self.held_keys;
+        self.machine_state.run_frame(
+            ipf,
+            || held_keys,
+            || (js_sys::Math::random() * 256.) as u8,
+        )?;
+        Ok(())
+    }
+
+    /// Set the keys held, with bit n set for key n
+    pub fn set_keys(&mut self, keys: u16) {
+        self.held_keys = keys;
+    }
+
+    /// The display, a byte for each pixel that's 1 if it's on and 0 if not,
+    /// row by row from the top left
+    pub fn get_framebuffer(&self) -> Vec<u8> {
+        let display = self.machine_state.display();
+        (0..DISPLAY_HEIGHT)
+            .flat_map(|y| (0..DISPLAY_WIDTH).map(move |x| display.pixel(x, y) as u8))
+            .collect()
+    }
+
+    /// Whether the display has changed since this was last called, so it only needs redrawing then
+    pub fn take_display_changed(&mut self) -> bool {
+        self.machine_state.take_dirty_rows() != 0
+    }
+
+    /// Whether the sound timer is running, which is when the beeper should sound
+    pub fn is_beeping(&self) -> bool {
+        self.machine_state.sound_timer() > 0
+    }
+
+    pub fn width() -> usize {
+        DISPLAY_WIDTH
+    }
+
+    pub fn height() -> usize {
+        DISPLAY_HEIGHT
+    }
+
+    /// Everything about the running program, to restore later with [`Self::load_state`]
+    pub fn save_state(&self) -> Vec<u8> {
+        let mut state = [0; STATE_SIZE];
+        self.machine_state.save_state(&mut state);
+        state.to_vec()
+    }
+
+    /// Restore a state from [`Self::save_state`], throwing if it isn't one
+    pub fn load_state(&mut self, state: &[u8]) -> Result<(), JsError> {
+        self.machine_state.load_state(state)?;
+        Ok(())
+    }
+}
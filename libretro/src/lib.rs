@@ -0,0 +1,537 @@
+//! A libretro core, so ROMs can be run in RetroArch and other libretro frontends
+//!
+//! The keypad is mapped onto the RetroPad, with 2, 4, 6, and 8 on the D-pad and 5 on A,
+//! and onto the 1234/QWER/ASDF/ZXCV block of the keyboard.
+//! Speed, quirks, and the palette are core options.
+
+// Every export is only called by the frontend, with the pointers `libretro.h` says it passes
+#![allow(clippy::missing_safety_doc)]
+
+mod ffi;
+
+use ffi::*;
+use rs_chip8_core::{
+    DISPLAY_HEIGHT, DISPLAY_WIDTH, EmulationSystem, MachineState, PROGRAM_ADDRESS, Quirks,
+    RAM_SIZE, STATE_SIZE,
+};
+use std::{
+    ffi::{CStr, c_char, c_uint, c_void},
+    ptr, slice,
+    sync::Mutex,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+const SAMPLE_RATE: f64 = 44100.;
+const SAMPLES_PER_FRAME: usize = 735;
+const FREQUENCY: f32 = 440.;
+const VOLUME: i16 = 0x1000;
+
+const IPF_OPTION: &CStr = c"rs_chip8_ipf";
+const QUIRKS_OPTION: &CStr = c"rs_chip8_quirks";
+const PALETTE_OPTION: &CStr = c"rs_chip8_palette";
+
+/// The RetroPad button for each CHIP-8 key, with most games' directions on the D-pad
+const JOYPAD: [c_uint; 16] = [
+    RETRO_DEVICE_ID_JOYPAD_B,
+    RETRO_DEVICE_ID_JOYPAD_X,
+    RETRO_DEVICE_ID_JOYPAD_UP,
+    RETRO_DEVICE_ID_JOYPAD_Y,
+    RETRO_DEVICE_ID_JOYPAD_LEFT,
+    RETRO_DEVICE_ID_JOYPAD_A,
+    RETRO_DEVICE_ID_JOYPAD_RIGHT,
+    RETRO_DEVICE_ID_JOYPAD_L,
+    RETRO_DEVICE_ID_JOYPAD_DOWN,
+    RETRO_DEVICE_ID_JOYPAD_R,
+    RETRO_DEVICE_ID_JOYPAD_L2,
+    RETRO_DEVICE_ID_JOYPAD_R2,
+    RETRO_DEVICE_ID_JOYPAD_L3,
+    RETRO_DEVICE_ID_JOYPAD_R3,
+    RETRO_DEVICE_ID_JOYPAD_SELECT,
+    RETRO_DEVICE_ID_JOYPAD_START,
+];
+
+/// The keycode for each CHIP-8 key, the COSMAC VIP keypad on the 1234/QWER/ASDF/ZXCV block
+///
+/// libretro's keycodes for letters and digits are their lower case ASCII codes.
+const KEYBOARD: [u8; 16] = *b"x123qweasdzc4rfv";
+
+const KEY_NAMES: [&CStr; 16] = [
+    c"0", c"1", c"2", c"3", c"4", c"5", c"6", c"7", c"8", c"9", c"A", c"B", c"C", c"D", c"E", c"F",
+];
+
+#[derive(Clone, Copy)]
+struct Callbacks {
+    environment: Option<RetroEnvironment>,
+    video_refresh: Option<RetroVideoRefresh>,
+    audio_sample_batch: Option<RetroAudioSampleBatch>,
+    input_poll: Option<RetroInputPoll>,
+    input_state: Option<RetroInputState>,
+}
+
+static CALLBACKS: Mutex<Callbacks> = Mutex::new(Callbacks {
+    environment: None,
+    video_refresh: None,
+    audio_sample_batch: None,
+    input_poll: None,
+    input_state: None,
+});
+
+/// The loaded game, `None` until one is
+static CORE: Mutex<Option<Core>> = Mutex::new(None);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Options {
+    ipf: u32,
+    /// The system to take quirks from, or `None` for the one the ROM runs as
+    quirks: Option<EmulationSystem>,
+    /// XRGB8888 colours of pixels that are off and on
+    colours: [u32; 2],
+}
+
+impl Options {
+    /// Read the options set in the frontend, the first value of an option being its default
+    fn read(environment: RetroEnvironment) -> Self {
+        let ipf = variable(environment, IPF_OPTION)
+            .and_then(|ipf| ipf.parse().ok())
+            .unwrap_or(10);
+        let quirks = match variable(environment, QUIRKS_OPTION).as_deref() {
+            Some("CHIP-8") => Some(EmulationSystem::Chip8),
+            Some("SUPER-CHIP") => Some(EmulationSystem::SuperChip),
+            _ => None,
+        };
+        // The same colours as the desktop frontend's palettes
+        let colours = match variable(environment, PALETTE_OPTION).as_deref() {
+            Some("Classic green") => [0x9bbc0f, 0x0f380f],
+            Some("Amber") => [0x1a0f00, 0xffb000],
+            Some("High contrast") => [0x000000, 0xffffff],
+            _ => [0x8f9185, 0x111d2b],
+        };
+
+        Self {
+            ipf,
+            quirks,
+            colours,
+        }
+    }
+}
+
+/// The value of a core option, if the frontend has one for it
+fn variable(environment: RetroEnvironment, key: &CStr) -> Option<String> {
+    let mut variable = RetroVariable {
+        key: key.as_ptr(),
+        value: ptr::null(),
+    };
+    let found = unsafe { environment(RETRO_ENVIRONMENT_GET_VARIABLE, (&raw mut variable).cast()) };
+    if !found || variable.value.is_null() {
+        return None;
+    }
+    // SAFETY: the frontend set the value to a string that lasts until the next call
+    let value = unsafe { CStr::from_ptr(variable.value) };
+    Some(value.to_string_lossy().into_owned())
+}
+
+struct Core {
+    machine_state: MachineState,
+    /// Kept to start the program again on reset
+    program: Vec<u8>,
+    system: EmulationSystem,
+    options: Options,
+    /// Set once the program fails or exits, leaving the last frame showing
+    stopped: bool,
+    /// The display as XRGB8888
+    frame: Vec<u32>,
+    /// Rows of `frame` that have to be drawn again, on top of the display's dirty rows
+    redraw_rows: u64,
+    /// How far through a cycle of the beep the audio is
+    phase: f32,
+    /// xorshift32 state, for CXNN
+    random: u32,
+}
+
+impl Core {
+    fn new(program: Vec<u8>, system: EmulationSystem, options: Options) -> Self {
+        let seed = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |time| time.subsec_nanos());
+        let mut core = Self {
+            machine_state: MachineState::new(system),
+            program,
+            system,
+            options,
+            stopped: false,
+            frame: vec![options.colours[0]; DISPLAY_WIDTH * DISPLAY_HEIGHT],
+            redraw_rows: u64::MAX,
+            phase: 0.,
+            // xorshift gets stuck on 0
+            random: seed | 1,
+        };
+        core.reset();
+        core
+    }
+
+    fn reset(&mut self) {
+        self.machine_state = MachineState::new(self.system);
+        self.apply_quirks();
+        self.machine_state.load_default_font();
+        self.machine_state.load_program(&self.program);
+        self.stopped = false;
+    }
+
+    fn apply_quirks(&mut self) {
+        let system = self.options.quirks.unwrap_or(self.system);
+        self.machine_state.set_quirks(Quirks::for_system(system));
+    }
+
+    fn set_options(&mut self, options: Options) {
+        if options.colours != self.options.colours {
+            self.redraw_rows = u64::MAX;
+        }
+        self.options = options;
+        self.apply_quirks();
+    }
+
+    fn run_frame(&mut self, held_keys: u16) {
+        if self.stopped {
+            return;
+        }
+
+        let random = &mut self.random;
+        let result = self.machine_state.run_frame(
+            self.options.ipf,
+            || held_keys,
+            || {
+                *random ^= *random << 13;
+                *random ^= *random >> 17;
+                *random ^= *random << 5;
+                *random as u8
+            },
+        );
+        self.stopped = result.is_err();
+    }
+
+    /// Bring the frame up to date with the display
+    fn draw(&mut self) {
+        let dirty_rows =
+            self.machine_state.take_dirty_rows() | std::mem::take(&mut self.redraw_rows);
+        let display = self.machine_state.display();
+        for y in (0..DISPLAY_HEIGHT).filter(|y| dirty_rows >> y & 1 == 1) {
+            let row = &mut self.frame[y * DISPLAY_WIDTH..(y + 1) * DISPLAY_WIDTH];
+            for (x, pixel) in row.iter_mut().enumerate() {
+                *pixel = self.options.colours[display.pixel(x, y) as usize];
+            }
+        }
+    }
+
+    /// A frame's worth of stereo samples, a square wave while the sound timer runs
+    fn audio(&mut self) -> [i16; SAMPLES_PER_FRAME * 2] {
+        let mut samples = [0; SAMPLES_PER_FRAME * 2];
+        if self.stopped || self.machine_state.sound_timer() == 0 {
+            return samples;
+        }
+
+        for frame in samples.chunks_exact_mut(2) {
+            frame.fill(if self.phase < 0.5 { VOLUME } else { -VOLUME });
+            self.phase = (self.phase + FREQUENCY / SAMPLE_RATE as f32) % 1.;
+        }
+        samples
+    }
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn retro_api_version() -> c_uint {
+    RETRO_API_VERSION
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn retro_set_environment(environment: RetroEnvironment) {
+    CALLBACKS.lock().unwrap().environment = Some(environment);
+
+    let variables = [
+        RetroVariable {
+            key: IPF_OPTION.as_ptr(),
+            value: c"Instructions per frame; 10|15|20|30|50|100|200|500|1000|5".as_ptr(),
+        },
+        RetroVariable {
+            key: QUIRKS_OPTION.as_ptr(),
+            value: c"Quirks; From the ROM's extension|CHIP-8|SUPER-CHIP".as_ptr(),
+        },
+        RetroVariable {
+            key: PALETTE_OPTION.as_ptr(),
+            value: c"Palette; LCD|Classic green|Amber|High contrast".as_ptr(),
+        },
+        RetroVariable {
+            key: ptr::null(),
+            value: ptr::null(),
+        },
+    ];
+    unsafe {
+        environment(
+            RETRO_ENVIRONMENT_SET_VARIABLES,
+            variables.as_ptr().cast_mut().cast(),
+        )
+    };
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn retro_set_video_refresh(video_refresh: RetroVideoRefresh) {
+    CALLBACKS.lock().unwrap().video_refresh = Some(video_refresh);
+}
+
+/// Unused, all of a frame's audio is sent at once with the batch callback
+#[unsafe(no_mangle)]
+pub extern "C" fn retro_set_audio_sample(_audio_sample: RetroAudioSample) {}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn retro_set_audio_sample_batch(audio_sample_batch: RetroAudioSampleBatch) {
+    CALLBACKS.lock().unwrap().audio_sample_batch = Some(audio_sample_batch);
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn retro_set_input_poll(input_poll: RetroInputPoll) {
+    CALLBACKS.lock().unwrap().input_poll = Some(input_poll);
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn retro_set_input_state(input_state: RetroInputState) {
+    CALLBACKS.lock().unwrap().input_state = Some(input_state);
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn retro_init() {}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn retro_deinit() {
+    *CORE.lock().unwrap() = None;
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn retro_get_system_info(info: *mut RetroSystemInfo) {
+    unsafe {
+        info.write(RetroSystemInfo {
+            library_name: c"rs_chip8".as_ptr(),
+            library_version: concat!(env!("CARGO_PKG_VERSION"), "\0").as_ptr().cast(),
+            valid_extensions: c"ch8|sc8|c8".as_ptr(),
+            need_fullpath: false,
+            block_extract: false,
+        })
+    };
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn retro_get_system_av_info(info: *mut RetroSystemAvInfo) {
+    unsafe {
+        info.write(RetroSystemAvInfo {
+            geometry: RetroGameGeometry {
+                base_width: DISPLAY_WIDTH as c_uint,
+                base_height: DISPLAY_HEIGHT as c_uint,
+                max_width: DISPLAY_WIDTH as c_uint,
+                max_height: DISPLAY_HEIGHT as c_uint,
+                aspect_ratio: DISPLAY_WIDTH as f32 / DISPLAY_HEIGHT as f32,
+            },
+            timing: RetroSystemTiming {
+                fps: 60.,
+                sample_rate: SAMPLE_RATE,
+            },
+        })
+    };
+}
+
+/// There's only the one kind of controller
+#[unsafe(no_mangle)]
+pub extern "C" fn retro_set_controller_port_device(_port: c_uint, _device: c_uint) {}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn retro_reset() {
+    if let Some(core) = CORE.lock().unwrap().as_mut() {
+        core.reset();
+    }
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn retro_run() {
+    let callbacks = *CALLBACKS.lock().unwrap();
+    let mut core = CORE.lock().unwrap();
+    let Some(core) = core.as_mut() else {
+        return;
+    };
+
+    if let Some(environment) = callbacks.environment {
+        let mut updated = false;
+        let has_update = unsafe {
+            environment(
+                RETRO_ENVIRONMENT_GET_VARIABLE_UPDATE,
+                (&raw mut updated).cast(),
+            )
+        };
+        if has_update && updated {
+            core.set_options(Options::read(environment));
+        }
+    }
+
+    let mut held_keys = 0;
+    if let (Some(input_poll), Some(input_state)) = (callbacks.input_poll, callbacks.input_state) {
+        unsafe { input_poll() };
+        for key in 0..16 {
+            let held = unsafe {
+                input_state(0, RETRO_DEVICE_JOYPAD, 0, JOYPAD[key]) != 0
+                    || input_state(0, RETRO_DEVICE_KEYBOARD, 0, KEYBOARD[key] as c_uint) != 0
+            };
+            held_keys |= (held as u16) << key;
+        }
+    }
+
+    core.run_frame(held_keys);
+    core.draw();
+
+    if let Some(video_refresh) = callbacks.video_refresh {
+        unsafe {
+            video_refresh(
+                core.frame.as_ptr().cast(),
+                DISPLAY_WIDTH as c_uint,
+                DISPLAY_HEIGHT as c_uint,
+                DISPLAY_WIDTH * size_of::<u32>(),
+            )
+        };
+    }
+    let samples = core.audio();
+    if let Some(audio_sample_batch) = callbacks.audio_sample_batch {
+        unsafe { audio_sample_batch(samples.as_ptr(), SAMPLES_PER_FRAME) };
+    }
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn retro_serialize_size() -> usize {
+    STATE_SIZE
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn retro_serialize(data: *mut c_void, size: usize) -> bool {
+    let core = CORE.lock().unwrap();
+    let Some(core) = core.as_ref() else {
+        return false;
+    };
+    if size < STATE_SIZE {
+        return false;
+    }
+
+    let buffer = unsafe { &mut *data.cast::<[u8; STATE_SIZE]>() };
+    core.machine_state.save_state(buffer);
+    true
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn retro_unserialize(data: *const c_void, size: usize) -> bool {
+    let mut core = CORE.lock().unwrap();
+    let Some(core) = core.as_mut() else {
+        return false;
+    };
+    if data.is_null() {
+        return false;
+    }
+
+    let buffer = unsafe { slice::from_raw_parts(data.cast::<u8>(), size) };
+    if core.machine_state.load_state(buffer).is_err() {
+        return false;
+    }
+    core.stopped = false;
+    core.redraw_rows = u64::MAX;
+    true
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn retro_cheat_reset() {}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn retro_cheat_set(_index: c_uint, _enabled: bool, _code: *const c_char) {}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn retro_load_game(game: *const RetroGameInfo) -> bool {
+    let Some(game) = (unsafe { game.as_ref() }) else {
+        return false;
+    };
+    // Programs are loaded after the interpreter's reserved memory, and have to fit in the rest
+    if game.data.is_null() || game.size > RAM_SIZE - PROGRAM_ADDRESS as usize {
+        return false;
+    }
+    let program = unsafe { slice::from_raw_parts(game.data.cast::<u8>(), game.size) }.to_vec();
+
+    let is_super_chip = !game.path.is_null()
+        && unsafe { CStr::from_ptr(game.path) }
+            .to_bytes()
+            .to_ascii_lowercase()
+            .ends_with(b".sc8");
+    let system = if is_super_chip {
+        EmulationSystem::SuperChip
+    } else {
+        EmulationSystem::Chip8
+    };
+
+    let Some(environment) = CALLBACKS.lock().unwrap().environment else {
+        return false;
+    };
+    let mut pixel_format = RETRO_PIXEL_FORMAT_XRGB8888;
+    if !unsafe {
+        environment(
+            RETRO_ENVIRONMENT_SET_PIXEL_FORMAT,
+            (&raw mut pixel_format).cast(),
+        )
+    } {
+        return false;
+    }
+
+    let mut descriptors = (0..16)
+        .map(|key| RetroInputDescriptor {
+            port: 0,
+            device: RETRO_DEVICE_JOYPAD,
+            index: 0,
+            id: JOYPAD[key],
+            description: KEY_NAMES[key].as_ptr(),
+        })
+        .collect::<Vec<_>>();
+    descriptors.push(RetroInputDescriptor {
+        port: 0,
+        device: 0,
+        index: 0,
+        id: 0,
+        description: ptr::null(),
+    });
+    unsafe {
+        environment(
+            RETRO_ENVIRONMENT_SET_INPUT_DESCRIPTORS,
+            descriptors.as_mut_ptr().cast(),
+        )
+    };
+
+    *CORE.lock().unwrap() = Some(Core::new(program, system, Options::read(environment)));
+    true
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn retro_load_game_special(
+    _game_type: c_uint,
+    _info: *const RetroGameInfo,
+    _num_info: usize,
+) -> bool {
+    false
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn retro_unload_game() {
+    *CORE.lock().unwrap() = None;
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn retro_get_region() -> c_uint {
+    RETRO_REGION_NTSC
+}
+
+/// Memory isn't exposed, so achievements and RetroArch's cheats don't work
+#[unsafe(no_mangle)]
+pub extern "C" fn retro_get_memory_data(_id: c_uint) -> *mut c_void {
+    ptr::null_mut()
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn retro_get_memory_size(_id: c_uint) -> usize {
+    0
+}
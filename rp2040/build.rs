@@ -0,0 +1,14 @@
+use std::{env, fs, path::PathBuf};
+
+fn main() {
+    // Put the memory layout where cortex-m-rt's linker script can find it
+    let out_dir = PathBuf::from(env::var_os("OUT_DIR").unwrap());
+    fs::write(out_dir.join("memory.x"), include_bytes!("memory.x")).unwrap();
+    println!("cargo:rustc-link-search={}", out_dir.display());
+    println!("cargo:rerun-if-changed=memory.x");
+
+    // defmt's linker script keeps its log strings out of flash
+    if env::var_os("CARGO_FEATURE_DEFMT").is_some() {
+        println!("cargo:rustc-link-arg=-Tdefmt.x");
+    }
+}
@@ -0,0 +1,113 @@
+use cortex_m::peripheral::NVIC;
+use display_interface::{DisplayError, WriteOnlyDataCommand};
+use embedded_hal::{digital::InputPin, pwm::SetDutyCycle};
+use rp2040_hal::{
+    Timer,
+    gpio::{DynPinId, FunctionSioInput, Interrupt, Pin, PullUp},
+    pac,
+    pwm::{A, Channel, FreeRunning, Pwm0, Slice},
+    rosc::{Enabled, RingOscillator},
+};
+use rs_chip8_core::{DISPLAY_HEIGHT, DISPLAY_WIDTH, Display};
+use rs_chip8_embedded::{Beeper, Keypad, Monotonic, Rng, Screen};
+use ssd1306::{Ssd1306, mode::BufferedGraphicsMode, prelude::*};
+
+pub struct Oled<DI>(pub Ssd1306<DI, DisplaySize128x64, BufferedGraphicsMode<DisplaySize128x64>>);
+
+impl<DI: WriteOnlyDataCommand> Screen for Oled<DI> {
+    type Error = DisplayError;
+
+    fn flush(&mut self, display: &Display, dirty_rows: u64) -> Result<(), Self::Error> {
+        // The SSD1306 is written in pages of 8 rows, so only pages with a changed row are sent,
+        // each on its own so unchanged pages between them are skipped
+        for page in (0..DISPLAY_HEIGHT).step_by(8) {
+            if dirty_rows >> page & 0xFF == 0 {
+                continue;
+            }
+            for y in (page..page + 8).filter(|y| dirty_rows >> y & 1 == 1) {
+                for x in 0..DISPLAY_WIDTH {
+                    self.0.set_pixel(x as u32, y as u32, display.pixel(x, y));
+                }
+            }
+            self.0.flush()?;
+        }
+        Ok(())
+    }
+}
+
+/// A button to ground for each key
+pub struct Buttons(pub [Pin<DynPinId, FunctionSioInput, PullUp>; 16]);
+
+impl Keypad for Buttons {
+    fn scan(&mut self) -> u16 {
+        // Buttons pull their pin low when pressed
+        self.0
+            .iter_mut()
+            .enumerate()
+            .fold(0, |held_keys, (key, pin)| {
+                held_keys | (pin.is_low().unwrap() as u16) << key
+            })
+    }
+
+    fn sleep_until_changed(&mut self, held_keys: u16) {
+        // The pins' edges wake the core from WFI through IO_IRQ_BANK0. Interrupts are masked,
+        // so there's no handler, the interrupt is just cleared again after waking
+        cortex_m::interrupt::free(|_| {
+            for pin in &mut self.0 {
+                pin.clear_interrupt(Interrupt::EdgeLow);
+                pin.clear_interrupt(Interrupt::EdgeHigh);
+                pin.set_interrupt_enabled(Interrupt::EdgeLow, true);
+                pin.set_interrupt_enabled(Interrupt::EdgeHigh, true);
+            }
+            // SAFETY: nothing else uses IO_IRQ_BANK0, and it's masked again before returning
+            unsafe { NVIC::unmask(pac::Interrupt::IO_IRQ_BANK0) };
+
+            // A key changing between the scan and WFI leaves the interrupt pending,
+            // so WFI returns straight away instead of missing it
+            while self.scan() == held_keys {
+                cortex_m::asm::wfi();
+                for pin in &mut self.0 {
+                    pin.clear_interrupt(Interrupt::EdgeLow);
+                    pin.clear_interrupt(Interrupt::EdgeHigh);
+                }
+                NVIC::unpend(pac::Interrupt::IO_IRQ_BANK0);
+            }
+
+            NVIC::mask(pac::Interrupt::IO_IRQ_BANK0);
+            for pin in &self.0 {
+                pin.set_interrupt_enabled(Interrupt::EdgeLow, false);
+                pin.set_interrupt_enabled(Interrupt::EdgeHigh, false);
+            }
+        });
+    }
+}
+
+pub struct Buzzer(pub Channel<Slice<Pwm0, FreeRunning>, A>);
+
+impl Beeper for Buzzer {
+    fn set_beeping(&mut self, beeping: bool) {
+        if beeping {
+            self.0.set_duty_cycle_percent(50).unwrap();
+        } else {
+            self.0.set_duty_cycle_fully_off().unwrap();
+        }
+    }
+}
+
+/// The ring oscillator's jitter is random enough for games
+pub struct Rosc(pub RingOscillator<Enabled>);
+
+impl Rng for Rosc {
+    fn random_byte(&mut self) -> u8 {
+        (0..8).fold(0, |byte, _| byte << 1 | self.0.get_random_bit() as u8)
+    }
+}
+
+/// The timer counts microseconds from boot
+pub struct TimerCounter(pub Timer);
+
+impl Monotonic for TimerCounter {
+    fn now_micros(&mut self) -> u64 {
+        self.0.get_counter().ticks()
+    }
+}
@@ -0,0 +1,195 @@
+//! Runs a ROM on a Raspberry Pi Pico, with an SSD1306 OLED over SPI, 16 buttons, and a buzzer
+//!
+//! The ROM is built in, from the absolute path in the `CHIP8_ROM` environment variable.
+//! With the `sd-card` feature it's chosen from an SD card's root directory at boot instead,
+//! with keys 2 and 8 to move and 5 to choose.
+//!
+//! | Part          | Pins                                              |
+//! |---------------|---------------------------------------------------|
+//! | Keys 0 to F   | GP0 to GP15, each a button to ground              |
+//! | Buzzer        | GP16                                              |
+//! | SSD1306       | GP17 CS, GP18 SCK, GP19 MOSI, GP20 DC, GP21 RESET |
+//! | SD card       | GP22 CS, GP26 SCK, GP27 MOSI, GP28 MISO           |
+
+#![no_std]
+#![no_main]
+
+mod board;
+
+use board::{Buttons, Buzzer, Oled, Rosc, TimerCounter};
+use display_interface_spi::SPIInterface;
+use embedded_hal::spi::MODE_0;
+use embedded_hal_bus::spi::ExclusiveDevice;
+#[cfg(feature = "sd-card")]
+use embedded_sdmmc::SdCard;
+use fugit::RateExtU32;
+#[cfg(not(feature = "defmt"))]
+use panic_halt as _;
+use rp2040_hal::{
+    Clock, Sio, Spi, Timer, Watchdog,
+    clocks::init_clocks_and_plls,
+    gpio::{FunctionSpi, Pins},
+    pac,
+    pwm::Slices,
+    rosc::RingOscillator,
+};
+#[cfg(not(feature = "sd-card"))]
+use rs_chip8_core::EmulationSystem;
+use rs_chip8_core::MachineState;
+#[cfg(feature = "sd-card")]
+use rs_chip8_core::{PROGRAM_ADDRESS, RAM_SIZE};
+#[cfg(feature = "sd-card")]
+use rs_chip8_embedded::choose_rom;
+use rs_chip8_embedded::run;
+use ssd1306::{Ssd1306, prelude::*};
+#[cfg(feature = "defmt")]
+use {defmt_rtt as _, panic_probe as _};
+
+/// Runs the RP2040's ROM, then loads the rest of the program from flash
+#[unsafe(link_section = ".boot2")]
+#[used]
+pub static BOOT2: [u8; 256] = rp2040_boot2::BOOT_LOADER_GENERIC_03H;
+
+const XTAL_FREQ_HZ: u32 = 12_000_000;
+
+#[cfg(not(feature = "sd-card"))]
+const PROGRAM: &[u8] = include_bytes!(env!("CHIP8_ROM"));
+#[cfg(not(feature = "sd-card"))]
+const SYSTEM: EmulationSystem = EmulationSystem::SuperChip;
+const IPF: u32 = 10;
+
+/// PWM clock divider and top, making a 440 Hz square wave from the 125 MHz system clock
+const BUZZER_DIVIDER: u8 = 64;
+const BUZZER_TOP: u16 = (125_000_000 / BUZZER_DIVIDER as u32 / 440) as u16;
+
+#[rp2040_hal::entry]
+fn main() -> ! {
+    let mut pac = pac::Peripherals::take().unwrap();
+    let mut watchdog = Watchdog::new(pac.WATCHDOG);
+    let clocks = init_clocks_and_plls(
+        XTAL_FREQ_HZ,
+        pac.XOSC,
+        pac.CLOCKS,
+        pac.PLL_SYS,
+        pac.PLL_USB,
+        &mut pac.RESETS,
+        &mut watchdog,
+    )
+    .ok()
+    .unwrap();
+    let mut timer = Timer::new(pac.TIMER, &mut pac.RESETS, &clocks);
+    let sio = Sio::new(pac.SIO);
+    let pins = Pins::new(
+        pac.IO_BANK0,
+        pac.PADS_BANK0,
+        sio.gpio_bank0,
+        &mut pac.RESETS,
+    );
+
+    let spi = Spi::<_, _, _, 8>::new(
+        pac.SPI0,
+        (
+            pins.gpio19.into_function::<FunctionSpi>(),
+            pins.gpio18.into_function::<FunctionSpi>(),
+        ),
+    )
+    .init(
+        &mut pac.RESETS,
+        clocks.peripheral_clock.freq(),
+        8.MHz(),
+        MODE_0,
+    );
+    let spi = ExclusiveDevice::new_no_delay(spi, pins.gpio17.into_push_pull_output()).unwrap();
+    let interface = SPIInterface::new(spi, pins.gpio20.into_push_pull_output());
+    let mut oled = Ssd1306::new(interface, DisplaySize128x64, DisplayRotation::Rotate0)
+        .into_buffered_graphics_mode();
+    oled.reset(&mut pins.gpio21.into_push_pull_output(), &mut timer)
+        .unwrap();
+    oled.init().unwrap();
+
+    let keys = [
+        pins.gpio0.into_pull_up_input().into_dyn_pin(),
+        pins.gpio1.into_pull_up_input().into_dyn_pin(),
+        pins.gpio2.into_pull_up_input().into_dyn_pin(),
+        pins.gpio3.into_pull_up_input().into_dyn_pin(),
+        pins.gpio4.into_pull_up_input().into_dyn_pin(),
+        pins.gpio5.into_pull_up_input().into_dyn_pin(),
+        pins.gpio6.into_pull_up_input().into_dyn_pin(),
+        pins.gpio7.into_pull_up_input().into_dyn_pin(),
+        pins.gpio8.into_pull_up_input().into_dyn_pin(),
+        pins.gpio9.into_pull_up_input().into_dyn_pin(),
+        pins.gpio10.into_pull_up_input().into_dyn_pin(),
+        pins.gpio11.into_pull_up_input().into_dyn_pin(),
+        pins.gpio12.into_pull_up_input().into_dyn_pin(),
+        pins.gpio13.into_pull_up_input().into_dyn_pin(),
+        pins.gpio14.into_pull_up_input().into_dyn_pin(),
+        pins.gpio15.into_pull_up_input().into_dyn_pin(),
+    ];
+
+    let mut pwm = Slices::new(pac.PWM, &mut pac.RESETS).pwm0;
+    pwm.set_div_int(BUZZER_DIVIDER);
+    pwm.set_top(BUZZER_TOP);
+    pwm.enable();
+    let mut buzzer = pwm.channel_a;
+    buzzer.output_to(pins.gpio16);
+
+    // The ring oscillator's jitter is random enough for games
+    let rosc = RingOscillator::new(pac.ROSC).initialize();
+
+    let mut oled = Oled(oled);
+    let mut buttons = Buttons(keys);
+    let mut timer_counter = TimerCounter(timer);
+
+    #[cfg(not(feature = "sd-card"))]
+    let (program, system) = (PROGRAM, SYSTEM);
+    #[cfg(feature = "sd-card")]
+    let mut rom = [0; RAM_SIZE - PROGRAM_ADDRESS as usize];
+    #[cfg(feature = "sd-card")]
+    let (program, system) = {
+        // Cards have to be started at 400 kHz or less
+        let spi = Spi::<_, _, _, 8>::new(
+            pac.SPI1,
+            (
+                pins.gpio27.into_function::<FunctionSpi>(),
+                pins.gpio28.into_function::<FunctionSpi>(),
+                pins.gpio26.into_function::<FunctionSpi>(),
+            ),
+        )
+        .init(
+            &mut pac.RESETS,
+            clocks.peripheral_clock.freq(),
+            400.kHz(),
+            MODE_0,
+        );
+        let spi = ExclusiveDevice::new_no_delay(spi, pins.gpio22.into_push_pull_output()).unwrap();
+        let chosen = choose_rom(
+            SdCard::new(spi, timer),
+            &mut oled,
+            &mut buttons,
+            &mut timer_counter,
+            &mut rom,
+        )
+        .unwrap();
+        (chosen.program, chosen.system)
+    };
+
+    let mut machine_state = MachineState::new(system);
+    machine_state.load_default_font();
+    machine_state.load_program(program);
+    #[cfg(feature = "defmt")]
+    defmt::info!("Running a {=usize} byte program", program.len());
+
+    // Leave the last frame on the display, and stop
+    run(
+        &mut machine_state,
+        IPF,
+        &mut oled,
+        &mut buttons,
+        &mut Buzzer(buzzer),
+        &mut Rosc(rosc),
+        &mut timer_counter,
+    );
+    loop {
+        cortex_m::asm::wfi();
+    }
+}
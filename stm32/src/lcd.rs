@@ -0,0 +1,135 @@
+use embassy_stm32::{gpio::Output, mode::Async, spi, spi::Spi};
+use embassy_time::Timer;
+use rs_chip8_core::{DISPLAY_HEIGHT, DISPLAY_WIDTH, Display};
+
+/// The LCD's size, turned on its side
+const WIDTH: usize = 320;
+const HEIGHT: usize = 240;
+/// Each of the display's pixels is drawn as a square this many LCD pixels wide
+const SCALE: usize = 2;
+/// Where the display is drawn, centred on the LCD
+const LEFT: usize = (WIDTH - DISPLAY_WIDTH * SCALE) / 2;
+const TOP: usize = (HEIGHT - DISPLAY_HEIGHT * SCALE) / 2;
+
+/// RGB565 colours of pixels that are on and off
+const ON: u16 = 0xFFFF;
+const OFF: u16 = 0x0000;
+
+const SOFTWARE_RESET: u8 = 0x01;
+const SLEEP_OUT: u8 = 0x11;
+const INVERSION_ON: u8 = 0x21;
+const DISPLAY_ON: u8 = 0x29;
+const COLUMN_ADDRESS: u8 = 0x2A;
+const ROW_ADDRESS: u8 = 0x2B;
+const MEMORY_WRITE: u8 = 0x2C;
+const MEMORY_ACCESS: u8 = 0x36;
+const PIXEL_FORMAT: u8 = 0x3A;
+
+/// A 240x320 ST7789 LCD, with its pixels sent by DMA one of the display's rows at a time
+pub struct Lcd {
+    spi: Spi<'static, Async>,
+    cs: Output<'static>,
+    dc: Output<'static>,
+    /// A row of the display, scaled up, as big endian RGB565
+    row: [u8; DISPLAY_WIDTH * SCALE * SCALE * 2],
+}
+
+impl Lcd {
+    pub async fn new(
+        spi: Spi<'static, Async>,
+        cs: Output<'static>,
+        dc: Output<'static>,
+        reset: &mut Output<'_>,
+    ) -> Result<Self, spi::Error> {
+        reset.set_low();
+        Timer::after_millis(10).await;
+        reset.set_high();
+        Timer::after_millis(120).await;
+
+        let mut lcd = Self {
+            spi,
+            cs,
+            dc,
+            row: [0; DISPLAY_WIDTH * SCALE * SCALE * 2],
+        };
+        lcd.command(SOFTWARE_RESET, &[]).await?;
+        Timer::after_millis(150).await;
+        lcd.command(SLEEP_OUT, &[]).await?;
+        Timer::after_millis(10).await;
+        // 16 bit colour
+        lcd.command(PIXEL_FORMAT, &[0x55]).await?;
+        // Swap rows and columns, and mirror columns, to turn it into landscape
+        lcd.command(MEMORY_ACCESS, &[0x60]).await?;
+        // These panels show colours inverted without this
+        lcd.command(INVERSION_ON, &[]).await?;
+        lcd.command(DISPLAY_ON, &[]).await?;
+
+        // The row buffer starts out black, so it's sent over and over to clear the LCD
+        lcd.set_window(0, 0, WIDTH, HEIGHT).await?;
+        let size = WIDTH * HEIGHT * 2;
+        for start in (0..size).step_by(lcd.row.len()) {
+            let length = lcd.row.len().min(size - start);
+            lcd.data(length).await?;
+        }
+        Ok(lcd)
+    }
+
+    async fn command(&mut self, command: u8, parameters: &[u8]) -> Result<(), spi::Error> {
+        self.cs.set_low();
+        self.dc.set_low();
+        let mut result = self.spi.write(&[command]).await;
+        if result.is_ok() && !parameters.is_empty() {
+            self.dc.set_high();
+            result = self.spi.write(parameters).await;
+        }
+        self.cs.set_high();
+        result
+    }
+
+    /// Send the first `length` bytes of the row buffer as pixels
+    async fn data(&mut self, length: usize) -> Result<(), spi::Error> {
+        self.cs.set_low();
+        self.dc.set_high();
+        let result = self.spi.write(&self.row[..length]).await;
+        self.cs.set_high();
+        result
+    }
+
+    /// Set the area the next pixels are written to, then start writing them
+    async fn set_window(
+        &mut self,
+        x: usize,
+        y: usize,
+        width: usize,
+        height: usize,
+    ) -> Result<(), spi::Error> {
+        let [left_high, left_low] = (x as u16).to_be_bytes();
+        let [right_high, right_low] = ((x + width - 1) as u16).to_be_bytes();
+        self.command(
+            COLUMN_ADDRESS,
+            &[left_high, left_low, right_high, right_low],
+        )
+        .await?;
+        let [top_high, top_low] = (y as u16).to_be_bytes();
+        let [bottom_high, bottom_low] = ((y + height - 1) as u16).to_be_bytes();
+        self.command(ROW_ADDRESS, &[top_high, top_low, bottom_high, bottom_low])
+            .await?;
+        self.command(MEMORY_WRITE, &[]).await
+    }
+
+    /// Draw the rows of the display set in `dirty_rows`, from [`rs_chip8_core::MachineState::take_dirty_rows`]
+    pub async fn draw(&mut self, display: &Display, dirty_rows: u64) -> Result<(), spi::Error> {
+        for y in (0..DISPLAY_HEIGHT).filter(|y| dirty_rows >> y & 1 == 1) {
+            for (i, pixel) in self.row.chunks_exact_mut(2).enumerate() {
+                let x = i % (DISPLAY_WIDTH * SCALE) / SCALE;
+                let colour = if display.pixel(x, y) { ON } else { OFF };
+                pixel.copy_from_slice(&colour.to_be_bytes());
+            }
+
+            self.set_window(LEFT, TOP + y * SCALE, DISPLAY_WIDTH * SCALE, SCALE)
+                .await?;
+            self.data(self.row.len()).await?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,138 @@
+//! Runs a ROM on an STM32F407 with embassy, with an ST7789 LCD and 16 buttons
+//!
+//! The ROM is built in, from the absolute path in the `CHIP8_ROM` environment variable.
+//!
+//! The display is sent to the LCD by DMA, and only the rows that changed each frame are sent.
+//! The buttons are watched by their own tasks, woken by interrupts instead of polled.
+//! While the program waits for a key, frames stop and the core sleeps until a button changes.
+//!
+//! | Part        | Pins                                              |
+//! |-------------|---------------------------------------------------|
+//! | Keys 0 to F | PE0 to PE15, each a button to ground              |
+//! | ST7789      | PA4 CS, PA5 SCK, PA7 MOSI, PB0 DC, PB1 RESET      |
+
+#![no_std]
+#![no_main]
+
+mod lcd;
+
+use core::sync::atomic::{AtomicU16, Ordering};
+use embassy_executor::Spawner;
+use embassy_stm32::{
+    bind_interrupts,
+    exti::ExtiInput,
+    gpio::{Level, Output, Pull, Speed},
+    peripherals,
+    rng::{self, Rng},
+    spi::{self, Spi},
+    time::Hertz,
+};
+use embassy_sync::{blocking_mutex::raw::CriticalSectionRawMutex, signal::Signal};
+use embassy_time::{Duration, Ticker};
+use lcd::Lcd;
+#[cfg(not(feature = "defmt"))]
+use panic_halt as _;
+use rs_chip8_core::{EmulationSystem, MachineState};
+#[cfg(feature = "defmt")]
+use {defmt_rtt as _, panic_probe as _};
+
+const PROGRAM: &[u8] = include_bytes!(env!("CHIP8_ROM"));
+const SYSTEM: EmulationSystem = EmulationSystem::SuperChip;
+const IPF: u32 = 10;
+
+/// Bit n is set while key n is held, kept up to date by the [`watch_key`] tasks
+static HELD_KEYS: AtomicU16 = AtomicU16::new(0);
+/// Signalled by the [`watch_key`] tasks whenever a key is pressed or released
+static KEY_CHANGED: Signal<CriticalSectionRawMutex, ()> = Signal::new();
+
+bind_interrupts!(struct Irqs {
+    HASH_RNG => rng::InterruptHandler<peripherals::RNG>;
+});
+
+#[embassy_executor::task(pool_size = 16)]
+async fn watch_key(key: u8, mut pin: ExtiInput<'static>) {
+    loop {
+        // Buttons pull their pin low when pressed
+        if pin.is_low() {
+            HELD_KEYS.fetch_or(1 << key, Ordering::Relaxed);
+        } else {
+            HELD_KEYS.fetch_and(!(1 << key), Ordering::Relaxed);
+        }
+        KEY_CHANGED.signal(());
+        pin.wait_for_any_edge().await;
+    }
+}
+
+#[embassy_executor::main]
+async fn main(spawner: Spawner) {
+    let p = embassy_stm32::init(Default::default());
+
+    let mut spi_config = spi::Config::default();
+    spi_config.frequency = Hertz::mhz(8);
+    let spi = Spi::new_txonly(p.SPI1, p.PA5, p.PA7, p.DMA2_CH3, spi_config);
+    let cs = Output::new(p.PA4, Level::High, Speed::VeryHigh);
+    let dc = Output::new(p.PB0, Level::Low, Speed::VeryHigh);
+    let mut reset = Output::new(p.PB1, Level::High, Speed::Low);
+    let mut lcd = Lcd::new(spi, cs, dc, &mut reset).await.unwrap();
+
+    let keys = [
+        ExtiInput::new(p.PE0, p.EXTI0, Pull::Up),
+        ExtiInput::new(p.PE1, p.EXTI1, Pull::Up),
+        ExtiInput::new(p.PE2, p.EXTI2, Pull::Up),
+        ExtiInput::new(p.PE3, p.EXTI3, Pull::Up),
+        ExtiInput::new(p.PE4, p.EXTI4, Pull::Up),
+        ExtiInput::new(p.PE5, p.EXTI5, Pull::Up),
+        ExtiInput::new(p.PE6, p.EXTI6, Pull::Up),
+        ExtiInput::new(p.PE7, p.EXTI7, Pull::Up),
+        ExtiInput::new(p.PE8, p.EXTI8, Pull::Up),
+        ExtiInput::new(p.PE9, p.EXTI9, Pull::Up),
+        ExtiInput::new(p.PE10, p.EXTI10, Pull::Up),
+        ExtiInput::new(p.PE11, p.EXTI11, Pull::Up),
+        ExtiInput::new(p.PE12, p.EXTI12, Pull::Up),
+        ExtiInput::new(p.PE13, p.EXTI13, Pull::Up),
+        ExtiInput::new(p.PE14, p.EXTI14, Pull::Up),
+        ExtiInput::new(p.PE15, p.EXTI15, Pull::Up),
+    ];
+    for (key, pin) in keys.into_iter().enumerate() {
+        spawner.spawn(watch_key(key as u8, pin)).unwrap();
+    }
+
+    let mut rng = Rng::new(p.RNG, Irqs);
+
+    let mut machine_state = MachineState::new(SYSTEM);
+    machine_state.load_default_font();
+    machine_state.load_program(PROGRAM);
+    #[cfg(feature = "defmt")]
+    defmt::info!("Running a {=usize} byte program", PROGRAM.len());
+
+    let mut ticker = Ticker::every(Duration::from_hz(60));
+    loop {
+        // Each instruction takes at most one random number, so a frame can't run out
+        let mut random = [0; IPF as usize];
+        rng.async_fill_bytes(&mut random).await.unwrap();
+        let mut random = random.into_iter();
+
+        let held_keys = HELD_KEYS.load(Ordering::Relaxed);
+        if machine_state
+            .run_frame(IPF, || held_keys, || random.next().unwrap())
+            .is_err()
+        {
+            // Leave the last frame on the display, and stop
+            break;
+        }
+
+        let dirty_rows = machine_state.take_dirty_rows();
+        lcd.draw(machine_state.display(), dirty_rows).await.unwrap();
+
+        if machine_state.is_waiting_for_key() {
+            // Nothing changes until a key does, so there's no need to wake every frame
+            KEY_CHANGED.reset();
+            if HELD_KEYS.load(Ordering::Relaxed) == held_keys {
+                KEY_CHANGED.wait().await;
+            }
+            ticker.reset();
+        }
+
+        ticker.next().await;
+    }
+}
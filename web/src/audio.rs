@@ -0,0 +1,56 @@
+use wasm_bindgen::JsValue;
+use web_sys::{AudioContext, GainNode, OscillatorType};
+
+const FREQUENCY: f32 = 440.;
+const VOLUME: f32 = 0.1;
+
+/// Time constant of the fade in and out, which avoids clicks
+const RAMP_DURATION: f64 = 0.005;
+
+/// Plays a square wave while switched on, by turning its volume up and down
+pub struct Beeper {
+    context: AudioContext,
+    gain: GainNode,
+    playing: bool,
+}
+
+impl Beeper {
+    pub fn new() -> Result<Self, JsValue> {
+        let context = AudioContext::new()?;
+
+        let gain = context.create_gain()?;
+        gain.gain().set_value(0.);
+        gain.connect_with_audio_node(&context.destination())?;
+
+        let oscillator = context.create_oscillator()?;
+        oscillator.set_type(OscillatorType::Square);
+        oscillator.frequency().set_value(FREQUENCY);
+        oscillator.connect_with_audio_node(&gain)?;
+        oscillator.start()?;
+
+        Ok(Self {
+            context,
+            gain,
+            playing: false,
+        })
+    }
+
+    /// Browsers keep audio suspended until the page is interacted with,
+    /// so this has to be called from an input event
+    pub fn resume(&self) {
+        let _ = self.context.resume();
+    }
+
+    pub fn set(&mut self, playing: bool) {
+        if playing == self.playing {
+            return;
+        }
+        self.playing = playing;
+
+        let target = if playing { VOLUME } else { 0. };
+        let _ =
+            self.gain
+                .gain()
+                .set_target_at_time(target, self.context.current_time(), RAMP_DURATION);
+    }
+}
@@ -0,0 +1,12 @@
+/// The `KeyboardEvent.code` used for each CHIP-8 key, the COSMAC VIP keypad on the 1234/QWER/ASDF/ZXCV block
+///
+/// Codes refer to physical key positions, so this is the same layout on any keyboard.
+const KEYMAP: [&str; 16] = [
+    "KeyX", "Digit1", "Digit2", "Digit3", "KeyQ", "KeyW", "KeyE", "KeyA", "KeyS", "KeyD", "KeyZ",
+    "KeyC", "Digit4", "KeyR", "KeyF", "KeyV",
+];
+
+/// The CHIP-8 key the code is mapped to
+pub fn key(code: &str) -> Option<usize> {
+    KEYMAP.iter().position(|&key| key == code)
+}
@@ -0,0 +1,233 @@
+//! Runs ROMs in a browser, drawn to a canvas, with the keyboard as the keypad
+//!
+//! Build with `wasm-pack build --target web`, then serve this directory and open `index.html`.
+//! ROMs are opened with the file picker, or fetched from the URL in the page's `rom` parameter,
+//! like `index.html?rom=roms/pong.ch8`. `.sc8` ROMs run as SUPER-CHIP, and anything else as CHIP-8.
+
+mod audio;
+mod keymap;
+mod screen;
+
+use audio::Beeper;
+use js_sys::Uint8Array;
+use rs_chip8_core::{EmulationSystem, MachineState, PROGRAM_ADDRESS, RAM_SIZE};
+use screen::Screen;
+use std::{cell::RefCell, rc::Rc};
+use wasm_bindgen::{JsCast, JsValue, prelude::*};
+use wasm_bindgen_futures::{JsFuture, spawn_local};
+use web_sys::{
+    Document, HtmlCanvasElement, HtmlInputElement, KeyboardEvent, Response, UrlSearchParams,
+    Window, console,
+};
+
+const IPF: u32 = 10;
+
+/// Length of a 60 Hz frame in milliseconds, the unit of `requestAnimationFrame`'s timestamps
+const FRAME_LENGTH: f64 = 1000. / 60.;
+
+/// Frames run in one animation frame at most, so coming back to a hidden tab doesn't catch up on
+/// every frame it missed
+const MAX_FRAMES: u32 = 4;
+
+struct App {
+    /// The running program, `None` before one is loaded and after it stops
+    machine_state: Option<MachineState>,
+    held_keys: u16,
+    screen: Screen,
+    beeper: Beeper,
+    /// When the last frame was due, from `requestAnimationFrame`
+    last_frame: Option<f64>,
+}
+
+impl App {
+    fn load(&mut self, name: &str, program: &[u8]) {
+        let max_len = RAM_SIZE - PROGRAM_ADDRESS as usize;
+        if program.len() > max_len {
+            console::error_1(
+                &format!(
+                    "{name} is too big, at {} bytes when {max_len} fit in memory",
+                    program.len()
+                )
+                .into(),
+            );
+            return;
+        }
+
+        let system = if name.to_ascii_lowercase().ends_with(".sc8") {
+            EmulationSystem::SuperChip
+        } else {
+            EmulationSystem::Chip8
+        };
+
+        let mut machine_state = MachineState::new(system);
+        machine_state.load_default_font();
+        machine_state.load_program(program);
+        self.machine_state = Some(machine_state);
+        self.last_frame = None;
+    }
+
+    /// Run the frames that are due by `now`, then draw what changed
+    fn update(&mut self, now: f64) -> Result<(), JsValue> {
+        let Some(machine_state) = &mut self.machine_state else {
+            return Ok(());
+        };
+
+        let last_frame = *self.last_frame.get_or_insert(now);
+        let frames = ((now - last_frame) / FRAME_LENGTH) as u32;
+        self.last_frame = Some(if frames > MAX_FRAMES {
+            now
+        } else {
+            last_frame + frames as f64 * FRAME_LENGTH
+        });
+
+        let held_keys = self.held_keys;
+        let mut result = Ok(());
+        for _ in 0..frames.min(MAX_FRAMES) {
+            result = machine_state.run_frame(
+                IPF,
+                || held_keys,
+                || (js_sys::Math::random() * 256.) as u8,
+            );
+            if result.is_err() {
+                break;
+            }
+        }
+
+        let dirty_rows = machine_state.take_dirty_rows();
+        self.screen.draw(machine_state.display(), dirty_rows)?;
+
+        if let Err(err) = result {
+            // Leave the last frame on the screen, and stop
+            console::error_1(&format!("The program stopped: {err}").into());
+            self.beeper.set(false);
+            self.machine_state = None;
+        } else {
+            self.beeper.set(machine_state.sound_timer() > 0);
+        }
+        Ok(())
+    }
+}
+
+#[wasm_bindgen(start)]
+pub fn start() -> Result<(), JsValue> {
+    let window = web_sys::window().ok_or("There's no window")?;
+    let document = window.document().ok_or("There's no document")?;
+    let canvas = document
+        .get_element_by_id("screen")
+        .ok_or("There's no #screen canvas")?
+        .dyn_into::<HtmlCanvasElement>()?;
+
+    let app = Rc::new(RefCell::new(App {
+        machine_state: None,
+        held_keys: 0,
+        screen: Screen::new(canvas)?,
+        beeper: Beeper::new()?,
+        last_frame: None,
+    }));
+
+    listen_for_keys(&window, &app)?;
+    listen_for_files(&document, &app)?;
+
+    if let Some(url) = UrlSearchParams::new_with_str(&window.location().search()?)?.get("rom") {
+        let app = app.clone();
+        spawn_local(async move {
+            match fetch(&url).await {
+                Ok(program) => app.borrow_mut().load(&url, &program),
+                Err(err) => console::error_2(&"Couldn't fetch the ROM:".into(), &err),
+            }
+        });
+    }
+
+    animate(&window, app)
+}
+
+fn listen_for_keys(window: &Window, app: &Rc<RefCell<App>>) -> Result<(), JsValue> {
+    for (event, pressed) in [("keydown", true), ("keyup", false)] {
+        let app = app.clone();
+        let listener = Closure::<dyn FnMut(KeyboardEvent)>::new(move |event: KeyboardEvent| {
+            let Some(key) = keymap::key(&event.code()) else {
+                return;
+            };
+            event.prevent_default();
+
+            let mut app = app.borrow_mut();
+            app.beeper.resume();
+            if pressed {
+                app.held_keys |= 1 << key;
+            } else {
+                app.held_keys &= !(1 << key);
+            }
+        });
+        window.add_event_listener_with_callback(event, listener.as_ref().unchecked_ref())?;
+        // Listeners are needed for as long as the page is open
+        listener.forget();
+    }
+    Ok(())
+}
+
+fn listen_for_files(document: &Document, app: &Rc<RefCell<App>>) -> Result<(), JsValue> {
+    let input = document
+        .get_element_by_id("rom")
+        .ok_or("There's no #rom file input")?
+        .dyn_into::<HtmlInputElement>()?;
+
+    let listener = Closure::<dyn FnMut()>::new({
+        let input = input.clone();
+        let app = app.clone();
+        move || {
+            let Some(file) = input.files().and_then(|files| files.get(0)) else {
+                return;
+            };
+            app.borrow().beeper.resume();
+
+            let app = app.clone();
+            spawn_local(async move {
+                match JsFuture::from(file.array_buffer()).await {
+                    Ok(buffer) => app
+                        .borrow_mut()
+                        .load(&file.name(), &Uint8Array::new(&buffer).to_vec()),
+                    Err(err) => console::error_2(&"Couldn't read the ROM:".into(), &err),
+                }
+            });
+        }
+    });
+    input.add_event_listener_with_callback("change", listener.as_ref().unchecked_ref())?;
+    listener.forget();
+    Ok(())
+}
+
+async fn fetch(url: &str) -> Result<Vec<u8>, JsValue> {
+    let window = web_sys::window().ok_or("There's no window")?;
+    let response = JsFuture::from(window.fetch_with_str(url))
+        .await?
+        .dyn_into::<Response>()?;
+    if !response.ok() {
+        return Err(format!("{} {}", response.status(), response.status_text()).into());
+    }
+
+    let buffer = JsFuture::from(response.array_buffer()?).await?;
+    Ok(Uint8Array::new(&buffer).to_vec())
+}
+
+/// Update the app every time the browser is about to repaint, from `requestAnimationFrame`
+fn animate(window: &Window, app: Rc<RefCell<App>>) -> Result<(), JsValue> {
+    // Each callback requests the next one, so the callback has to be able to reach itself
+    let callback = Rc::new(RefCell::new(None::<Closure<dyn FnMut(f64)>>));
+    *callback.borrow_mut() = Some(Closure::new({
+        let callback = callback.clone();
+        let window = window.clone();
+        move |now: f64| {
+            if let Err(err) = app.borrow_mut().update(now) {
+                console::error_2(&"Couldn't draw the display:".into(), &err);
+            }
+            if let Some(callback) = callback.borrow().as_ref() {
+                let _ = window.request_animation_frame(callback.as_ref().unchecked_ref());
+            }
+        }
+    }));
+
+    if let Some(callback) = callback.borrow().as_ref() {
+        window.request_animation_frame(callback.as_ref().unchecked_ref())?;
+    }
+    Ok(())
+}
@@ -0,0 +1,50 @@
+use rs_chip8_core::{DISPLAY_HEIGHT, DISPLAY_WIDTH, Display};
+use wasm_bindgen::{Clamped, JsCast, JsValue};
+use web_sys::{CanvasRenderingContext2d, HtmlCanvasElement, ImageData};
+
+/// RGBA colours of pixels that are on and off
+const ON: [u8; 4] = [0xFF, 0xFF, 0xFF, 0xFF];
+const OFF: [u8; 4] = [0x00, 0x00, 0x00, 0xFF];
+
+/// A canvas with one pixel per display pixel, scaled up by the page's CSS
+pub struct Screen {
+    context: CanvasRenderingContext2d,
+    pixels: Vec<u8>,
+}
+
+impl Screen {
+    pub fn new(canvas: HtmlCanvasElement) -> Result<Self, JsValue> {
+        canvas.set_width(DISPLAY_WIDTH as u32);
+        canvas.set_height(DISPLAY_HEIGHT as u32);
+        let context = canvas
+            .get_context("2d")?
+            .ok_or("The canvas has no 2D context")?
+            .dyn_into::<CanvasRenderingContext2d>()?;
+
+        Ok(Self {
+            context,
+            pixels: OFF.repeat(DISPLAY_WIDTH * DISPLAY_HEIGHT),
+        })
+    }
+
+    /// Draw the rows of the display set in `dirty_rows`, from [`rs_chip8_core::MachineState::take_dirty_rows`]
+    pub fn draw(&mut self, display: &Display, dirty_rows: u64) -> Result<(), JsValue> {
+        if dirty_rows == 0 {
+            return Ok(());
+        }
+
+        for y in (0..DISPLAY_HEIGHT).filter(|y| dirty_rows >> y & 1 == 1) {
+            let row = &mut self.pixels[y * DISPLAY_WIDTH * 4..(y + 1) * DISPLAY_WIDTH * 4];
+            for (x, pixel) in row.chunks_exact_mut(4).enumerate() {
+                pixel.copy_from_slice(if display.pixel(x, y) { &ON } else { &OFF });
+            }
+        }
+
+        let image_data = ImageData::new_with_u8_clamped_array_and_sh(
+            Clamped(&self.pixels),
+            DISPLAY_WIDTH as u32,
+            DISPLAY_HEIGHT as u32,
+        )?;
+        self.context.put_image_data(&image_data, 0., 0.)
+    }
+}